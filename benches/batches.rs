@@ -0,0 +1,212 @@
+// Cost of handing scan results to the list, comparing the old delivery, where
+// the scanner sent a clone of each batch and kept its own copy while the
+// listener cloned every record again into the file list and every match into
+// the filtered list, with moving each batch through the channel and keeping
+// matches as indices. Measures the time from the first batch to the last one
+// stored and the heap held once the scan is done.
+//
+//     cargo bench --bench batches
+
+#![allow(dead_code)]
+
+#[path = "../src/category.rs"]
+mod category;
+#[path = "../src/mounts.rs"]
+mod mounts;
+#[path = "../src/normalize.rs"]
+mod normalize;
+#[path = "../src/scanner.rs"]
+mod scanner;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::path::{PathBuf, MAIN_SEPARATOR_STR};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use crossbeam_channel::unbounded;
+use scanner::FileInfo;
+
+const FILE_COUNT: usize = 1_000_000;
+const BATCH_SIZE: usize = 100;
+const RUNS: usize = 5;
+
+// Counts the bytes currently allocated, so what each delivery keeps resident
+// can be read off as the difference before and after a scan
+struct Counting;
+
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+            LIVE_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+// What a scan leaves behind: the scanner's copy, if it keeps one, the file
+// list and the filtered list
+struct Delivered<F> {
+    kept: Vec<FileInfo>,
+    files: Vec<FileInfo>,
+    filtered: Vec<F>,
+}
+
+fn main() {
+    println!("{} files in batches of {}, median of {} runs\n", FILE_COUNT, BATCH_SIZE, RUNS);
+    println!("{:<12}{:>12}{:>14}{:>14}{:>10}", "delivery", "time", "resident", "per file", "matches");
+
+    let (time, bytes, matches) = measure(cloned);
+    report("cloned", time, bytes, matches);
+
+    let (time, bytes, matches) = measure(moved);
+    report("moved", time, bytes, matches);
+}
+
+// Runs a delivery `RUNS` times, dropping each result before the next
+fn measure<F>(deliver: fn() -> Delivered<F>) -> (Duration, usize, usize) {
+    let mut times = Vec::with_capacity(RUNS);
+    let mut bytes = 0;
+    let mut matches = 0;
+    for _ in 0..RUNS {
+        let before = LIVE_BYTES.load(Ordering::Relaxed);
+        let start = Instant::now();
+        let delivered = black_box(deliver());
+        times.push(start.elapsed());
+        bytes = LIVE_BYTES.load(Ordering::Relaxed) - before;
+        matches = delivered.filtered.len();
+        assert_eq!(delivered.kept.len() + delivered.files.len(), FILE_COUNT * if delivered.kept.is_empty() { 1 } else { 2 });
+        drop(delivered);
+    }
+    times.sort();
+    (times[RUNS / 2], bytes, matches)
+}
+
+// The scanner sends a clone of each batch and keeps the batch itself; the
+// listener clones every record into the file list and every match into the
+// filtered list
+fn cloned() -> Delivered<FileInfo> {
+    let (sender, receiver) = unbounded::<Vec<FileInfo>>();
+    let scanner = thread::spawn(move || {
+        let mut all_files = Vec::new();
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        for i in 0..FILE_COUNT {
+            batch.push(synthetic_file(i));
+            if batch.len() >= BATCH_SIZE {
+                let _ = sender.send(batch.clone());
+                all_files.append(&mut batch);
+            }
+        }
+        if !batch.is_empty() {
+            let _ = sender.send(batch.clone());
+            all_files.extend(batch);
+        }
+        all_files
+    });
+
+    let mut files = Vec::new();
+    let mut filtered = Vec::new();
+    for batch in receiver {
+        files.extend(batch.iter().cloned());
+        for item in &batch {
+            if matches(item) {
+                filtered.push(item.clone());
+            }
+        }
+    }
+    Delivered { kept: scanner.join().unwrap(), files, filtered }
+}
+
+// The scanner moves each batch into the channel; the listener moves it into the
+// file list and keeps the matches as indices into it
+fn moved() -> Delivered<usize> {
+    let (sender, receiver) = unbounded::<Vec<FileInfo>>();
+    let scanner = thread::spawn(move || {
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        for i in 0..FILE_COUNT {
+            batch.push(synthetic_file(i));
+            if batch.len() >= BATCH_SIZE {
+                let full = std::mem::replace(&mut batch, Vec::with_capacity(BATCH_SIZE));
+                let _ = sender.send(full);
+            }
+        }
+        if !batch.is_empty() {
+            let _ = sender.send(batch);
+        }
+    });
+
+    let mut files = Vec::new();
+    let mut filtered = Vec::new();
+    for batch in receiver {
+        let start = files.len();
+        filtered.extend(batch.iter().enumerate().filter(|(_, item)| matches(item)).map(|(i, _)| start + i));
+        files.extend(batch);
+    }
+    scanner.join().unwrap();
+    Delivered { kept: Vec::new(), files, filtered }
+}
+
+// A search left in the box while the scan runs
+fn matches(file: &FileInfo) -> bool {
+    file.name.contains("Site Plan")
+}
+
+// The same rows as the filter bench, under a root that is absolute on any host
+// so every record's path has its folders as parents
+fn synthetic_file(i: usize) -> FileInfo {
+    let kinds = ["Site Plan", "Floor Plan", "Elevation", "Section", "Detail", "Schedule", "Report", "Photo"];
+    let extensions = ["pdf", "dwg", "dxf", "docx", "xlsx", "JPG"];
+    let extension = extensions[i % extensions.len()];
+    let name = format!("{}-{:06} {}.{}", ["DWG", "DOC", "IMG"][i % 3], i, kinds[i % kinds.len()], extension);
+    let path = PathBuf::from(MAIN_SEPARATOR_STR)
+        .join("Projects")
+        .join(format!("Job {:04}", i / 2000))
+        .join(["Drawings", "Documents", "Photos", "Archive"][i / 500 % 4])
+        .join(format!("Rev {}", i / 100 % 5))
+        .join(&name);
+    FileInfo {
+        name,
+        is_dir: false,
+        is_shortcut: false,
+        size: (i as u64 * 7919) % 50_000_000,
+        allocated_size: 0,
+        modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000 + i as u64 * 60)),
+        extension: Some(extension.to_lowercase()),
+        path,
+    }
+}
+
+fn report(delivery: &str, time: Duration, bytes: usize, matches: usize) {
+    println!(
+        "{:<12}{:>12}{:>14}{:>14}{:>10}",
+        delivery,
+        ms(time),
+        format!("{:.1} MB", bytes as f64 / 1e6),
+        format!("{} bytes", bytes / FILE_COUNT),
+        matches
+    );
+}
+
+fn ms(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
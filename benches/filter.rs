@@ -0,0 +1,157 @@
+// Per-keystroke filtering cost on a synthetic 1M-file list, comparing the old
+// approach of lowercasing each name and path on every search with matching
+// against the keys `FileStore` precomputes, and with narrowing the rows to
+// check through the trigram index first.
+//
+//     cargo bench --bench filter
+
+#![allow(dead_code)]
+
+#[path = "../src/category.rs"]
+mod category;
+#[path = "../src/content.rs"]
+mod content;
+#[path = "../src/filter.rs"]
+mod filter;
+#[path = "../src/fuzzy.rs"]
+mod fuzzy;
+#[path = "../src/mounts.rs"]
+mod mounts;
+#[path = "../src/normalize.rs"]
+mod normalize;
+#[path = "../src/query.rs"]
+mod query;
+#[path = "../src/scanner.rs"]
+mod scanner;
+#[path = "../src/store.rs"]
+mod store;
+#[path = "../src/trigram.rs"]
+mod trigram;
+
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+use rayon::prelude::*;
+use filter::Filter;
+use scanner::{FileInfo, FileRecord};
+use store::FileStore;
+
+const FILE_COUNT: usize = 1_000_000;
+const RUNS: usize = 5;
+// What typing a search one key at a time sends
+const KEYSTROKES: &[&str] = &["s", "si", "sit", "site", "site ", "site p", "site pl", "site pla", "site plan"];
+
+fn main() {
+    let mut store = build_store();
+    let start = Instant::now();
+    store.set_indexed(true);
+    println!(
+        "{} files, median of {} runs per keystroke; index built in {} using {:.1} MB\n",
+        store.len(),
+        RUNS,
+        ms(start.elapsed()),
+        store.index_memory().unwrap_or(0) as f64 / 1e6
+    );
+    println!("{:<12}{:>14}{:>14}{:>14}{:>14}{:>10}", "query", "lowercasing", "keys", "keys (par)", "index", "matches");
+
+    let mut totals = [Duration::ZERO; 4];
+    for text in KEYSTROKES {
+        // Quoted, so spaces are part of the text as they were for the old search
+        let needle = text.to_lowercase();
+        let mut filter = Filter::new();
+        filter.set_search(&format!("\"{}\"", text)).unwrap();
+
+        let (before, expected) = median(|| {
+            (0..store.len())
+                .filter(|&i| lowercasing_matches(&store.entry(i), &needle))
+                .count()
+        });
+        let (after, count) = median(|| {
+            (0..store.len())
+                .filter(|&i| filter.score(&store.entry(i)).is_some())
+                .count()
+        });
+        let (parallel, _) = median(|| {
+            (0..store.len())
+                .into_par_iter()
+                .filter(|&i| filter.score(&store.entry(i)).is_some())
+                .count()
+        });
+        let (indexed, indexed_count) = median(|| match filter.candidates(&store) {
+            Some(rows) => rows.into_iter().filter(|&i| filter.score(&store.entry(i)).is_some()).count(),
+            None => (0..store.len()).filter(|&i| filter.score(&store.entry(i)).is_some()).count(),
+        });
+        assert_eq!(count, expected, "both approaches must agree on {:?}", text);
+        assert_eq!(indexed_count, expected, "the index must not change the results of {:?}", text);
+
+        println!(
+            "{:<12}{:>14}{:>14}{:>14}{:>14}{:>10}",
+            format!("{:?}", text), ms(before), ms(after), ms(parallel), ms(indexed), count
+        );
+        for (total, time) in totals.iter_mut().zip([before, after, parallel, indexed]) {
+            *total += time;
+        }
+    }
+
+    let keystrokes = KEYSTROKES.len() as u32;
+    println!(
+        "{:<12}{:>14}{:>14}{:>14}{:>14}",
+        "average",
+        ms(totals[0] / keystrokes),
+        ms(totals[1] / keystrokes),
+        ms(totals[2] / keystrokes),
+        ms(totals[3] / keystrokes)
+    );
+}
+
+// Text matching as it was before search keys: two lowercase copies per file
+fn lowercasing_matches<R: FileRecord>(file: &R, needle: &str) -> bool {
+    file.name().to_lowercase().contains(needle)
+        || file.full_path().to_string_lossy().to_lowercase().contains(needle)
+        || file.extension().is_some_and(|ext| ext.contains(needle))
+}
+
+fn build_store() -> FileStore {
+    let kinds = ["Site Plan", "Floor Plan", "Elevation", "Section", "Detail", "Schedule", "Report", "Photo"];
+    let extensions = ["pdf", "dwg", "dxf", "docx", "xlsx", "JPG"];
+    let mut store = FileStore::new();
+    for i in 0..FILE_COUNT {
+        let folder = format!(
+            "C:\\Projects\\Job {:04}\\{}\\Rev {}",
+            i / 2000,
+            ["Drawings", "Documents", "Photos", "Archive"][i / 500 % 4],
+            i / 100 % 5
+        );
+        let extension = extensions[i % extensions.len()];
+        let name = format!("{}-{:06} {}.{}", ["DWG", "DOC", "IMG"][i % 3], i, kinds[i % kinds.len()], extension);
+        let path = PathBuf::from(folder).join(&name);
+        store.push(FileInfo {
+            name,
+            is_dir: false,
+            is_shortcut: false,
+            size: (i as u64 * 7919) % 50_000_000,
+            allocated_size: 0,
+            modified: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000 + i as u64 * 60)),
+            extension: Some(extension.to_lowercase()),
+            path,
+        });
+    }
+    store
+}
+
+fn median<T>(mut run: impl FnMut() -> T) -> (Duration, T) {
+    let mut times = Vec::with_capacity(RUNS);
+    let mut result = None;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let value = black_box(run());
+        times.push(start.elapsed());
+        result = Some(value);
+    }
+    times.sort();
+    (times[RUNS / 2], result.unwrap())
+}
+
+fn ms(duration: Duration) -> String {
+    format!("{:.1} ms", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use once_cell::sync::OnceCell;
+use serde_json::Value;
+
+// What kind of file something is, as used for icons, the Category column,
+// `type:` search terms and grouping
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FileCategory {
+    Folder,
+    Shortcut,
+    Document,
+    Image,
+    Video,
+    Audio,
+    Code,
+    Archive,
+    Executable,
+    Other,
+}
+
+impl FileCategory {
+    pub const ALL: [FileCategory; 10] = [
+        FileCategory::Folder,
+        FileCategory::Shortcut,
+        FileCategory::Document,
+        FileCategory::Image,
+        FileCategory::Video,
+        FileCategory::Audio,
+        FileCategory::Code,
+        FileCategory::Archive,
+        FileCategory::Executable,
+        FileCategory::Other,
+    ];
+
+    // Lowercase name used in `type:` terms and the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            FileCategory::Folder => "folder",
+            FileCategory::Shortcut => "shortcut",
+            FileCategory::Document => "document",
+            FileCategory::Image => "image",
+            FileCategory::Video => "video",
+            FileCategory::Audio => "audio",
+            FileCategory::Code => "code",
+            FileCategory::Archive => "archive",
+            FileCategory::Executable => "executable",
+            FileCategory::Other => "other",
+        }
+    }
+
+    // Accepts the name in any case, and plurals like `images`
+    pub fn from_name(name: &str) -> Option<FileCategory> {
+        let name = name.to_lowercase();
+        let singular = name.strip_suffix('s').unwrap_or(&name);
+        FileCategory::ALL.into_iter().find(|category| category.name() == name || category.name() == singular)
+    }
+
+    // Icon for extensions the table has no more specific icon for
+    pub fn icon(self) -> &'static str {
+        match self {
+            FileCategory::Folder => "📁",
+            FileCategory::Shortcut => "🔗",
+            FileCategory::Document => "📄",
+            FileCategory::Image => "🖼️",
+            FileCategory::Video => "🎬",
+            FileCategory::Audio => "🎵",
+            FileCategory::Code => "💻",
+            FileCategory::Archive => "📦",
+            FileCategory::Executable => "⚡",
+            FileCategory::Other => "📄",
+        }
+    }
+}
+
+impl fmt::Display for FileCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.name();
+        write!(f, "{}{}", name[..1].to_uppercase(), &name[1..])
+    }
+}
+
+// Built-in extension table: category, icon and the extensions that get them
+const BUILTIN: &[(FileCategory, &str, &[&str])] = &[
+    // Documents
+    (FileCategory::Document, "📄", &["pdf"]),
+    (FileCategory::Document, "📝", &["doc", "docx"]),
+    (FileCategory::Document, "📃", &["txt", "md", "rtf"]),
+    (FileCategory::Document, "📊", &["xls", "xlsx", "csv"]),
+    (FileCategory::Document, "📽️", &["ppt", "pptx"]),
+    // Images
+    (FileCategory::Image, "🖼️", &["jpg", "jpeg", "png", "gif", "bmp", "svg", "webp"]),
+    (FileCategory::Image, "🎨", &["ico", "psd", "ai"]),
+    // Video
+    (FileCategory::Video, "🎬", &["mp4", "avi", "mkv", "mov", "wmv", "flv"]),
+    // Audio
+    (FileCategory::Audio, "🎵", &["mp3", "wav", "flac", "aac", "ogg", "wma"]),
+    // Code
+    (FileCategory::Code, "💻", &["rs", "py", "js", "ts", "cpp", "c", "h"]),
+    (FileCategory::Code, "🌐", &["html", "css", "scss", "sass"]),
+    (FileCategory::Code, "⚙️", &["json", "xml", "yaml", "toml"]),
+    (FileCategory::Code, "🗄️", &["sql", "db"]),
+    // Archives
+    (FileCategory::Archive, "📦", &["zip", "rar", "7z", "tar", "gz"]),
+    // Executables
+    (FileCategory::Executable, "⚡", &["exe", "msi", "bat", "cmd"]),
+    (FileCategory::Executable, "🔧", &["dll", "sys"]),
+    // Special
+    (FileCategory::Other, "📋", &["log"]),
+    (FileCategory::Other, "💾", &["bak", "tmp"]),
+];
+
+// Lowercase extension to category and icon
+type Table = HashMap<String, (FileCategory, &'static str)>;
+
+static TABLE: OnceCell<Table> = OnceCell::new();
+
+fn table() -> &'static Table {
+    TABLE.get_or_init(builtin_table)
+}
+
+fn builtin_table() -> Table {
+    BUILTIN.iter()
+        .flat_map(|&(category, icon, extensions)| extensions.iter().map(move |ext| (ext.to_string(), (category, icon))))
+        .collect()
+}
+
+// Category and icon for a lowercase extension; unknown extensions are `Other`
+pub fn lookup(extension: Option<&str>) -> (FileCategory, &'static str) {
+    lookup_in(table(), extension)
+}
+
+fn lookup_in(table: &Table, extension: Option<&str>) -> (FileCategory, &'static str) {
+    extension
+        .and_then(|ext| table.get(ext).copied())
+        .unwrap_or((FileCategory::Other, FileCategory::Other.icon()))
+}
+
+// Extends the built-in table from a JSON file mapping category names to lists of
+// extensions, e.g. `{ "document": ["dwg", "dxf"], "video": ["ts"] }`. Listed
+// extensions take the category's icon and override the built-in entry. Must run
+// before anything is classified; a missing file leaves the built-in table.
+pub fn load_config(path: &Path) -> Result<(), String> {
+    match read_config(path)? {
+        Some(table) => TABLE.set(table).map_err(|_| "file categories were already in use".to_string()),
+        None => Ok(()),
+    }
+}
+
+// The built-in table with the config file's entries applied, or `None` if there's no file
+fn read_config(path: &Path) -> Result<Option<Table>, String> {
+    let text = match std::fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("{}: {}", path.display(), e)),
+    };
+    let config: Value = serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let entries = config.as_object().ok_or_else(|| format!("{}: expected an object of categories", path.display()))?;
+
+    let mut table = builtin_table();
+    for (name, extensions) in entries {
+        let category = FileCategory::from_name(name)
+            .ok_or_else(|| format!("{}: unknown category '{}'", path.display(), name))?;
+        let extensions = extensions.as_array()
+            .ok_or_else(|| format!("{}: '{}' should list extensions", path.display(), name))?;
+        for ext in extensions {
+            let ext = ext.as_str()
+                .ok_or_else(|| format!("{}: '{}' should list extensions as strings", path.display(), name))?;
+            table.insert(ext.trim().trim_start_matches('.').to_lowercase(), (category, category.icon()));
+        }
+    }
+    Ok(Some(table))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn config(name: &str, text: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("file-list-categories-{}-{}.json", std::process::id(), name));
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn read(name: &str, text: &str) -> Result<Table, String> {
+        let path = config(name, text);
+        let table = read_config(&path).map(Option::unwrap);
+        std::fs::remove_file(&path).unwrap();
+        table
+    }
+
+    #[test]
+    fn builtin_lookup() {
+        assert_eq!(lookup(Some("pdf")), (FileCategory::Document, "📄"));
+        assert_eq!(lookup(Some("docx")), (FileCategory::Document, "📝"));
+        assert_eq!(lookup(Some("jpg")).0, FileCategory::Image);
+        assert_eq!(lookup(Some("7z")).0, FileCategory::Archive);
+        assert_eq!(lookup(Some("log")), (FileCategory::Other, "📋"));
+        // Unknown and missing extensions are Other
+        assert_eq!(lookup(Some("dwg")), (FileCategory::Other, FileCategory::Other.icon()));
+        assert_eq!(lookup(None), (FileCategory::Other, FileCategory::Other.icon()));
+        // Callers lowercase the extension; the table holds only lowercase keys
+        assert_eq!(lookup(Some("PDF")).0, FileCategory::Other);
+        assert!(builtin_table().keys().all(|ext| *ext == ext.to_lowercase()));
+    }
+
+    #[test]
+    fn extensions_are_classified_however_cased() {
+        use crate::scanner::{FileInfo, FileRecord};
+        for ext in ["pdf", "PDF", "Pdf"] {
+            let file = FileInfo {
+                path: PathBuf::from(format!("/work/plan.{}", ext)),
+                name: format!("plan.{}", ext),
+                is_dir: false,
+                is_shortcut: false,
+                size: 1,
+                allocated_size: 1,
+                modified: None,
+                extension: Some(ext.to_string()),
+            };
+            assert_eq!((file.category(), file.get_icon()), (FileCategory::Document, "📄"), "{}", ext);
+        }
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for category in FileCategory::ALL {
+            assert_eq!(FileCategory::from_name(category.name()), Some(category));
+            assert_eq!(FileCategory::from_name(&category.to_string()), Some(category));
+            assert_eq!(FileCategory::from_name(&category.name().to_uppercase()), Some(category));
+        }
+        assert_eq!(FileCategory::from_name("Images"), Some(FileCategory::Image));
+        assert_eq!(FileCategory::from_name("pictures"), None);
+        assert_eq!(FileCategory::Executable.to_string(), "Executable");
+    }
+
+    #[test]
+    fn config_overrides_and_adds() {
+        let table = read("overrides", r#"{ "Documents": ["DWG", ".dxf"], "video": ["ts"], "code": [] }"#).unwrap();
+        // New extensions take the category's icon, however they were written
+        assert_eq!(lookup_in(&table, Some("dwg")), (FileCategory::Document, FileCategory::Document.icon()));
+        assert_eq!(lookup_in(&table, Some("dxf")).0, FileCategory::Document);
+        // Listed built-in extensions move to the new category
+        assert_eq!(lookup_in(&table, Some("ts")), (FileCategory::Video, FileCategory::Video.icon()));
+        // Everything else keeps its built-in entry
+        assert_eq!(lookup_in(&table, Some("rs")), lookup(Some("rs")));
+        assert_eq!(lookup_in(&table, Some("pdf")), lookup(Some("pdf")));
+        assert_eq!(table.len(), builtin_table().len() + 2);
+    }
+
+    #[test]
+    fn missing_config() {
+        let path = std::env::temp_dir().join(format!("file-list-categories-{}-missing.json", std::process::id()));
+        assert_eq!(read_config(&path), Ok(None));
+        assert_eq!(load_config(&path), Ok(()));
+    }
+
+    #[test]
+    fn config_errors() {
+        let error = |name: &str, text: &str| {
+            let path = config(name, text);
+            let error = read_config(&path).unwrap_err();
+            std::fs::remove_file(&path).unwrap();
+            assert!(error.starts_with(&format!("{}: ", path.display())), "{}", error);
+            error[path.display().to_string().len() + 2..].to_string()
+        };
+        assert_eq!(error("syntax", "{ \"document\": [\"dwg\",\n  \"dxf\" }"), "expected `,` or `]` at line 2 column 9");
+        assert_eq!(error("unknown", r#"{ "drawing": ["dwg"] }"#), "unknown category 'drawing'");
+        assert_eq!(error("not_a_list", r#"{ "document": "dwg" }"#), "'document' should list extensions");
+        assert_eq!(error("not_strings", r#"{ "document": [1] }"#), "'document' should list extensions as strings");
+        assert_eq!(error("not_an_object", "[]"), "expected an object of categories");
+    }
+}
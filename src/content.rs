@@ -0,0 +1,325 @@
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use zip::ZipArchive;
+use crate::normalize;
+
+// Files larger than this are skipped rather than read
+pub const MAX_FILE_SIZE: u64 = 64 * 1024 * 1024;
+// Line numbers kept per file; a file with more matches still matches
+const MAX_LINES: usize = 1000;
+// How much of a file is checked for NUL bytes before treating it as binary
+const SNIFF_LEN: usize = 8192;
+
+// 1-based numbers of the lines in the file at `path` containing `needle`, which is
+// already in search form. Empty if nothing matches or the file is too large, binary
+// or unreadable. Word documents count paragraphs and spreadsheets count rows.
+pub fn matching_lines(path: &Path, needle: &str, fold_accents: bool) -> Vec<u32> {
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    match ext.as_deref() {
+        Some(ext @ ("docx" | "xlsx")) => office_lines(path, ext)
+            .map(|lines| search(lines.into_iter(), needle, fold_accents))
+            .unwrap_or_default(),
+        _ => search_text_file(path, needle, fold_accents).unwrap_or_default(),
+    }
+}
+
+fn search<S: AsRef<str>>(lines: impl Iterator<Item = (u32, S)>, needle: &str, fold_accents: bool) -> Vec<u32> {
+    lines
+        .filter(|(_, line)| normalize::search_form(line.as_ref(), fold_accents).contains(needle))
+        .map(|(number, _)| number)
+        .take(MAX_LINES)
+        .collect()
+}
+
+// Streams the file a line at a time, so only matching line numbers are kept
+fn search_text_file(path: &Path, needle: &str, fold_accents: bool) -> std::io::Result<Vec<u32>> {
+    let file = File::open(path)?;
+    if file.metadata()?.len() > MAX_FILE_SIZE {
+        return Ok(Vec::new());
+    }
+    let mut reader = BufReader::with_capacity(64 * 1024, file);
+    let head = reader.fill_buf()?;
+
+    // Windows tools often write UTF-16, which starts with a byte order mark
+    if let Some(big_endian) = match head {
+        [0xFF, 0xFE, ..] => Some(false),
+        [0xFE, 0xFF, ..] => Some(true),
+        _ => None,
+    } {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let units = bytes[2..].chunks_exact(2).map(|pair| {
+            let pair = [pair[0], pair[1]];
+            if big_endian { u16::from_be_bytes(pair) } else { u16::from_le_bytes(pair) }
+        });
+        let text: String = char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)).collect();
+        return Ok(search(numbered(text.lines()), needle, fold_accents));
+    }
+    if head[..head.len().min(SNIFF_LEN)].contains(&0) {
+        return Ok(Vec::new());
+    }
+
+    let mut found = Vec::new();
+    let mut line = Vec::new();
+    let mut number = 0u32;
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        number += 1;
+        let text = String::from_utf8_lossy(&line);
+        if normalize::search_form(text.trim_end_matches(['\r', '\n']), fold_accents).contains(needle) {
+            found.push(number);
+            if found.len() == MAX_LINES {
+                break;
+            }
+        }
+        line.clear();
+    }
+    Ok(found)
+}
+
+fn numbered<S>(lines: impl Iterator<Item = S>) -> impl Iterator<Item = (u32, S)> {
+    (1..).zip(lines)
+}
+
+// Plain text of a Word document's paragraphs or a workbook's rows, numbered
+fn office_lines(path: &Path, ext: &str) -> Option<Vec<(u32, String)>> {
+    let file = File::open(path).ok()?;
+    if file.metadata().ok()?.len() > MAX_FILE_SIZE {
+        return None;
+    }
+    let mut archive = ZipArchive::new(file).ok()?;
+    if ext == "docx" {
+        return Some(numbered(docx_paragraphs(&read_entry(&mut archive, "word/document.xml")?).into_iter()).collect());
+    }
+
+    // Cells mostly refer to a shared table of strings rather than holding text
+    let shared = read_entry(&mut archive, "xl/sharedStrings.xml")
+        .map(|xml| xlsx_shared_strings(&xml))
+        .unwrap_or_default();
+    let mut sheets: Vec<String> = archive.file_names()
+        .filter(|name| name.starts_with("xl/worksheets/") && name.ends_with(".xml"))
+        .map(str::to_string)
+        .collect();
+    sheets.sort();
+    // A sheet that can't be read is left out rather than losing the others
+    let mut rows = Vec::new();
+    for sheet in sheets {
+        if let Some(xml) = read_entry(&mut archive, &sheet) {
+            rows.extend(xlsx_rows(&xml, &shared));
+        }
+    }
+    Some(rows)
+}
+
+fn read_entry<R: Read + std::io::Seek>(archive: &mut ZipArchive<R>, name: &str) -> Option<String> {
+    let entry = archive.by_name(name).ok()?;
+    let mut text = String::new();
+    entry.take(MAX_FILE_SIZE).read_to_string(&mut text).ok()?;
+    Some(text)
+}
+
+fn docx_paragraphs(xml: &str) -> Vec<String> {
+    let mut paragraphs = Vec::new();
+    let mut paragraph = String::new();
+    let mut in_text = false;
+    for event in XmlEvents::new(xml) {
+        match event {
+            Xml::Open("w:t", _) => in_text = true,
+            Xml::Close("w:t") => in_text = false,
+            Xml::Open("w:tab", _) | Xml::Empty("w:tab") => paragraph.push('\t'),
+            Xml::Open("w:br", _) | Xml::Empty("w:br") => paragraph.push(' '),
+            Xml::Close("w:p") => paragraphs.push(std::mem::take(&mut paragraph)),
+            Xml::Text(text) if in_text => paragraph.push_str(&unescape(text)),
+            _ => {}
+        }
+    }
+    paragraphs
+}
+
+fn xlsx_shared_strings(xml: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut string = String::new();
+    let mut in_text = false;
+    for event in XmlEvents::new(xml) {
+        match event {
+            Xml::Open("t", _) => in_text = true,
+            Xml::Close("t") => in_text = false,
+            Xml::Close("si") => strings.push(std::mem::take(&mut string)),
+            Xml::Empty("si") => strings.push(String::new()),
+            Xml::Text(text) if in_text => string.push_str(&unescape(text)),
+            _ => {}
+        }
+    }
+    strings
+}
+
+// Each row's cell values joined by tabs, numbered as the sheet numbers them
+fn xlsx_rows(xml: &str, shared: &[String]) -> Vec<(u32, String)> {
+    let mut rows = Vec::new();
+    let mut row = (0u32, String::new());
+    let mut last_row = 0;
+    let mut cell_type = "";
+    let mut value = String::new();
+    let mut in_value = false;
+    for event in XmlEvents::new(xml) {
+        match event {
+            Xml::Open("row", tag) => {
+                last_row = attribute(tag, "r").and_then(|r| r.parse().ok()).unwrap_or(last_row + 1);
+                row = (last_row, String::new());
+            }
+            Xml::Close("row") => rows.push(std::mem::take(&mut row)),
+            Xml::Open("c", tag) => {
+                cell_type = attribute(tag, "t").unwrap_or("");
+                value.clear();
+            }
+            Xml::Open("v" | "t", _) => in_value = true,
+            Xml::Close("v" | "t") => in_value = false,
+            Xml::Text(text) if in_value => value.push_str(&unescape(text)),
+            Xml::Close("c") => {
+                let text = match cell_type {
+                    "s" => value.trim().parse::<usize>().ok().and_then(|i| shared.get(i)).map_or("", String::as_str),
+                    _ => value.as_str(),
+                };
+                if !row.1.is_empty() {
+                    row.1.push('\t');
+                }
+                row.1.push_str(text);
+            }
+            _ => {}
+        }
+    }
+    rows
+}
+
+// Just enough XML for the text in Office documents: tags by name, with their
+// attributes as raw text, and the text between them
+enum Xml<'a> {
+    Open(&'a str, &'a str),
+    Empty(&'a str),
+    Close(&'a str),
+    Text(&'a str),
+}
+
+struct XmlEvents<'a> {
+    rest: &'a str,
+}
+
+impl<'a> XmlEvents<'a> {
+    fn new(xml: &'a str) -> Self {
+        XmlEvents { rest: xml }
+    }
+}
+
+impl<'a> Iterator for XmlEvents<'a> {
+    type Item = Xml<'a>;
+
+    fn next(&mut self) -> Option<Xml<'a>> {
+        loop {
+            if self.rest.is_empty() {
+                return None;
+            }
+            if !self.rest.starts_with('<') {
+                let end = self.rest.find('<').unwrap_or(self.rest.len());
+                let (text, rest) = self.rest.split_at(end);
+                self.rest = rest;
+                return Some(Xml::Text(text));
+            }
+            let end = self.rest.find('>')?;
+            let tag = &self.rest[1..end];
+            self.rest = &self.rest[end + 1..];
+
+            // Declarations, comments and processing instructions carry no text
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                return Some(Xml::Close(name.trim()));
+            }
+            let (tag, empty) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let name = tag.split_whitespace().next().unwrap_or("");
+            return Some(if empty { Xml::Empty(name) } else { Xml::Open(name, tag) });
+        }
+    }
+}
+
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    tag.split_whitespace().skip(1).find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then(|| value.trim_matches(['"', '\'']))
+    })
+}
+
+fn unescape(text: &str) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else { break };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity.strip_prefix("#x").map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match c {
+            Some(c) => {
+                result.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                result.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    result.push_str(rest);
+    Cow::Owned(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    fn workbook(name: &str, entries: &[(&str, &[u8])]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("file-list-content-{}-{}", std::process::id(), name));
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+        for (entry, data) in entries {
+            writer.start_file(*entry, SimpleFileOptions::default()).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn unreadable_sheets_are_skipped() {
+        let shared: &[u8] = b"<sst><si><t>Pump P-101</t></si><si><t>Valve V-7</t></si></sst>";
+        let sheet: &[u8] = b"<worksheet><sheetData><row r=\"4\"><c t=\"s\"><v>0</v></c><c t=\"s\"><v>1</v></c></row></sheetData></worksheet>";
+        let path = workbook("sheets.xlsx", &[
+            ("xl/sharedStrings.xml", shared),
+            // Not UTF-8, so the sheet can't be read as text
+            ("xl/worksheets/sheet1.xml", b"<worksheet>\xff\xfe</worksheet>"),
+            ("xl/worksheets/sheet2.xml", sheet),
+        ]);
+        assert_eq!(office_lines(&path, "xlsx"), Some(vec![(4, "Pump P-101\tValve V-7".to_string())]));
+        assert_eq!(matching_lines(&path, "valve v-7", true), [4]);
+        std::fs::remove_file(path).unwrap();
+    }
+}
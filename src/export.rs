@@ -0,0 +1,546 @@
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+use crate::group::Group;
+use crate::scanner::{format_size, iso_time, serialize_path, FileInfo, FileRecord, SizeMode};
+use crate::sort::{SortContext, SortField};
+use crate::tree::html_escape;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Delimiter {
+    Comma,
+    Tab,
+}
+
+// How a delimited table is written. Fields are quoted as RFC 4180 sets out for
+// CSV, which spreadsheets also accept in tab-separated text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableFormat {
+    pub delimiter: Delimiter,
+    // Starts the text with a byte order mark, which Excel needs to read UTF-8
+    pub bom: bool,
+}
+
+impl TableFormat {
+    pub const CSV: TableFormat = TableFormat { delimiter: Delimiter::Comma, bom: false };
+    pub const TSV: TableFormat = TableFormat { delimiter: Delimiter::Tab, bom: false };
+
+    pub fn with_bom(self, bom: bool) -> Self {
+        TableFormat { bom, ..self }
+    }
+
+    // Tab-separated for .tsv, .tab and .txt files, otherwise comma-separated
+    pub fn for_path(path: &Path) -> Self {
+        let ext = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+        match ext.as_deref() {
+            Some("tsv" | "tab" | "txt") => TableFormat::TSV,
+            _ => TableFormat::CSV,
+        }
+    }
+
+    fn separator(self) -> char {
+        match self.delimiter {
+            Delimiter::Comma => ',',
+            Delimiter::Tab => '\t',
+        }
+    }
+}
+
+// What a file list can be saved as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+    Table(TableFormat),
+    Html,
+    // One JSON document with the scan summary and an array of files
+    Json,
+    // The scan summary on the first line, then one file per line
+    JsonLines,
+}
+
+impl ListFormat {
+    // By the file's extension, CSV when it's not one of the others
+    pub fn for_path(path: &Path) -> Self {
+        let ext = path.extension().map(|ext| ext.to_string_lossy().to_lowercase());
+        match ext.as_deref() {
+            Some("html" | "htm") => ListFormat::Html,
+            Some("json") => ListFormat::Json,
+            Some("jsonl" | "ndjson") => ListFormat::JsonLines,
+            _ => ListFormat::Table(TableFormat::for_path(path)),
+        }
+    }
+}
+
+// About the scan a list came from, written at the top of JSON exports
+#[derive(Debug, Clone, Serialize)]
+pub struct ScanSummary {
+    #[serde(serialize_with = "serialize_path")]
+    pub root: PathBuf,
+    // When the list was exported, in ISO 8601
+    pub exported_at: String,
+    // The search the list is filtered by; empty when it lists everything
+    pub query: String,
+    #[serde(serialize_with = "serialize_size_mode")]
+    pub size_mode: SizeMode,
+    pub scanned_files: usize,
+    pub scanned_size: u64,
+    pub skipped_folders: usize,
+    // Whether the scan had finished, and how long it took if so
+    pub complete: bool,
+    pub scan_ms: Option<u64>,
+}
+
+// The summary with the listed files' totals added, and each group's subtotal
+// when the list is grouped
+#[derive(Serialize)]
+struct ListSummary<'a> {
+    #[serde(flatten)]
+    scan: &'a ScanSummary,
+    listed_files: usize,
+    listed_size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    groups: Option<Vec<GroupTotal<'a>>>,
+}
+
+#[derive(Serialize)]
+struct GroupTotal<'a> {
+    label: &'a str,
+    files: usize,
+    size: u64,
+}
+
+// One file of a JSON export: the file's own fields plus what is worked out from them
+#[derive(Serialize)]
+struct JsonRecord<'a> {
+    // Relative to the scanned folder, with the platform's separators
+    relative_path: String,
+    #[serde(flatten)]
+    file: FileInfo,
+    category: String,
+    // The heading the file is listed under when the list is grouped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    group: Option<&'a str>,
+    // Matching line numbers when the list is a content search
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lines: Option<&'a [u32]>,
+}
+
+// The listed files as a delimited table or an HTML table, with the columns shown
+// in the window. When the list is grouped each group gets subtotal rows.
+pub struct ListExport<'a> {
+    pub columns: &'a [SortField],
+    pub context: SortContext<'a>,
+    pub groups: Option<&'a [Group]>,
+}
+
+impl ListExport<'_> {
+    // A heading row and one row per file, for spreadsheets and scripts. Sizes are
+    // given both formatted and in bytes, and dates in ISO 8601. A grouped list has
+    // a subtotal row after each group, as spreadsheet subtotals do, and a grand total.
+    pub fn to_table<R: FileRecord>(&self, rows: &[usize], file: impl Fn(usize) -> R, format: TableFormat) -> String {
+        let mut result = String::new();
+        if format.bom {
+            result.push('\u{feff}');
+        }
+        let titles = self.columns.iter().flat_map(|&column| match column {
+            SortField::Size => vec![title(column, self.context.size_mode).to_string(), format!("{} (bytes)", title(column, self.context.size_mode))],
+            _ => vec![title(column, self.context.size_mode).to_string()],
+        });
+        push_row(&mut result, titles, format);
+
+        for (group, rows) in self.sections(rows) {
+            for &index in rows {
+                let record = file(index);
+                let cells = self.columns.iter().flat_map(|&column| match column {
+                    SortField::Size => {
+                        let size = record.size_for(self.context.size_mode);
+                        vec![format_size(size), size.to_string()]
+                    }
+                    SortField::Modified => vec![record.modified().map(iso_time).unwrap_or_default()],
+                    _ => vec![self.cell(column, index, &record)],
+                });
+                push_row(&mut result, cells, format);
+            }
+            if let Some(group) = group {
+                let label = format!("{} Total ({})", group.label, count(group.rows.len()));
+                push_row(&mut result, self.table_total_cells(label, group.size), format);
+            }
+        }
+        if let Some(groups) = self.groups {
+            let size = groups.iter().map(|group| group.size).sum();
+            push_row(&mut result, self.table_total_cells(format!("Grand Total ({})", count(rows.len())), size), format);
+        }
+        result
+    }
+
+    // Writes the list in `format`, streaming JSON records as they are made.
+    // HTML pages are titled with the scanned folder.
+    pub fn write<R: FileRecord, W: Write>(&self, out: &mut W, format: ListFormat, summary: &ScanSummary, rows: &[usize], file: impl Fn(usize) -> R) -> io::Result<()> {
+        match format {
+            ListFormat::Table(format) => out.write_all(self.to_table(rows, file, format).as_bytes()),
+            ListFormat::Html => out.write_all(self.to_html(&summary.root.to_string_lossy(), rows, file).as_bytes()),
+            ListFormat::Json => self.write_json(out, summary, rows, file, false),
+            ListFormat::JsonLines => self.write_json(out, summary, rows, file, true),
+        }
+    }
+
+    // JSON is laid out with a file per line too, so it stays readable and diffable.
+    // A grouped list is written group by group, with the subtotals in the summary.
+    fn write_json<R: FileRecord, W: Write>(&self, out: &mut W, summary: &ScanSummary, rows: &[usize], file: impl Fn(usize) -> R, lines: bool) -> io::Result<()> {
+        let summary = ListSummary {
+            scan: summary,
+            listed_files: rows.len(),
+            listed_size: rows.iter().map(|&index| file(index).size_for(self.context.size_mode)).sum(),
+            groups: self.groups.map(|groups| {
+                groups.iter()
+                    .map(|group| GroupTotal { label: &group.label, files: group.rows.len(), size: group.size })
+                    .collect()
+            }),
+        };
+        if lines {
+            out.write_all(b"{\"summary\":")?;
+            serde_json::to_writer(&mut *out, &summary)?;
+            out.write_all(b"}\n")?;
+        } else {
+            out.write_all(b"{\n\"summary\": ")?;
+            serde_json::to_writer(&mut *out, &summary)?;
+            out.write_all(b",\n\"files\": [")?;
+        }
+
+        let mut first = true;
+        for (group, rows) in self.sections(rows) {
+            for &index in rows {
+                let record = file(index);
+                let full_path = record.full_path();
+                let json = JsonRecord {
+                    relative_path: full_path.strip_prefix(&summary.scan.root).unwrap_or(&full_path).to_string_lossy().to_string(),
+                    file: record.to_info(),
+                    category: record.category().to_string(),
+                    group: group.map(|group| group.label.as_str()),
+                    lines: self.context.lines.get(&index).map(Vec::as_slice),
+                };
+                if !lines {
+                    out.write_all(if first { b"\n" } else { b",\n" })?;
+                }
+                first = false;
+                serde_json::to_writer(&mut *out, &json)?;
+                if lines {
+                    out.write_all(b"\n")?;
+                }
+            }
+        }
+
+        if !lines {
+            out.write_all(b"\n]\n}\n")?;
+        }
+        Ok(())
+    }
+
+    // A standalone page with the list as a table, each group under a heading row
+    pub fn to_html<R: FileRecord>(&self, title: &str, rows: &[usize], file: impl Fn(usize) -> R) -> String {
+        let mut body = String::new();
+        body.push_str("<tr>");
+        for title in self.titles() {
+            body.push_str(&format!("<th>{}</th>", html_escape(&title)));
+        }
+        body.push_str("</tr>\n");
+
+        for (group, rows) in self.sections(rows) {
+            if let Some(group) = group {
+                body.push_str(&format!(
+                    "<tr class=\"group\"><td colspan=\"{}\">{} <span>{}</span></td></tr>\n",
+                    self.columns.len(),
+                    html_escape(&group.label),
+                    html_escape(&group.summary())
+                ));
+            }
+            for &index in rows {
+                let record = file(index);
+                body.push_str("<tr>");
+                for &column in self.columns {
+                    body.push_str(&format!("<td>{}</td>", html_escape(&self.cell(column, index, &record))));
+                }
+                body.push_str("</tr>\n");
+            }
+            if let Some(group) = group {
+                body.push_str("<tr class=\"subtotal\">");
+                for cell in self.total_cells(format!("Subtotal ({})", count(group.rows.len())), group.size) {
+                    body.push_str(&format!("<td>{}</td>", html_escape(&cell)));
+                }
+                body.push_str("</tr>\n");
+            }
+        }
+        if let Some(groups) = self.groups {
+            let size = groups.iter().map(|group| group.size).sum();
+            body.push_str("<tr class=\"subtotal\">");
+            for cell in self.total_cells(format!("Grand Total ({})", count(rows.len())), size) {
+                body.push_str(&format!("<td>{}</td>", html_escape(&cell)));
+            }
+            body.push_str("</tr>\n");
+        }
+
+        LIST_TEMPLATE
+            .replace("__TITLE__", &html_escape(title))
+            .replace("__TOTAL__", &count(rows.len()))
+            .replace("__ROWS__", &body)
+    }
+
+    fn titles(&self) -> Vec<String> {
+        self.columns.iter().map(|&column| title(column, self.context.size_mode).to_string()).collect()
+    }
+
+    // What a column shows for the file at `index`
+    fn cell<R: FileRecord>(&self, column: SortField, index: usize, file: &R) -> String {
+        match column {
+            SortField::Name => file.name().to_string(),
+            SortField::Type => file.extension().unwrap_or("File").to_uppercase(),
+            SortField::Category => file.category().to_string(),
+            SortField::Size => file.size_formatted(self.context.size_mode),
+            SortField::Modified => file.modified_formatted(),
+            SortField::Path => file.parent().to_string_lossy().to_string(),
+            SortField::Match => self.context.scores.get(index).copied().unwrap_or(0).to_string(),
+            SortField::Lines => self.context.lines.get(&index)
+                .map(|lines| lines.iter().map(u32::to_string).collect::<Vec<_>>().join(", "))
+                .unwrap_or_default(),
+        }
+    }
+
+    // A total row for a table, with the size in both of its columns
+    fn table_total_cells(&self, label: String, size: u64) -> Vec<String> {
+        let mut label = Some(label);
+        self.columns.iter()
+            .flat_map(|&column| match column {
+                SortField::Name => vec![label.take().unwrap_or_default()],
+                SortField::Size => vec![format_size(size), size.to_string()],
+                _ => vec![String::new()],
+            })
+            .collect()
+    }
+
+    // A total row: the label in the Name column and the size under Size
+    fn total_cells(&self, label: String, size: u64) -> Vec<String> {
+        let mut label = Some(label);
+        self.columns.iter()
+            .map(|&column| match column {
+                SortField::Name => label.take().unwrap_or_default(),
+                SortField::Size => format_size(size),
+                _ => String::new(),
+            })
+            .collect()
+    }
+
+    // The rows in one section per group, or all together when ungrouped
+    fn sections<'b>(&'b self, rows: &'b [usize]) -> Vec<(Option<&'b Group>, &'b [usize])> {
+        match self.groups {
+            Some(groups) => groups.iter().map(|group| (Some(group), group.rows.as_slice())).collect(),
+            None => vec![(None, rows)],
+        }
+    }
+}
+
+pub fn title(column: SortField, size_mode: SizeMode) -> &'static str {
+    match column {
+        SortField::Name => "Name",
+        SortField::Type => "Type",
+        SortField::Category => "Category",
+        SortField::Size => match size_mode {
+            SizeMode::Apparent => "Size",
+            SizeMode::Allocated => "Size on Disk",
+        },
+        SortField::Modified => "Modified",
+        SortField::Path => "Path",
+        SortField::Match => "Match",
+        SortField::Lines => "Lines",
+    }
+}
+
+fn count(files: usize) -> String {
+    format!("{} {}", files, if files == 1 { "file" } else { "files" })
+}
+
+// Column names for `--columns`, as they are written on the command line
+pub fn parse_columns(text: &str) -> Result<Vec<SortField>, String> {
+    text.split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| match name.to_lowercase().as_str() {
+            "name" => Ok(SortField::Name),
+            "type" => Ok(SortField::Type),
+            "category" => Ok(SortField::Category),
+            "size" => Ok(SortField::Size),
+            "modified" => Ok(SortField::Modified),
+            "path" => Ok(SortField::Path),
+            "lines" => Ok(SortField::Lines),
+            _ => Err(format!("Unknown column '{}'; use name, type, category, size, modified, path or lines", name)),
+        })
+        .collect()
+}
+
+fn serialize_size_mode<S: serde::Serializer>(mode: &SizeMode, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(match mode {
+        SizeMode::Apparent => "apparent",
+        SizeMode::Allocated => "allocated",
+    })
+}
+
+// Fields with the separator, quotes or line breaks are quoted, doubling any
+// quotes; rows end with CRLF
+fn push_row(out: &mut String, cells: impl IntoIterator<Item = String>, format: TableFormat) {
+    let separator = format.separator();
+    for (i, cell) in cells.into_iter().enumerate() {
+        if i > 0 {
+            out.push(separator);
+        }
+        if cell.contains([separator, '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&cell.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(&cell);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+const LIST_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>File List - __TITLE__</title>
+<style>
+body { margin: 16px; font-family: "Segoe UI", Arial, sans-serif; font-size: 13px; }
+h1 { font-size: 18px; font-weight: normal; }
+table { border-collapse: collapse; }
+th, td { padding: 3px 10px; text-align: left; border-bottom: 1px solid #eee; white-space: nowrap; }
+th { background: #f4f4f4; border-bottom: 1px solid #ccc; }
+tr.group td { padding-top: 14px; font-weight: bold; border-bottom: 1px solid #ccc; }
+tr.group span { font-weight: normal; color: #666; margin-left: 8px; }
+tr.subtotal td { font-style: italic; color: #444; border-bottom: none; }
+</style>
+</head>
+<body>
+<h1>__TITLE__ &ndash; __TOTAL__</h1>
+<table>
+__ROWS__</table>
+</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn file(path: &str, size: u64) -> FileInfo {
+        let path = PathBuf::from(path);
+        FileInfo {
+            name: path.file_name().unwrap().to_string_lossy().into(),
+            is_dir: false,
+            is_shortcut: false,
+            size,
+            allocated_size: size,
+            modified: None,
+            extension: path.extension().map(|e| e.to_string_lossy().to_lowercase()),
+            path,
+        }
+    }
+
+    fn files() -> Vec<FileInfo> {
+        vec![file("/jobs/a/plan.pdf", 2048), file("/jobs/a/site.dwg", 100), file("/jobs/b/spec.pdf", 1024)]
+    }
+
+    fn groups() -> Vec<Group> {
+        vec![
+            Group { label: "dwg".into(), rows: vec![1], size: 100 },
+            Group { label: "pdf".into(), rows: vec![0, 2], size: 3072 },
+        ]
+    }
+
+    fn summary() -> ScanSummary {
+        ScanSummary {
+            root: PathBuf::from("/jobs"),
+            exported_at: String::new(),
+            query: String::new(),
+            size_mode: SizeMode::Apparent,
+            scanned_files: 3,
+            scanned_size: 3172,
+            skipped_folders: 0,
+            complete: true,
+            scan_ms: None,
+        }
+    }
+
+    // `f` with the three files listed, grouped by extension or not
+    fn with_export<T>(grouped: bool, f: impl FnOnce(&ListExport, &[FileInfo]) -> T) -> T {
+        let files = files();
+        let groups = groups();
+        let lines = HashMap::new();
+        let export = ListExport {
+            columns: &[SortField::Name, SortField::Size],
+            context: SortContext { size_mode: SizeMode::Apparent, scores: &[], lines: &lines },
+            groups: grouped.then_some(groups.as_slice()),
+        };
+        f(&export, &files)
+    }
+
+    fn json(format: ListFormat, grouped: bool) -> Vec<serde_json::Value> {
+        let mut out = Vec::new();
+        with_export(grouped, |export, files| export.write(&mut out, format, &summary(), &[0, 1, 2], |i| files[i].clone())).unwrap();
+        match format {
+            ListFormat::JsonLines => String::from_utf8(out).unwrap().lines().map(|line| serde_json::from_str(line).unwrap()).collect(),
+            _ => vec![serde_json::from_slice(&out).unwrap()],
+        }
+    }
+
+    #[test]
+    fn grouped_tables_have_subtotals() {
+        for format in [TableFormat::CSV, TableFormat::TSV] {
+            let table = with_export(true, |export, files| export.to_table(&[0, 1, 2], |i| files[i].clone(), format));
+            let rows: Vec<Vec<&str>> = table.lines().map(|row| row.split(format.separator()).collect()).collect();
+            assert_eq!(rows[0], ["Name", "Size", "Size (bytes)"]);
+            assert_eq!(rows[1], ["site.dwg", "100 B", "100"]);
+            assert_eq!(rows[2], ["dwg Total (1 file)", "100 B", "100"]);
+            assert_eq!(rows[3][0], "plan.pdf");
+            assert_eq!(rows[4][0], "spec.pdf");
+            assert_eq!(rows[5], ["pdf Total (2 files)", "3.0 KB", "3072"]);
+            assert_eq!(rows[6], ["Grand Total (3 files)", "3.1 KB", "3172"]);
+            assert_eq!(rows.len(), 7);
+        }
+        let flat = with_export(false, |export, files| export.to_table(&[0, 1, 2], |i| files[i].clone(), TableFormat::CSV));
+        assert_eq!(flat.lines().count(), 4);
+        assert!(!flat.contains("Total"));
+    }
+
+    #[test]
+    fn grouped_html_has_subtotals() {
+        let html = with_export(true, |export, files| export.to_html("/jobs", &[0, 1, 2], |i| files[i].clone()));
+        assert!(html.contains("<td>Subtotal (1 file)</td><td>100 B</td>"));
+        assert!(html.contains("<td>Subtotal (2 files)</td><td>3.0 KB</td>"));
+        assert!(html.contains("<td>Grand Total (3 files)</td><td>3.1 KB</td>"));
+        assert!(html.find("site.dwg") < html.find("plan.pdf"));
+        let flat = with_export(false, |export, files| export.to_html("/jobs", &[0, 1, 2], |i| files[i].clone()));
+        assert!(!flat.contains("Total ("));
+    }
+
+    #[test]
+    fn grouped_json_has_subtotals() {
+        let expected = serde_json::json!([
+            { "label": "dwg", "files": 1, "size": 100 },
+            { "label": "pdf", "files": 2, "size": 3072 },
+        ]);
+        let document = &json(ListFormat::Json, true)[0];
+        assert_eq!(document["summary"]["groups"], expected);
+        let names: Vec<_> = document["files"].as_array().unwrap().iter().map(|file| (&file["name"], &file["group"])).collect();
+        assert_eq!(names[0], (&"site.dwg".into(), &"dwg".into()));
+        assert_eq!(names[2], (&"spec.pdf".into(), &"pdf".into()));
+
+        let lines = json(ListFormat::JsonLines, true);
+        assert_eq!(lines[0]["summary"]["groups"], expected);
+        assert_eq!(lines[1]["group"], "dwg");
+        assert_eq!(lines.len(), 4);
+
+        let flat = &json(ListFormat::Json, false)[0];
+        assert!(flat["summary"].get("groups").is_none());
+        assert!(flat["files"][0].get("group").is_none());
+        assert_eq!(flat["files"][0]["name"], "plan.pdf");
+    }
+}
@@ -1,95 +1,890 @@
-use crate::scanner::FileInfo;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+use crate::category::FileCategory;
+use crate::content;
+use crate::fuzzy;
+use crate::normalize;
+use crate::query::{self, Predicate, Query, QueryError, TextField};
+use crate::scanner::{FileRecord, SizeMode};
+use crate::store::FileStore;
+use crate::trigram;
 
+// The parts of each displayed field a query matched, as character (not byte)
+// ranges, sorted and non-overlapping. `path` is relative to the containing folder,
+// as the Path column shows it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatchRanges {
+    pub name: Vec<Range<usize>>,
+    pub path: Vec<Range<usize>>,
+    pub extension: Vec<Range<usize>>,
+}
+
+impl MatchRanges {
+    pub fn is_empty(&self) -> bool {
+        self.name.is_empty() && self.path.is_empty() && self.extension.is_empty()
+    }
+
+    // Splits a range over the full path between the folder and the name
+    fn add_full_path(&mut self, range: Range<usize>, folder_len: usize, name_start: usize) {
+        if range.start < folder_len {
+            self.path.push(range.start..range.end.min(folder_len));
+        }
+        if range.end > name_start {
+            self.name.push(range.start.max(name_start) - name_start..range.end - name_start);
+        }
+    }
+
+    fn normalize(&mut self) {
+        for ranges in [&mut self.name, &mut self.path, &mut self.extension] {
+            ranges.sort_by_key(|r| r.start);
+            let mut merged: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+            for range in ranges.drain(..) {
+                match merged.last_mut() {
+                    Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                    _ => merged.push(range),
+                }
+            }
+            *ranges = merged;
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
 pub struct Filter {
-    search_text: String,
-    search_lower: String,
+    query: Query,
+    size_mode: SizeMode,
+    fuzzy: bool,
+    match_accents: bool,
 }
 
 impl Filter {
     pub fn new() -> Self {
         Filter {
-            search_text: String::new(),
-            search_lower: String::new(),
+            query: Query::All,
+            size_mode: SizeMode::Apparent,
+            fuzzy: false,
+            match_accents: false,
         }
     }
 
-    pub fn set_search(&mut self, text: &str) {
-        self.search_text = text.to_string();
-        self.search_lower = text.to_lowercase();
+    // Compiles the search text once. On a parse error the previous query stays
+    // active so the list doesn't go blank while the user is mid-edit. Text terms
+    // are normalized like the search keys they're compared with, and terms that
+    // read file contents are moved last so they only run on files the rest match.
+    pub fn set_search(&mut self, text: &str) -> Result<(), QueryError> {
+        let mut query = query::parse(text)?;
+        normalize_terms(&mut query, self.fold_accents());
+        defer_content_terms(&mut query);
+        self.query = query;
+        Ok(())
     }
 
-    pub fn matches(&self, file: &FileInfo) -> bool {
-        if self.search_text.is_empty() {
-            return true;
-        }
+    // Size filters compare against the same size the list is showing
+    pub fn set_size_mode(&mut self, mode: SizeMode) {
+        self.size_mode = mode;
+    }
+
+    pub fn size_mode(&self) -> SizeMode {
+        self.size_mode
+    }
+
+    // In fuzzy mode plain text terms only need their characters to appear in
+    // order, and each match gets a relevance score
+    pub fn set_fuzzy(&mut self, fuzzy: bool) {
+        self.fuzzy = fuzzy;
+    }
+
+    // Whether `é` only matches `é`, rather than `e` too. Should agree with the
+    // store's setting so its precomputed keys can be used; takes effect at the
+    // next `set_search`.
+    pub fn set_match_accents(&mut self, match_accents: bool) {
+        self.match_accents = match_accents;
+    }
 
-        let name_lower = file.name.to_lowercase();
-        let path_lower = file.path.to_string_lossy().to_lowercase();
-        
-        if name_lower.contains(&self.search_lower) {
-            return true;
+    // Fuzzy matching always compares base letters
+    fn fold_accents(&self) -> bool {
+        !self.match_accents || self.fuzzy
+    }
+
+    // Whether this filter only keeps files that `previous` kept too, so filtering
+    // `previous`'s results gives the same list as filtering everything
+    pub fn narrows(&self, previous: &Filter) -> bool {
+        self.size_mode == previous.size_mode
+            && self.fuzzy == previous.fuzzy
+            && self.match_accents == previous.match_accents
+            && self.query.narrows(&previous.query)
+    }
+
+    // Rows of `files` that can match, ascending, narrowed down with the store's
+    // trigram index. `None` when the index can't rule anything out, which is
+    // always the case in fuzzy mode.
+    pub fn candidates(&self, files: &FileStore) -> Option<Vec<usize>> {
+        if self.fuzzy || files.match_accents() != self.match_accents {
+            return None;
         }
+        query_candidates(&self.query, files)
+    }
 
-        if path_lower.contains(&self.search_lower) {
-            return true;
+    // `None` if the file doesn't match. In fuzzy mode higher is more relevant:
+    // the scores of all matched text terms are added up, with name matches counting
+    // double. Outside fuzzy mode every match scores 0.
+    pub fn score<R: FileRecord>(&self, file: &R) -> Option<u32> {
+        self.eval(&self.query, file)
+    }
+
+    fn eval<R: FileRecord>(&self, query: &Query, file: &R) -> Option<u32> {
+        match query {
+            Query::All => Some(0),
+            Query::And(terms) => terms.iter().try_fold(0u32, |total, q| Some(total.saturating_add(self.eval(q, file)?))),
+            Query::Or(branches) => branches.iter().filter_map(|q| self.eval(q, file)).max(),
+            Query::Not(inner) => match self.eval(inner, file) {
+                Some(_) => None,
+                None => Some(0),
+            },
+            Query::Term(Predicate::Text { field, text }) if self.fuzzy => self.fuzzy_score(*field, text, file),
+            Query::Term(predicate) => self.test(predicate, file).then_some(0),
         }
+    }
 
-        if let Some(ref ext) = file.extension {
-            if ext.contains(&self.search_lower) {
-                return true;
+    // Where the query matched, for highlighting; `None` if the file doesn't match.
+    // Only terms that contributed to the match count, so excluded (`-term`) text
+    // and OR branches that failed are not reported.
+    pub fn match_ranges<R: FileRecord>(&self, file: &R) -> Option<MatchRanges> {
+        self.score(file)?;
+        let mut ranges = MatchRanges::default();
+        self.collect_ranges(&self.query, file, &mut ranges);
+        ranges.normalize();
+        Some(ranges)
+    }
+
+    // Whether matching has to read file contents, for a `contains:` term
+    pub fn searches_content(&self) -> bool {
+        reads_content(&self.query)
+    }
+
+    // Numbers of the lines that matched `contains:` terms, ascending. Like the
+    // highlights, only terms that contributed to the match count. Reads the file
+    // again, so it's only worth calling for files that matched.
+    pub fn content_lines<R: FileRecord>(&self, file: &R) -> Vec<u32> {
+        let mut lines = Vec::new();
+        self.collect_lines(&self.query, file, &mut lines);
+        lines.sort_unstable();
+        lines.dedup();
+        lines
+    }
+
+    fn collect_lines<R: FileRecord>(&self, query: &Query, file: &R, out: &mut Vec<u32>) {
+        match query {
+            Query::And(terms) => terms.iter().for_each(|q| self.collect_lines(q, file, out)),
+            Query::Or(branches) => {
+                for branch in branches.iter().filter(|q| self.eval(q, file).is_some()) {
+                    self.collect_lines(branch, file, out);
+                }
             }
+            Query::Term(Predicate::Contains(text)) => {
+                out.extend(content::matching_lines(&file.full_path(), text, self.fold_accents()));
+            }
+            Query::All | Query::Not(_) | Query::Term(_) => {}
         }
+    }
 
-        if self.search_text.starts_with('>') {
-            let size_query = self.search_text[1..].trim();
-            if let Ok(min_size) = parse_size(size_query) {
-                return file.size > min_size;
+    fn collect_ranges<R: FileRecord>(&self, query: &Query, file: &R, out: &mut MatchRanges) {
+        match query {
+            Query::All | Query::Not(_) => {}
+            Query::And(terms) => terms.iter().for_each(|q| self.collect_ranges(q, file, out)),
+            Query::Or(branches) => {
+                for branch in branches.iter().filter(|q| self.eval(q, file).is_some()) {
+                    self.collect_ranges(branch, file, out);
+                }
             }
+            Query::Term(predicate) => self.term_ranges(predicate, file, out),
         }
+    }
+
+    fn term_ranges<R: FileRecord>(&self, predicate: &Predicate, file: &R, out: &mut MatchRanges) {
+        let name = file.name();
+        let folder = file.parent().to_string_lossy();
+        let full_path = file.full_path();
+        let full = full_path.to_string_lossy();
+        let folder_len = folder.chars().count();
+        let name_start = full.chars().count() - name.chars().count();
 
-        if self.search_text.starts_with('<') {
-            let size_query = self.search_text[1..].trim();
-            if let Ok(max_size) = parse_size(size_query) {
-                return file.size < max_size;
+        match predicate {
+            Predicate::Text { field, text } if self.fuzzy => {
+                // Same choice between name and full path that `fuzzy_score` makes
+                let name_score = || fuzzy::score(text, name).map(|score| score.saturating_mul(2));
+                let use_name = match field {
+                    TextField::Any => name_score() >= fuzzy::score(text, &full),
+                    TextField::Name => true,
+                    _ => false,
+                };
+                if use_name {
+                    out.name.extend(fuzzy::positions(text, name).unwrap_or_default().into_iter().map(|i| i..i + 1));
+                } else if *field == TextField::Path {
+                    out.path.extend(fuzzy::positions(text, &folder).unwrap_or_default().into_iter().map(|i| i..i + 1));
+                } else {
+                    for i in fuzzy::positions(text, &full).unwrap_or_default() {
+                        out.add_full_path(i..i + 1, folder_len, name_start);
+                    }
+                }
+            }
+            Predicate::Text { field, text } => {
+                let needle: Vec<char> = text.chars().collect();
+                let fold = !self.match_accents;
+                match field {
+                    TextField::Any => {
+                        out.name.extend(find_all(name, &needle, fold));
+                        for range in find_all(&full, &needle, fold) {
+                            out.add_full_path(range, folder_len, name_start);
+                        }
+                        if let Some(ext) = file.extension() {
+                            out.extension.extend(find_all(ext, &needle, fold));
+                        }
+                    }
+                    TextField::Name => out.name.extend(find_all(name, &needle, fold)),
+                    TextField::Path => out.path.extend(find_all(&folder, &needle, fold)),
+                    TextField::FullPath => {
+                        for range in find_all(&full, &needle, fold) {
+                            out.add_full_path(range, folder_len, name_start);
+                        }
+                    }
+                }
+            }
+            Predicate::Pattern { field, pattern } => {
+                match field {
+                    TextField::Any => {
+                        out.name.extend(pattern.find_ranges(name).map(|r| char_range(name, r)));
+                        for range in pattern.find_ranges(&full) {
+                            out.add_full_path(char_range(&full, range), folder_len, name_start);
+                        }
+                    }
+                    TextField::Name => out.name.extend(pattern.find_ranges(name).map(|r| char_range(name, r))),
+                    TextField::Path => out.path.extend(pattern.find_ranges(&folder).map(|r| char_range(&folder, r))),
+                    TextField::FullPath => {
+                        for range in pattern.find_ranges(&full) {
+                            out.add_full_path(char_range(&full, range), folder_len, name_start);
+                        }
+                    }
+                }
+            }
+            Predicate::Extension(_) => {
+                if let Some(ext) = file.extension() {
+                    out.extension.push(0..ext.chars().count());
+                }
             }
+            Predicate::Category(_) | Predicate::Contains(_) | Predicate::Size(_) | Predicate::Modified(_) => {}
         }
+    }
 
+    fn fuzzy_score<R: FileRecord>(&self, field: TextField, text: &str, file: &R) -> Option<u32> {
+        let name_score = || fuzzy::score(text, file.name()).map(|score| score.saturating_mul(2));
+        match field {
+            TextField::Any => name_score().max(fuzzy::score(text, &file.full_path().to_string_lossy())),
+            TextField::Name => name_score(),
+            TextField::Path => fuzzy::score(text, &file.parent().to_string_lossy()),
+            TextField::FullPath => fuzzy::score(text, &file.full_path().to_string_lossy()),
+        }
+    }
 
-        false
+    fn test<R: FileRecord>(&self, predicate: &Predicate, file: &R) -> bool {
+        match predicate {
+            Predicate::Text { field, text } => {
+                let key = file.search_key(!self.match_accents);
+                match field {
+                    // The full path takes in the name too
+                    TextField::Any => {
+                        key.path_contains(text) || file.extension().is_some_and(|ext| ext.contains(text.as_str()))
+                    }
+                    TextField::Name => key.name.contains(text.as_str()),
+                    TextField::Path => key.folder().contains(text.as_str()),
+                    TextField::FullPath => key.path_contains(text),
+                }
+            }
+            Predicate::Pattern { field, pattern } => match field {
+                TextField::Any => pattern.is_match(file.name()) || pattern.is_match(&file.full_path().to_string_lossy()),
+                TextField::Name => pattern.is_match(file.name()),
+                TextField::Path => pattern.is_match(&file.parent().to_string_lossy()),
+                TextField::FullPath => pattern.is_match(&file.full_path().to_string_lossy()),
+            },
+            Predicate::Extension(extensions) => {
+                file.extension().is_some_and(|ext| extensions.iter().any(|e| e == ext))
+            }
+            Predicate::Category(categories) => categories.contains(&file.category()),
+            Predicate::Contains(text) => {
+                !file.is_dir()
+                    && file.size() <= content::MAX_FILE_SIZE
+                    && !content::matching_lines(&file.full_path(), text, self.fold_accents()).is_empty()
+            }
+            Predicate::Size(range) => range.contains(file.size_for(self.size_mode)),
+            Predicate::Modified(range) => file.modified().is_some_and(|time| range.contains(time)),
+        }
     }
 }
 
-fn parse_size(s: &str) -> Result<u64, ()> {
-    let s = s.trim().to_lowercase();
-    
-    if s.is_empty() {
-        return Err(());
+// Suggestions are cut off after this many
+const MAX_COMPLETIONS: usize = 20;
+
+// A suggestion for the word being typed: `text` replaces the query text from
+// character `start` up to the cursor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Completion {
+    pub start: usize,
+    pub text: String,
+    // Shown alongside, e.g. how many files have an extension
+    pub detail: String,
+}
+
+// Completions for the word before character `cursor` of a partly typed query:
+// field names, then values for the field being typed, drawn from the scan where
+// they can be (extensions and folder names), and size units after a number
+pub fn complete(text: &str, cursor: usize, files: &FileStore) -> Vec<Completion> {
+    let before: Vec<char> = text.chars().take(cursor).collect();
+
+    // Start of the word under the cursor; spaces inside quotes don't end it
+    let mut start = 0;
+    let mut in_quote = false;
+    for (i, &c) in before.iter().enumerate() {
+        if c == '"' {
+            in_quote = !in_quote;
+        } else if !in_quote && (c.is_whitespace() || c == '(' || c == ')') {
+            start = i + 1;
+        }
+    }
+    if before.get(start) == Some(&'-') {
+        start += 1;
     }
+    let word: String = before[start..].iter().collect();
 
-    let (number_part, unit_part) = if s.ends_with("kb") {
-        (&s[..s.len()-2], "kb")
-    } else if s.ends_with("mb") {
-        (&s[..s.len()-2], "mb")
-    } else if s.ends_with("gb") {
-        (&s[..s.len()-2], "gb")
-    } else if s.ends_with("k") {
-        (&s[..s.len()-1], "k")
-    } else if s.ends_with("m") {
-        (&s[..s.len()-1], "m")
-    } else if s.ends_with("g") {
-        (&s[..s.len()-1], "g")
-    } else {
-        (s.as_str(), "")
+    let mut completions = match word.split_once(':') {
+        Some((field, value)) => {
+            let field = field.to_lowercase();
+            if !query::FIELDS.contains(&field.as_str()) {
+                return Vec::new();
+            }
+            complete_value(&field, value, start + field.chars().count() + 1, files)
+        }
+        None if word.starts_with(['>', '<']) || word.starts_with(|c: char| c.is_ascii_digit()) => {
+            complete_size(&word, start)
+        }
+        None => {
+            let typed = word.to_lowercase();
+            query::FIELDS.iter()
+                .filter(|field| field.starts_with(&typed))
+                .map(|field| Completion { start, text: format!("{}:", field), detail: field_help(field).to_string() })
+                .collect()
+        }
     };
+    completions.truncate(MAX_COMPLETIONS);
+    completions
+}
 
-    let number: f64 = number_part.trim().parse().map_err(|_| ())?;
-
-    let multiplier = match unit_part {
-        "k" | "kb" => 1024.0,
-        "m" | "mb" => 1024.0 * 1024.0,
-        "g" | "gb" => 1024.0 * 1024.0 * 1024.0,
-        _ => 1.0,
+fn complete_value(field: &str, value: &str, value_start: usize, files: &FileStore) -> Vec<Completion> {
+    // Lists like `ext:dwg,dx` complete their last item
+    let item_start = value.rfind(',').map_or(0, |i| i + 1);
+    let item = value[item_start..].trim_start_matches('.').to_lowercase();
+    let item_position = value_start + value[..item_start].chars().count();
+    let from = |candidates: Vec<(String, String)>| -> Vec<Completion> {
+        candidates.into_iter()
+            .filter(|(candidate, _)| candidate.starts_with(&item) && *candidate != item)
+            .map(|(text, detail)| Completion { start: item_position, text, detail })
+            .collect()
     };
 
-    Ok((number * multiplier) as u64)
-}
\ No newline at end of file
+    match field {
+        "ext" | "extension" => from(
+            files.extension_counts()
+                .into_iter()
+                .map(|(ext, count)| (ext.to_string(), format!("{} {}", count, if count == 1 { "file" } else { "files" })))
+                .collect(),
+        ),
+        "type" => from(
+            FileCategory::ALL.iter()
+                .map(|category| (category.name().to_string(), String::new()))
+                .collect(),
+        ),
+        "modified" => from(
+            query::PERIODS.iter()
+                .map(|period| (period.to_string(), String::new()))
+                .collect(),
+        ),
+        "size" => complete_size(value, value_start),
+        "path" => {
+            // Folder names are matched however they're cased, and quoted if they hold spaces
+            let typed = normalize::search_form(value.trim_start_matches('"'), true);
+            let names: BTreeSet<&str> = files.folder_names()
+                .filter(|name| normalize::search_form(name, true).starts_with(&typed))
+                .take(MAX_COMPLETIONS * 10)
+                .collect();
+            names.into_iter()
+                .map(|name| {
+                    let text = if name.contains(char::is_whitespace) { format!("\"{}\"", name) } else { name.to_string() };
+                    Completion { start: value_start, text, detail: "folder".to_string() }
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+// Units for a size being typed, as in `>10` or `10mb..1g`
+fn complete_size(text: &str, text_start: usize) -> Vec<Completion> {
+    let unit_start = text.trim_end_matches(|c: char| c.is_ascii_alphabetic()).len();
+    let head = &text[..unit_start];
+    let number_start = head.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.').len()
+        .max(head.rfind("..").map_or(0, |i| i + 2));
+    if number_start == unit_start {
+        return Vec::new();
+    }
+    let number = &text[number_start..unit_start];
+    let unit = text[unit_start..].to_lowercase();
+    query::SIZE_UNITS.iter()
+        .filter(|candidate| candidate.starts_with(&unit) && **candidate != unit)
+        .map(|candidate| Completion {
+            start: text_start + text[..number_start].chars().count(),
+            text: format!("{}{}", number, candidate),
+            detail: unit_help(candidate).to_string(),
+        })
+        .collect()
+}
+
+fn field_help(field: &str) -> &'static str {
+    match field {
+        "name" => "file name contains",
+        "ext" | "extension" => "exact extension",
+        "path" => "folder path contains",
+        "type" => "file category, e.g. image",
+        "contains" => "text inside the file",
+        "size" => "size, e.g. >5mb",
+        "modified" => "date, e.g. thisweek",
+        _ => "",
+    }
+}
+
+fn unit_help(unit: &str) -> &'static str {
+    match unit {
+        "b" => "bytes",
+        "kb" => "1000 bytes",
+        "mb" => "1000 KB",
+        "gb" => "1000 MB",
+        "tb" => "1000 GB",
+        "k" | "kib" => "1024 bytes",
+        "m" | "mib" => "1024 KiB",
+        "g" | "gib" => "1024 MiB",
+        "t" | "tib" => "1024 GiB",
+        _ => "",
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    // The query can't run; the previous results stay listed
+    Error,
+    // The query runs, but probably not as intended
+    Warning,
+}
+
+// A problem with the query text at a character position
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.position + 1, self.message)
+    }
+}
+
+impl From<QueryError> for Diagnostic {
+    fn from(error: QueryError) -> Self {
+        Diagnostic { severity: Severity::Error, position: error.position, message: error.message }
+    }
+}
+
+// Everything worth pointing out about a query: the parse error if there is one,
+// otherwise terms that likely don't do what was meant, such as a misspelled field
+// (searched as plain text) or an extension no scanned file has
+pub fn diagnose(text: &str, files: &FileStore) -> Vec<Diagnostic> {
+    if let Err(error) = query::parse(text) {
+        return vec![error.into()];
+    }
+
+    let warning = |position: usize, message: String| Diagnostic { severity: Severity::Warning, position, message };
+    let mut diagnostics = Vec::new();
+    for word in query::words(text) {
+        match word.field.as_deref() {
+            Some(field @ ("ext" | "extension")) if !files.is_empty() => {
+                let mut position = word.position + field.chars().count() + 1 + word.quoted as usize;
+                for ext in word.text.split(',') {
+                    let name = ext.trim().trim_start_matches('.').to_lowercase();
+                    if !name.is_empty() && !files.has_extension(&name) {
+                        diagnostics.push(warning(position, format!("no .{} files have been found", name)));
+                    }
+                    position += ext.chars().count() + 1;
+                }
+            }
+            None if !word.quoted => {
+                if matches!(word.text.as_str(), "or" | "and" | "not") {
+                    diagnostics.push(warning(
+                        word.position,
+                        format!("'{}' is searched for as text; write {} to combine terms", word.text, word.text.to_uppercase()),
+                    ));
+                } else if let Some((field, _)) = word.text.split_once(':') {
+                    // One letter is a drive, as in `c:\projects`
+                    if field.chars().count() > 1 && field.chars().all(|c| c.is_ascii_alphabetic()) {
+                        let message = match closest_field(field) {
+                            Some(known) => format!("'{}' is not a field, so this is searched for as text; did you mean {}:?", field, known),
+                            None => format!("'{}' is not a field, so this is searched for as text", field),
+                        };
+                        diagnostics.push(warning(word.position, message));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    diagnostics
+}
+
+// The field a misspelling is most likely meant to be, if any is close
+fn closest_field(typed: &str) -> Option<&'static str> {
+    let typed = typed.to_lowercase();
+    query::FIELDS.iter()
+        .map(|&field| (edit_distance(&typed, field), field))
+        .filter(|&(distance, field)| distance <= 2 && distance < field.len())
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, field)| field)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = diagonal + (ca != cb) as usize;
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// Only plain text terms can be looked up; a NOT or any other term has to be
+// checked on every row
+fn query_candidates(query: &Query, files: &FileStore) -> Option<Vec<usize>> {
+    match query {
+        Query::And(terms) => terms.iter()
+            .filter_map(|q| query_candidates(q, files))
+            .reduce(|a, b| trigram::intersect(&a, &b)),
+        Query::Or(branches) => branches.iter()
+            .map(|q| query_candidates(q, files))
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .reduce(|a, b| trigram::union(&a, &b)),
+        Query::Term(Predicate::Text { field, text }) => files.text_candidates(*field, text),
+        _ => None,
+    }
+}
+
+fn normalize_terms(query: &mut Query, fold_accents: bool) {
+    match query {
+        Query::And(terms) | Query::Or(terms) => terms.iter_mut().for_each(|q| normalize_terms(q, fold_accents)),
+        Query::Not(inner) => normalize_terms(inner, fold_accents),
+        Query::Term(Predicate::Text { text, .. } | Predicate::Contains(text)) => {
+            *text = normalize::search_form(text, fold_accents)
+        }
+        Query::All | Query::Term(_) => {}
+    }
+}
+
+fn defer_content_terms(query: &mut Query) {
+    match query {
+        Query::And(terms) => {
+            terms.iter_mut().for_each(defer_content_terms);
+            terms.sort_by_key(reads_content);
+        }
+        Query::Or(terms) => terms.iter_mut().for_each(defer_content_terms),
+        Query::Not(inner) => defer_content_terms(inner),
+        Query::All | Query::Term(_) => {}
+    }
+}
+
+fn reads_content(query: &Query) -> bool {
+    match query {
+        Query::And(terms) | Query::Or(terms) => terms.iter().any(reads_content),
+        Query::Not(inner) => reads_content(inner),
+        Query::Term(predicate) => matches!(predicate, Predicate::Contains(_)),
+        Query::All => false,
+    }
+}
+
+// Non-overlapping occurrences of a normalized `needle`, as character ranges of the
+// original text. Normalizes one character at a time so positions can be mapped back.
+fn find_all(haystack: &str, needle: &[char], fold_accents: bool) -> Vec<Range<usize>> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let folded = normalize::search_chars(haystack, fold_accents);
+    let total = haystack.chars().count();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i + needle.len() <= folded.len() {
+        if folded[i..i + needle.len()].iter().map(|&(c, _)| c).eq(needle.iter().copied()) {
+            // Up to the next kept character, so accents folded away stay highlighted
+            let last = folded[i + needle.len() - 1].1;
+            let next = folded.get(i + needle.len()).map_or(total, |&(_, origin)| origin);
+            ranges.push(folded[i].1..(last + 1).max(next));
+            i += needle.len();
+        } else {
+            i += 1;
+        }
+    }
+    ranges
+}
+
+// Character `ranges` of `text` as ranges of UTF-16 code units, which is how
+// Windows measures and draws text
+pub fn utf16_ranges(text: &str, ranges: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    let mut units = 0;
+    for c in text.chars() {
+        offsets.push(units);
+        units += c.len_utf16();
+    }
+    offsets.push(units);
+    ranges.iter()
+        .map(|range| offsets[range.start.min(offsets.len() - 1)]..offsets[range.end.min(offsets.len() - 1)])
+        .collect()
+}
+
+fn char_range(text: &str, bytes: Range<usize>) -> Range<usize> {
+    let start = text[..bytes.start].chars().count();
+    start..start + text[bytes].chars().count()
+}
+
+#[cfg(test)]
+// Expected ranges are written as arrays of ranges, often with just one
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::*;
+    use crate::scanner::FileInfo;
+    use std::path::PathBuf;
+
+    fn file(path: &str) -> FileInfo {
+        let path = PathBuf::from(path);
+        FileInfo {
+            name: path.file_name().unwrap().to_string_lossy().into(),
+            is_dir: false,
+            is_shortcut: false,
+            size: 1,
+            allocated_size: 1,
+            modified: None,
+            extension: path.extension().map(|e| e.to_string_lossy().to_lowercase()),
+            path,
+        }
+    }
+
+    fn ranges(search: &str, path: &str) -> MatchRanges {
+        let mut filter = Filter::new();
+        filter.set_search(search).unwrap();
+        filter.match_ranges(&file(path)).unwrap()
+    }
+
+    fn slices(text: &str, ranges: &[Range<usize>]) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        ranges.iter().map(|range| chars[range.clone()].iter().collect()).collect()
+    }
+
+    #[test]
+    fn substrings() {
+        let found = ranges("plan", "/work/plans/Site Plan.pdf");
+        assert_eq!(found.name, [5..9]);
+        assert_eq!(found.path, [6..10]);
+        assert!(found.extension.is_empty());
+
+        let found = ranges("pdf", "/work/plans/Site Plan.pdf");
+        assert_eq!(found.name, [10..13]);
+        assert_eq!(found.extension, [0..3]);
+
+        // A term spanning the folder and the name is split between both
+        let found = ranges("plans/site", "/work/plans/Site Plan.pdf");
+        assert_eq!(found.path, [6..11]);
+        assert_eq!(found.name, [0..4]);
+
+        // Excluded terms and failed alternatives are not highlighted
+        let found = ranges("plan -draft OR nothing", "/work/Site Plan.pdf");
+        assert_eq!(found.name, [5..9]);
+        assert!(ranges("site name:plan", "/work/Site Plan.pdf").path.is_empty());
+        let mut filter = Filter::new();
+        filter.set_search("missing").unwrap();
+        assert!(filter.match_ranges(&file("/work/Site Plan.pdf")).is_none());
+    }
+
+    #[test]
+    fn globs() {
+        assert_eq!(ranges("*.pdf", "/work/Site Plan.pdf").name, [0..13]);
+        assert_eq!(ranges("name:s*f", "/work/Site Plan.pdf").name, [0..13]);
+        // A path glob covers the folders it names, from the separator before them, and the name
+        let found = ranges("path:pl?ns/*.pdf", "/work/plans/Site Plan.pdf");
+        assert_eq!(found.path, [5..11]);
+        assert_eq!(found.name, [0..13]);
+    }
+
+    #[test]
+    fn fuzzy() {
+        let mut filter = Filter::new();
+        filter.set_fuzzy(true);
+        filter.set_search("sitpln").unwrap();
+        let name = "Site Plan.pdf";
+        let found = filter.match_ranges(&file("/work/Site Plan.pdf")).unwrap();
+        assert_eq!(slices(name, &found.name).concat().to_lowercase(), "sitpln");
+        assert!(found.name.windows(2).all(|pair| pair[0].end < pair[1].start));
+    }
+
+    #[test]
+    fn accent_folding() {
+        // Both the composed and the decomposed name highlight the whole word
+        let composed = "R\u{e9}sum\u{e9}.pdf";
+        let decomposed = "Re\u{301}sume\u{301}.pdf";
+        assert_eq!(ranges("resume", &format!("/cv/{}", composed)).name, [0..6]);
+        assert_eq!(ranges("resume", &format!("/cv/{}", decomposed)).name, [0..8]);
+        assert_eq!(ranges("r\u{e9}sum\u{e9}", &format!("/cv/{}", decomposed)).name, [0..8]);
+        assert_eq!(slices(decomposed, &[0..8]), ["Re\u{301}sume\u{301}"]);
+
+        let mut filter = Filter::new();
+        filter.set_match_accents(true);
+        filter.set_search("resume").unwrap();
+        assert!(filter.match_ranges(&file(&format!("/cv/{}", composed))).is_none());
+        filter.set_search("r\u{e9}sum\u{e9}").unwrap();
+        assert_eq!(filter.match_ranges(&file(&format!("/cv/{}", composed))).unwrap().name, [0..6]);
+    }
+
+    #[test]
+    fn non_ascii_offsets() {
+        // Ranges count characters; UTF-8 and UTF-16 offsets differ from them
+        let name = "\u{1f4d0} \u{8a2d}\u{8a08} plan.dwg";
+        let found = ranges("plan", &format!("/work/{}", name));
+        assert_eq!(found.name, [5..9]);
+        assert_eq!(slices(name, &found.name), ["plan"]);
+        assert_eq!(name.find("plan"), Some(12));
+        assert_eq!(utf16_ranges(name, &found.name), [6..10]);
+
+        // Glob matches are found on bytes and reported as characters
+        let found = ranges("*\u{8a08}*", &format!("/work/{}", name));
+        assert_eq!(found.name, [0..13]);
+        assert_eq!(utf16_ranges(name, &found.name), [0..14]);
+        assert_eq!(ranges("\u{8a2d}\u{8a08}", &format!("/work/{}", name)).name, [2..4]);
+        assert_eq!(utf16_ranges(name, &[2..4]), [3..5]);
+        assert_eq!(utf16_ranges(name, &[0..1, 1..2]), [0..2, 2..3]);
+    }
+
+    fn scanned() -> FileStore {
+        let mut store = FileStore::new();
+        store.extend(["/work/Site Plans/a.dwg", "/work/Site Plans/b.dwg", "/work/Sections/c.dxf", "/work/notes.docx"].map(file));
+        store
+    }
+
+    fn completions(text: &str, cursor: usize) -> Vec<(usize, String)> {
+        complete(text, cursor, &scanned()).into_iter().map(|c| (c.start, c.text)).collect()
+    }
+
+    #[test]
+    fn completes_fields_and_values() {
+        let files = scanned();
+        let found = complete("na", 2, &files);
+        assert_eq!((found[0].start, found[0].text.as_str(), found[0].detail.as_str()), (0, "name:", "file name contains"));
+        // Extensions come from the scan, with how many files have each
+        let found = complete("ext:d", 5, &files);
+        assert_eq!(
+            found.iter().map(|c| (c.start, c.text.as_str(), c.detail.as_str())).collect::<Vec<_>>(),
+            [(4, "dwg", "2 files"), (4, "docx", "1 file"), (4, "dxf", "1 file")]
+        );
+        assert_eq!(completions("ext:dwg,dx", 10), [(8, "dxf".to_string())]);
+        // Folder names replace the whole value, quoted when they hold spaces
+        assert_eq!(completions("path:s", 6), [(5, "Sections".to_string()), (5, "\"Site Plans\"".to_string())]);
+        assert_eq!(completions("type:ima", 8), [(5, "image".to_string())]);
+        assert!(completions("nme:pl", 6).is_empty());
+    }
+
+    #[test]
+    fn completes_size_units() {
+        let found = complete(">10m", 4, &scanned());
+        assert_eq!(
+            found.iter().map(|c| (c.start, c.text.as_str(), c.detail.as_str())).collect::<Vec<_>>(),
+            [(1, "10mb", "1000 KB"), (1, "10mib", "1024 KiB")]
+        );
+        // The upper end of a range completes on its own
+        assert_eq!(completions("size:1..5g", 10), [(8, "5gb".to_string()), (8, "5gib".to_string())]);
+        assert!(completions(">", 1).is_empty());
+    }
+
+    #[test]
+    fn completes_at_the_cursor() {
+        // Only the word before the cursor counts; what follows it is left alone
+        assert_eq!(completions("report ty and", 9), [(7, "type:".to_string())]);
+        assert_eq!(completions("name:x ext:dw size:1", 13), [(11, "dwg".to_string())]);
+        assert_eq!(completions("(-pa", 4), [(2, "path:".to_string())]);
+        // The cursor counts characters, not bytes
+        assert_eq!(completions("\u{e9}t\u{e9} ex", 6), [(4, "ext:".to_string()), (4, "extension:".to_string())]);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let files = scanned();
+        let found = diagnose("report nme:plan", &files);
+        assert_eq!(found.len(), 1);
+        assert_eq!((found[0].severity, found[0].position), (Severity::Warning, 7));
+        assert_eq!(found[0].message, "'nme' is not a field, so this is searched for as text; did you mean name:?");
+
+        let found = diagnose("size:>10zb", &files);
+        assert_eq!(found, [Diagnostic {
+            severity: Severity::Error,
+            position: 6,
+            message: "'10zb' is not a size (try 10mb, 4MiB or 500kb)".to_string(),
+        }]);
+
+        let found = diagnose("(a OR b", &files);
+        assert_eq!(found, [Diagnostic { severity: Severity::Error, position: 0, message: "missing closing ')'".to_string() }]);
+        assert_eq!(found[0].to_string(), "column 1: missing closing ')'");
+
+        let found = diagnose("ext:dwg,pdf", &files);
+        assert_eq!(found, [Diagnostic {
+            severity: Severity::Warning,
+            position: 8,
+            message: "no .pdf files have been found".to_string(),
+        }]);
+        assert_eq!(diagnose("plan or site", &files)[0].position, 5);
+    }
+
+    #[test]
+    fn valid_queries_have_no_diagnostics() {
+        let files = scanned();
+        for query in ["site OR ext:dwg", "name:plan ext:dwg,dxf size:1mb..5mb modified:today", "c:\\work -(a OR b)", ""] {
+            assert!(diagnose(query, &files).is_empty(), "{}", query);
+        }
+        // Before a scan there are no extensions to check against
+        assert!(diagnose("ext:pdf", &FileStore::new()).is_empty());
+    }
+
+    #[test]
+    fn undated_files() {
+        // Files without a modified time match no date term, so only excluding one finds them
+        let undated = file("/work/plan.pdf");
+        let mut dated = file("/work/old.pdf");
+        dated.modified = Some(std::time::SystemTime::now() - std::time::Duration::from_secs(60 * 24 * 60 * 60));
+        let mut filter = Filter::new();
+        for search in ["modified:today", "modified:<30d", "modified:>30d", "modified:2000..", "modified:30d..7d"] {
+            filter.set_search(search).unwrap();
+            assert!(filter.match_ranges(&undated).is_none(), "{}", search);
+        }
+        filter.set_search("modified:>30d").unwrap();
+        assert!(filter.match_ranges(&dated).is_some());
+        filter.set_search("-modified:today").unwrap();
+        assert!(filter.match_ranges(&undated).is_some());
+    }
+}
@@ -0,0 +1,160 @@
+// fzf-style fuzzy matching: the pattern's characters must appear in order, and
+// the score rewards runs of consecutive characters and matches at word starts
+// while penalising the gaps between them.
+
+use crate::normalize;
+
+const SCORE_MATCH: i32 = 16;
+const PENALTY_GAP_START: i32 = 3;
+const PENALTY_GAP_EXTENSION: i32 = 1;
+const BONUS_PATH_SEPARATOR: i32 = 10;
+const BONUS_BOUNDARY: i32 = 8;
+const BONUS_CAMEL_CASE: i32 = 7;
+const BONUS_CONSECUTIVE: i32 = 4;
+// The first pattern character counts double, so `dwg` prefers `DWG-101` over `old-dwg`
+const FIRST_CHAR_MULTIPLIER: i32 = 2;
+
+// Scores `text` against `pattern`; `None` if the pattern's characters don't all
+// appear in order. Case and accents are ignored on both sides.
+pub fn score(pattern: &str, text: &str) -> Option<u32> {
+    best_match(pattern, text).map(|(score, _)| score)
+}
+
+// Character indices in `text` of the letters that `score` matched
+pub fn positions(pattern: &str, text: &str) -> Option<Vec<usize>> {
+    best_match(pattern, text).map(|(_, positions)| positions)
+}
+
+fn best_match(pattern: &str, text: &str) -> Option<(u32, Vec<usize>)> {
+    // Both sides in the accent-folded search form, as substring search compares
+    // them; each folded text character keeps the index of the one it came from
+    let pattern: Vec<char> = normalize::search_form(pattern, true).chars().collect();
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let folded = normalize::search_chars(text, true);
+    let text: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = folded.iter().map(|&(c, _)| c).collect();
+
+    // Earliest end of a match, then walk back from it to the latest start, which
+    // gives the tightest window to score
+    let mut next = 0;
+    let mut end = None;
+    for (i, &c) in lower.iter().enumerate() {
+        if c == pattern[next] {
+            next += 1;
+            if next == pattern.len() {
+                end = Some(i);
+                break;
+            }
+        }
+    }
+    let end = end?;
+
+    let mut remaining = pattern.len();
+    let mut start = end;
+    for i in (0..=end).rev() {
+        if lower[i] == pattern[remaining - 1] {
+            remaining -= 1;
+            if remaining == 0 {
+                start = i;
+                break;
+            }
+        }
+    }
+
+    let mut total = 0;
+    let mut positions: Vec<usize> = Vec::with_capacity(pattern.len());
+    let mut next = 0;
+    let mut in_gap = false;
+    // Characters in a consecutive run inherit the bonus of the run's first character
+    let mut run_bonus = None;
+    for i in start..=end {
+        if next < pattern.len() && lower[i] == pattern[next] {
+            let original = folded[i].1;
+            // Word boundaries are judged on the original characters; the parts of
+            // one character, like the letters of a ligature, are never a boundary
+            let previous = i.checked_sub(1).map(|p| folded[p].1);
+            let mut bonus = match previous {
+                Some(p) if p == original => 0,
+                _ => boundary_bonus(previous.map(|p| text[p]), text[original]),
+            };
+            match run_bonus {
+                Some(first) => bonus = bonus.max(first).max(BONUS_CONSECUTIVE),
+                None => run_bonus = Some(bonus),
+            }
+            let weighted = if next == 0 { bonus * FIRST_CHAR_MULTIPLIER } else { bonus };
+            total += SCORE_MATCH + weighted;
+            if positions.last() != Some(&original) {
+                positions.push(original);
+            }
+            next += 1;
+            in_gap = false;
+        } else {
+            total -= if in_gap { PENALTY_GAP_EXTENSION } else { PENALTY_GAP_START };
+            in_gap = true;
+            run_bonus = None;
+        }
+    }
+
+    Some((total.max(0) as u32, positions))
+}
+
+fn boundary_bonus(previous: Option<char>, current: char) -> i32 {
+    match previous {
+        None => BONUS_PATH_SEPARATOR,
+        Some('/') | Some('\\') => BONUS_PATH_SEPARATOR,
+        Some(p) if p.is_whitespace() || matches!(p, '_' | '-' | '.' | '(' | '[') => BONUS_BOUNDARY,
+        Some(p) if p.is_lowercase() && current.is_uppercase() => BONUS_CAMEL_CASE,
+        Some(p) if !p.is_numeric() && current.is_numeric() => BONUS_CAMEL_CASE,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score_of(pattern: &str, text: &str) -> u32 {
+        score(pattern, text).unwrap_or_else(|| panic!("{} doesn't match {}", pattern, text))
+    }
+
+    #[test]
+    fn word_starts_rank_higher() {
+        // Letters starting words beat the same letters inside them
+        assert!(score("sp", "Site Plan.pdf") > score("sp", "asxxpx.pdf"));
+        assert!(score("fb", "FooBar") > score("fb", "foobar"));
+        assert!(score("dwg", "DWG-101.pdf") > score("dwg", "old-dwg.pdf"));
+    }
+
+    #[test]
+    fn consecutive_letters_rank_higher() {
+        assert!(score("plan", "Site Plan.pdf") > score("plan", "xplxaxn"));
+        assert!(score("sitepl", "Site Plan.pdf") > score("sitepl", "Sxixtxe plan"));
+        assert!(score("sp", "sp.pdf") > score("sp", "Site Plan.pdf"));
+        assert_eq!(positions("sitepl", "Site Plan.pdf"), Some(vec![0, 1, 2, 3, 5, 6]));
+    }
+
+    #[test]
+    fn letters_out_of_order_dont_match() {
+        assert_eq!(score("zz", "Site Plan"), None);
+        assert_eq!(score("plna", "plan"), None);
+        assert_eq!(positions("plna", "plan"), None);
+        assert_eq!(score("", "anything"), Some(0));
+    }
+
+    #[test]
+    fn case_and_accents_as_in_substring_search() {
+        let plain = score_of("resume", "resume.pdf");
+        assert_eq!(score_of("RESUME", "resume.pdf"), plain);
+        assert_eq!(score_of("resume", "R\u{e9}sum\u{e9}.pdf"), plain);
+        assert_eq!(score_of("r\u{e9}sum\u{e9}", "RESUME.pdf"), plain);
+        // Decomposed accents aren't gaps, and map back onto the letters they follow
+        assert_eq!(score_of("resume", "Re\u{301}sume\u{301}.pdf"), plain);
+        assert_eq!(positions("resume", "Re\u{301}sume\u{301}.pdf"), Some(vec![0, 1, 3, 4, 5, 6]));
+        // Compatibility forms fold as well, on either side
+        assert_eq!(positions("file", "\u{fb01}le"), Some(vec![0, 1, 2]));
+        assert_eq!(positions("\u{fb01}le", "file"), Some(vec![0, 1, 2, 3]));
+        assert!(score("pdf", "\u{ff30}\u{ff24}\u{ff26}").is_some());
+    }
+}
@@ -0,0 +1,346 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use crate::category::FileCategory;
+use crate::scanner::{format_size, FileRecord, SizeMode};
+use crate::sort::{self, SortField};
+
+// What the listed files can be gathered into groups by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    Folder,
+    Extension,
+    Category,
+    Month,
+}
+
+impl GroupBy {
+    pub const ALL: [GroupBy; 4] = [GroupBy::Folder, GroupBy::Extension, GroupBy::Category, GroupBy::Month];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GroupBy::Folder => "Folder",
+            GroupBy::Extension => "Extension",
+            GroupBy::Category => "Category",
+            GroupBy::Month => "Modified Month",
+        }
+    }
+
+    // The column whose sort direction also orders the groups
+    pub fn sort_field(self) -> SortField {
+        match self {
+            GroupBy::Folder => SortField::Path,
+            GroupBy::Extension => SortField::Type,
+            GroupBy::Category => SortField::Category,
+            GroupBy::Month => SortField::Modified,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum GroupKey {
+    Folder(PathBuf),
+    Extension(Option<String>),
+    Category(FileCategory),
+    Month(Option<(i32, u32)>),
+}
+
+impl GroupKey {
+    fn of<R: FileRecord>(by: GroupBy, file: &R) -> GroupKey {
+        match by {
+            GroupBy::Folder => GroupKey::Folder(file.parent().to_path_buf()),
+            GroupBy::Extension => GroupKey::Extension(file.extension().map(str::to_lowercase)),
+            GroupBy::Category => GroupKey::Category(file.category()),
+            GroupBy::Month => GroupKey::Month(month(file)),
+        }
+    }
+
+    // Whether `file` belongs under this key, without building a key for it
+    fn holds<R: FileRecord>(&self, file: &R) -> bool {
+        match self {
+            GroupKey::Folder(folder) => folder == file.parent(),
+            GroupKey::Extension(ext) => match (ext, file.extension()) {
+                (Some(ext), Some(other)) => ext.eq_ignore_ascii_case(other),
+                (None, None) => true,
+                _ => false,
+            },
+            GroupKey::Category(category) => *category == file.category(),
+            GroupKey::Month(key) => *key == month(file),
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            GroupKey::Folder(folder) => folder.to_string_lossy().to_string(),
+            GroupKey::Extension(Some(ext)) => ext.to_uppercase(),
+            GroupKey::Extension(None) => "No extension".to_string(),
+            GroupKey::Category(category) => category.to_string(),
+            GroupKey::Month(Some((year, month))) => NaiveDate::from_ymd_opt(*year, *month, 1)
+                .map(|date| date.format("%B %Y").to_string())
+                .unwrap_or_default(),
+            GroupKey::Month(None) => "No date".to_string(),
+        }
+    }
+
+    // Groups without an extension or a date come after the rest
+    fn order(&self, other: &GroupKey) -> Ordering {
+        match (self, other) {
+            (GroupKey::Folder(a), GroupKey::Folder(b)) => sort::natural_cmp(&a.to_string_lossy(), &b.to_string_lossy()),
+            (GroupKey::Extension(a), GroupKey::Extension(b)) => last_if_none(a, b),
+            (GroupKey::Category(a), GroupKey::Category(b)) => a.cmp(b),
+            (GroupKey::Month(a), GroupKey::Month(b)) => last_if_none(a, b),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+fn last_if_none<T: Ord>(a: &Option<T>, b: &Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+// Local year and month the file was last modified in
+fn month<R: FileRecord>(file: &R) -> Option<(i32, u32)> {
+    let local = DateTime::<Local>::from(file.modified()?);
+    Some((local.year(), local.month()))
+}
+
+// One group of listed files, with the count and size subtotals shown in its heading
+pub struct Group {
+    pub label: String,
+    pub rows: Vec<usize>,
+    pub size: u64,
+}
+
+impl Group {
+    // "12 files, 3.4 MB"
+    pub fn summary(&self) -> String {
+        let count = self.rows.len();
+        format!("{} {}, {}", count, if count == 1 { "file" } else { "files" }, format_size(self.size))
+    }
+}
+
+// Splits `rows` of file indexes into groups ordered by their key, reversed if
+// `descending`. Files keep their order from `rows` within each group, so the
+// list's sort still applies inside groups.
+pub fn group_rows<R: FileRecord>(rows: &[usize], by: GroupBy, file: impl Fn(usize) -> R, size_mode: SizeMode, descending: bool) -> Vec<Group> {
+    let mut keys: Vec<GroupKey> = Vec::new();
+    let mut groups: Vec<Group> = Vec::new();
+    let mut slots: HashMap<GroupKey, usize> = HashMap::new();
+    // Neighbouring rows usually share a group, which saves building their keys
+    let mut last: Option<usize> = None;
+    for &row in rows {
+        let record = file(row);
+        let slot = match last {
+            Some(slot) if keys[slot].holds(&record) => slot,
+            _ => *slots.entry(GroupKey::of(by, &record)).or_insert_with_key(|key| {
+                keys.push(key.clone());
+                groups.push(Group { label: key.label(), rows: Vec::new(), size: 0 });
+                groups.len() - 1
+            }),
+        };
+        groups[slot].rows.push(row);
+        groups[slot].size += record.size_for(size_mode);
+        last = Some(slot);
+    }
+
+    let mut order: Vec<usize> = (0..groups.len()).collect();
+    order.sort_by(|&a, &b| {
+        let order = keys[a].order(&keys[b]);
+        if descending { order.reverse() } else { order }
+    });
+    let mut groups: Vec<Option<Group>> = groups.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|slot| groups[slot].take()).collect()
+}
+
+// A row of the grouped list: a group's heading or one of its files
+pub enum ListRow<'a> {
+    Heading(&'a Group),
+    File(usize),
+}
+
+// Groups laid out as list rows, each heading followed by the group's files
+pub struct GroupedRows {
+    groups: Vec<Group>,
+    // Row each group's heading is on
+    starts: Vec<usize>,
+    len: usize,
+}
+
+impl GroupedRows {
+    pub fn new(groups: Vec<Group>) -> Self {
+        let mut starts = Vec::with_capacity(groups.len());
+        let mut len = 0;
+        for group in &groups {
+            starts.push(len);
+            len += 1 + group.rows.len();
+        }
+        GroupedRows { groups, starts, len }
+    }
+
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn row(&self, row: usize) -> Option<ListRow<'_>> {
+        if row >= self.len {
+            return None;
+        }
+        let group = self.starts.partition_point(|&start| start <= row) - 1;
+        match row - self.starts[group] {
+            0 => Some(ListRow::Heading(&self.groups[group])),
+            offset => Some(ListRow::File(self.groups[group].rows[offset - 1])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use chrono::TimeZone;
+    use crate::scanner::FileInfo;
+    use crate::store::FileStore;
+
+    fn file(path: &str, size: u64, allocated_size: u64, modified: Option<SystemTime>) -> FileInfo {
+        let path = PathBuf::from(path);
+        FileInfo {
+            name: path.file_name().unwrap().to_string_lossy().into(),
+            is_dir: false,
+            is_shortcut: false,
+            size,
+            allocated_size,
+            modified,
+            extension: path.extension().map(|e| e.to_string_lossy().to_lowercase()),
+            path,
+        }
+    }
+
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> Option<SystemTime> {
+        Some(Local.with_ymd_and_hms(year, month, day, hour, minute, second).unwrap().into())
+    }
+
+    fn store() -> FileStore {
+        let mut store = FileStore::new();
+        store.extend([
+            file("/jobs/a9/plan.PDF", 100, 4096, local(2025, 1, 31, 23, 59, 59)),
+            file("/jobs/a/site.dwg", 20, 4096, local(2025, 2, 1, 0, 0, 0)),
+            file("/jobs/a9/README", 3, 4096, None),
+            file("/jobs/a/spec.pdf", 1000, 8192, local(2025, 1, 1, 0, 0, 0)),
+            file("/jobs/a10/photo.jpg", 7, 4096, local(2024, 12, 31, 12, 0, 0)),
+        ]);
+        store
+    }
+
+    fn grouped(by: GroupBy, size_mode: SizeMode, descending: bool) -> Vec<(String, Vec<usize>, u64)> {
+        let files = store();
+        let rows: Vec<usize> = (0..files.len()).collect();
+        group_rows(&rows, by, |index| files.entry(index), size_mode, descending)
+            .into_iter()
+            .map(|group| (group.label, group.rows, group.size))
+            .collect()
+    }
+
+    fn labels(by: GroupBy, descending: bool) -> Vec<String> {
+        grouped(by, SizeMode::Apparent, descending).into_iter().map(|(label, _, _)| label).collect()
+    }
+
+    #[test]
+    fn by_folder() {
+        let sep = std::path::MAIN_SEPARATOR;
+        let folder = |name: &str| format!("{sep}jobs{sep}{name}");
+        // Folders in natural order, files in their listed order within each
+        assert_eq!(grouped(GroupBy::Folder, SizeMode::Apparent, false), [
+            (folder("a"), vec![1, 3], 1020),
+            (folder("a9"), vec![0, 2], 103),
+            (folder("a10"), vec![4], 7),
+        ]);
+        assert_eq!(labels(GroupBy::Folder, true), [folder("a10"), folder("a9"), folder("a")]);
+    }
+
+    #[test]
+    fn by_extension() {
+        // Extensions are grouped however they're cased; files without one come last
+        assert_eq!(grouped(GroupBy::Extension, SizeMode::Apparent, false), [
+            ("DWG".to_string(), vec![1], 20),
+            ("JPG".to_string(), vec![4], 7),
+            ("PDF".to_string(), vec![0, 3], 1100),
+            ("No extension".to_string(), vec![2], 3),
+        ]);
+        assert_eq!(labels(GroupBy::Extension, true), ["No extension", "PDF", "JPG", "DWG"]);
+    }
+
+    #[test]
+    fn by_category() {
+        // Categories in their declared order; unknown extensions and none at all are Other
+        assert_eq!(grouped(GroupBy::Category, SizeMode::Apparent, false), [
+            ("Document".to_string(), vec![0, 3], 1100),
+            ("Image".to_string(), vec![4], 7),
+            ("Other".to_string(), vec![1, 2], 23),
+        ]);
+        assert_eq!(labels(GroupBy::Category, true), ["Other", "Image", "Document"]);
+    }
+
+    #[test]
+    fn by_month() {
+        // The last second of January and the first of February fall in different months;
+        // files without a date come last, or first when descending
+        assert_eq!(grouped(GroupBy::Month, SizeMode::Apparent, false), [
+            ("December 2024".to_string(), vec![4], 7),
+            ("January 2025".to_string(), vec![0, 3], 1100),
+            ("February 2025".to_string(), vec![1], 20),
+            ("No date".to_string(), vec![2], 3),
+        ]);
+        assert_eq!(labels(GroupBy::Month, true), ["No date", "February 2025", "January 2025", "December 2024"]);
+    }
+
+    #[test]
+    fn subtotals_follow_the_size_mode() {
+        let sizes = |mode| grouped(GroupBy::Extension, mode, false).into_iter().map(|(_, _, size)| size).collect::<Vec<_>>();
+        assert_eq!(sizes(SizeMode::Apparent), [20, 7, 1100, 3]);
+        assert_eq!(sizes(SizeMode::Allocated), [4096, 4096, 12288, 4096]);
+        let group = Group { label: String::new(), rows: vec![0, 3], size: 1100 };
+        assert_eq!(group.summary(), format!("2 files, {}", format_size(1100)));
+        let group = Group { label: String::new(), rows: vec![1], size: 20 };
+        assert_eq!(group.summary(), format!("1 file, {}", format_size(20)));
+    }
+
+    #[test]
+    fn only_listed_rows_are_grouped() {
+        let files = store();
+        let groups = group_rows(&[3, 0, 4], GroupBy::Extension, |index| files.entry(index), SizeMode::Apparent, false);
+        assert_eq!(groups.iter().map(|group| (group.label.as_str(), group.rows.clone())).collect::<Vec<_>>(),
+            [("JPG", vec![4]), ("PDF", vec![3, 0])]);
+        assert!(group_rows(&[], GroupBy::Folder, |index| files.entry(index), SizeMode::Apparent, false).is_empty());
+    }
+
+    #[test]
+    fn grouped_row_indexes() {
+        let groups = vec![
+            Group { label: "a".to_string(), rows: vec![7, 3], size: 0 },
+            Group { label: "b".to_string(), rows: vec![5], size: 0 },
+            Group { label: "c".to_string(), rows: vec![], size: 0 },
+        ];
+        let rows = GroupedRows::new(groups);
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows.groups().len(), 3);
+        let laid_out: Vec<String> = (0..rows.len())
+            .map(|row| match rows.row(row).unwrap() {
+                ListRow::Heading(group) => group.label.clone(),
+                ListRow::File(index) => index.to_string(),
+            })
+            .collect();
+        assert_eq!(laid_out, ["a", "7", "3", "b", "5", "c"]);
+        assert!(rows.row(6).is_none());
+        assert_eq!(GroupedRows::new(Vec::new()).len(), 0);
+    }
+}
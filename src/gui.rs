@@ -12,19 +12,29 @@ use windows::{
         },
         UI::{
             Controls::*,
+            Controls::Dialogs::*,
             Shell::*,
             WindowsAndMessaging::*,
             Input::KeyboardAndMouse::*,
         },
     },
 };
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use std::path::Path;
-use std::time::Instant;
-use crate::scanner::{FileInfo, ScanMessage};
-use crate::filter::Filter;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use crate::scanner::{format_size, iso_time, FileInfo, FileRecord, ScanMessage, ScanOptions, SizeMode};
+use crate::store::{FileEntry, FileStore};
+use crate::tree::DirNode;
+use crate::filter::{self, Diagnostic, Filter, Severity};
+use crate::searches::SearchLibrary;
+use crate::sort::{self, SortContext, SortField, SortKey};
+use crate::group::{self, GroupBy, GroupedRows, ListRow};
+use crate::export::{ListExport, ListFormat, ScanSummary, TableFormat};
+use crate::stats::Statistics;
 use crossbeam_channel::{Receiver, unbounded, Sender};
 use std::thread;
+use rayon::prelude::*;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 const ID_LISTVIEW: i32 = 1001;
@@ -38,10 +48,37 @@ const ID_CHK_MODIFIED: i32 = 1008;
 const ID_CHK_PATH: i32 = 1009;
 const ID_SIGNATURE: i32 = 1010;
 const ID_HELP_BUTTON: i32 = 1011;
+const ID_CHK_ALLOCATED: i32 = 1012;
+const ID_EXPORT_BUTTON: i32 = 1013;
+const ID_CHK_FUZZY: i32 = 1014;
+const ID_CHK_ACCENTS: i32 = 1015;
+const ID_SEARCHES_BUTTON: i32 = 1016;
+const ID_GROUP_BUTTON: i32 = 1017;
+const ID_STATS_BUTTON: i32 = 1018;
+const ID_STATS_TEXT: i32 = 1019;
 const ID_CTX_OPEN: i32 = 2001;
 const ID_CTX_OPEN_FOLDER: i32 = 2002;
 const ID_CTX_COPY_PATH: i32 = 2003;
 const ID_CTX_COPY_NAME: i32 = 2004;
+const ID_EXPORT_TREEMAP_HTML: i32 = 2101;
+const ID_EXPORT_TREE_JSON: i32 = 2102;
+const ID_EXPORT_LIST_CSV: i32 = 2103;
+const ID_EXPORT_LIST_HTML: i32 = 2104;
+const ID_EXPORT_LIST_TSV: i32 = 2105;
+const ID_EXPORT_BOM: i32 = 2106;
+const ID_EXPORT_LIST_JSON: i32 = 2107;
+const ID_EXPORT_LIST_JSONL: i32 = 2108;
+const ID_SEARCH_SAVE: i32 = 2201;
+const ID_SEARCH_CLEAR_HISTORY: i32 = 2202;
+const ID_GROUP_NONE: i32 = 2301;
+// Followed by one ID per grouping in `GroupBy::ALL`
+const ID_GROUP_FIRST: i32 = 2302;
+const ID_STATS_SAVE_JSON: i32 = 2401;
+const ID_STATS_SAVE_HTML: i32 = 2402;
+// Menu items for each saved search, delete entry and history entry, by position
+const ID_SEARCH_SAVED_FIRST: i32 = 3000;
+const ID_SEARCH_DELETE_FIRST: i32 = 4000;
+const ID_SEARCH_HISTORY_FIRST: i32 = 5000;
 const WM_UPDATE_LIST: u32 = WM_USER + 1;
 const WM_HOTKEY: u32 = 0x0312;
 const WM_UPDATE_SEARCH: u32 = WM_USER + 2;
@@ -52,14 +89,22 @@ const CF_UNICODETEXT: u32 = 13;
 const EN_CHANGE: u32 = 0x0300;
 const CDDS_PREPAINT: u32 = 1;
 const CDDS_ITEMPREPAINT: u32 = 0x10001;
+const CDDS_SUBITEMPREPAINT: u32 = 0x30001; // CDDS_ITEMPREPAINT | CDDS_SUBITEM
 const CDRF_NOTIFYITEMDRAW: u32 = 0x20;
+const CDRF_NOTIFYSUBITEMDRAW: u32 = 0x20;
 const CDRF_NEWFONT: u32 = 0x2;
+const CDRF_SKIPDEFAULT: u32 = 0x4;
+const HIGHLIGHT_COLOR: u32 = 0x0099FFFF; // Light yellow behind matched text
+const GROUP_HEADING_COLOR: u32 = 0x00F3E6DA; // Light blue behind group headings
+// Rows filtered per hold of the `files` lock, so scan batches can land between chunks
+const SEARCH_CHUNK: usize = 1 << 16;
 const BS_PUSHBUTTON: u32 = 0x00000000;
 const BS_AUTOCHECKBOX: u32 = 0x00000003;
 const BM_GETCHECK: u32 = 0x00F0;
 const BM_SETCHECK: u32 = 0x00F1;
 const BST_CHECKED: u32 = 0x0001;
 const SS_CENTER: u32 = 0x00000001;
+const SS_ENDELLIPSIS: u32 = 0x00004000;
 
 #[repr(C)]
 #[allow(non_snake_case)]
@@ -124,21 +169,58 @@ pub struct FileListWindow {
     chk_path: HWND,
     signature_label: HWND,
     help_button: HWND,
-    files: Arc<Mutex<Vec<FileInfo>>>,
-    filtered_files: Arc<Mutex<Vec<FileInfo>>>,
+    chk_allocated: HWND,
+    export_button: HWND,
+    chk_fuzzy: HWND,
+    chk_accents: HWND,
+    searches_button: HWND,
+    group_button: HWND,
+    stats_button: HWND,
+    // The statistics window while it is open, and its report text
+    statistics_window: HWND,
+    statistics_text: HWND,
+    statistics_font: HFONT,
+    // When the open statistics were last recomputed, to limit it during scans
+    statistics_updated: Arc<Mutex<Option<Instant>>>,
+    scan_root: PathBuf,
+    files: Arc<Mutex<FileStore>>,
+    // Indices into `files`; the records themselves are stored only once
+    filtered_files: Arc<Mutex<Vec<usize>>>,
+    // What the list is grouped by, and `filtered_files` laid out under group headings
+    group_by: Arc<Mutex<Option<GroupBy>>>,
+    grouped_rows: Arc<Mutex<Option<GroupedRows>>>,
+    // Fuzzy relevance per file, also indexed like `files`; only kept up to date in fuzzy mode
+    match_scores: Arc<Mutex<Vec<u32>>>,
+    // Matching line numbers per file while a `contains:` search is listed
+    content_lines: Arc<Mutex<HashMap<usize, Vec<u32>>>>,
+    // Whether the listed results came from a content search, which shows the Lines column
+    show_lines: Arc<AtomicBool>,
+    // Whether CSV and TSV exports start with a UTF-8 byte order mark for Excel
+    export_bom: Arc<AtomicBool>,
     filter: Arc<Mutex<Filter>>,
     search_sender: Option<Sender<String>>,
     is_searching: Arc<AtomicBool>,
+    // Bumped for every search request; a running search stops once it no longer matches
+    search_generation: Arc<AtomicUsize>,
     scan_animation_frame: Arc<Mutex<usize>>,
     scan_start_time: Arc<Mutex<Option<Instant>>>,
     scan_elapsed_ms: Arc<AtomicUsize>,
     is_scanning: Arc<AtomicBool>,
+    skipped: Arc<Mutex<Vec<(PathBuf, String)>>>,
+    // Problems with the current search text, worst first
+    search_diagnostics: Arc<Mutex<Vec<Diagnostic>>>,
+    scan_options: ScanOptions,
+    // Saved searches and history, written back to `search_library_path` when they change
+    search_library: SearchLibrary,
+    search_library_path: Option<PathBuf>,
     show_type: Arc<AtomicBool>,
     show_size: Arc<AtomicBool>,
     show_modified: Arc<AtomicBool>,
     show_path: Arc<AtomicBool>,
-    sort_column: Arc<Mutex<i32>>,
-    sort_ascending: Arc<AtomicBool>,
+    use_allocated_size: Arc<AtomicBool>,
+    use_fuzzy: Arc<AtomicBool>,
+    // Primary key first; empty keeps the scan or relevance order
+    sort_keys: Arc<Mutex<Vec<SortKey>>>,
 }
 
 impl FileListWindow {
@@ -156,21 +238,46 @@ impl FileListWindow {
             chk_path: HWND::default(),
             signature_label: HWND::default(),
             help_button: HWND::default(),
-            files: Arc::new(Mutex::new(Vec::new())),
+            chk_allocated: HWND::default(),
+            export_button: HWND::default(),
+            chk_fuzzy: HWND::default(),
+            chk_accents: HWND::default(),
+            searches_button: HWND::default(),
+            group_button: HWND::default(),
+            stats_button: HWND::default(),
+            statistics_window: HWND::default(),
+            statistics_text: HWND::default(),
+            statistics_font: HFONT::default(),
+            statistics_updated: Arc::new(Mutex::new(None)),
+            scan_root: PathBuf::new(),
+            files: Arc::new(Mutex::new(FileStore::new())),
             filtered_files: Arc::new(Mutex::new(Vec::new())),
+            group_by: Arc::new(Mutex::new(None)),
+            grouped_rows: Arc::new(Mutex::new(None)),
+            match_scores: Arc::new(Mutex::new(Vec::new())),
+            content_lines: Arc::new(Mutex::new(HashMap::new())),
+            show_lines: Arc::new(AtomicBool::new(false)),
+            export_bom: Arc::new(AtomicBool::new(true)),
             filter: Arc::new(Mutex::new(Filter::new())),
             search_sender: None,
             is_searching: Arc::new(AtomicBool::new(false)),
+            search_generation: Arc::new(AtomicUsize::new(0)),
             scan_animation_frame: Arc::new(Mutex::new(0)),
             scan_start_time: Arc::new(Mutex::new(None)),
             scan_elapsed_ms: Arc::new(AtomicUsize::new(0)),
             is_scanning: Arc::new(AtomicBool::new(false)),
+            skipped: Arc::new(Mutex::new(Vec::new())),
+            search_diagnostics: Arc::new(Mutex::new(Vec::new())),
+            scan_options: ScanOptions::default(),
+            search_library: SearchLibrary::default(),
+            search_library_path: None,
             show_type: Arc::new(AtomicBool::new(true)),
             show_size: Arc::new(AtomicBool::new(true)),
             show_modified: Arc::new(AtomicBool::new(true)),
             show_path: Arc::new(AtomicBool::new(true)),
-            sort_column: Arc::new(Mutex::new(-1)),
-            sort_ascending: Arc::new(AtomicBool::new(true)),
+            use_allocated_size: Arc::new(AtomicBool::new(false)),
+            use_fuzzy: Arc::new(AtomicBool::new(false)),
+            sort_keys: Arc::new(Mutex::new(Vec::new())),
         });
 
         window.create_window()?;
@@ -178,13 +285,41 @@ impl FileListWindow {
         Ok(window)
     }
 
+    pub fn set_scan_options(&mut self, options: ScanOptions) {
+        self.scan_options = options;
+    }
+
+    // Whether text searches are narrowed with a trigram index, at some memory cost
+    pub fn set_search_index(&mut self, enabled: bool) {
+        self.files.lock().unwrap().set_indexed(enabled);
+    }
+
+    pub fn set_search_library(&mut self, library: SearchLibrary, path: Option<PathBuf>) {
+        self.search_library = library;
+        self.search_library_path = path;
+    }
+
+    // Puts `text` in the search box, which runs the search
+    pub fn set_search_text(&mut self, text: &str) {
+        unsafe {
+            SetWindowTextW(self.search_box, &HSTRING::from(text)).ok();
+            let end = text.encode_utf16().count();
+            SendMessageW(self.search_box, EM_SETSEL, WPARAM(end), LPARAM(end as isize));
+        }
+    }
+
     pub fn set_update_receiver(&mut self, receiver: Receiver<ScanMessage>) {
         let files = Arc::clone(&self.files);
         let filtered_files = Arc::clone(&self.filtered_files);
+        let match_scores = Arc::clone(&self.match_scores);
+        let content_lines = Arc::clone(&self.content_lines);
         let filter = Arc::clone(&self.filter);
         let scan_start_time = Arc::clone(&self.scan_start_time);
         let scan_elapsed_ms = Arc::clone(&self.scan_elapsed_ms);
         let is_scanning = Arc::clone(&self.is_scanning);
+        let skipped = Arc::clone(&self.skipped);
+        let sort_keys = Arc::clone(&self.sort_keys);
+        let use_fuzzy = Arc::clone(&self.use_fuzzy);
         let hwnd = self.hwnd.0 as isize;
         
         thread::spawn(move || {
@@ -195,6 +330,7 @@ impl FileListWindow {
                         *scan_start_time.lock().unwrap() = Some(Instant::now());
                         is_scanning.store(true, Ordering::SeqCst);
                         scan_elapsed_ms.store(0, Ordering::SeqCst);
+                        skipped.lock().unwrap().clear();
                         
                         unsafe {
                             let hwnd = HWND(hwnd as *mut _);
@@ -202,22 +338,54 @@ impl FileListWindow {
                         }
                     },
                     ScanMessage::Batch(batch) => {
-                        // Append batch to master list
-                        {
-                            let mut all = files.lock().unwrap();
-                            all.extend(batch.iter().cloned());
-                        }
-                        
-                        // Append only matches to filtered list (so search results grow live)
-                        {
-                            let flt = filter.lock().unwrap();
-                            let mut ff = filtered_files.lock().unwrap();
-                            for item in &batch {
-                                if flt.matches(item) {
-                                    ff.push(item.clone());
+                        // Move the batch into the master list, remembering which new rows match
+                        // so search results grow live
+                        let mut all = files.lock().unwrap();
+                        let mut flt = filter.lock().unwrap();
+                        let (matched, lines) = if flt.searches_content() {
+                            // Reading contents takes a while, so it's done with nothing locked against
+                            // a copy of the filter, and done again if the search changes meanwhile
+                            loop {
+                                let current = flt.clone();
+                                drop(flt);
+                                drop(all);
+                                let result = match_batch(&current, &batch);
+                                all = files.lock().unwrap();
+                                flt = filter.lock().unwrap();
+                                if *flt == current {
+                                    break result;
                                 }
                             }
+                        } else {
+                            match_batch(&flt, &batch)
+                        };
+                        let size_mode = flt.size_mode();
+                        drop(flt);
+                        let start = all.len();
+                        let matched: Vec<(usize, u32)> = matched.into_iter().map(|(i, score)| (start + i, score)).collect();
+                        let lines: Vec<(usize, Vec<u32>)> = lines.into_iter().map(|(i, file_lines)| (start + i, file_lines)).collect();
+                        all.extend(batch);
+                        
+                        let mut filtered = filtered_files.lock().unwrap();
+                        let mut scores = match_scores.lock().unwrap();
+                        for &(index, score) in &matched {
+                            if scores.len() <= index {
+                                scores.resize(index + 1, 0);
+                            }
+                            scores[index] = score;
                         }
+                        let mut all_lines = content_lines.lock().unwrap();
+                        all_lines.extend(lines);
+                        
+                        // Slot the new rows into the sorted list rather than tacking them on the end
+                        let keys = active_sort_keys(&sort_keys, &use_fuzzy);
+                        let context = SortContext { size_mode, scores: &scores, lines: &all_lines };
+                        let rows = matched.into_iter().map(|(index, _)| index).collect();
+                        sort::merge_rows(&mut filtered, rows, &keys, |index| all.entry(index), &context);
+                        drop(all_lines);
+                        drop(scores);
+                        drop(filtered);
+                        drop(all);
                         
                         // Update elapsed time during scan
                         if let Some(start) = *scan_start_time.lock().unwrap() {
@@ -233,6 +401,10 @@ impl FileListWindow {
                         // Small delay to batch UI updates  
                         thread::sleep(std::time::Duration::from_millis(30));
                     },
+                    ScanMessage::Skipped { path, reason } => {
+                        // Mounts left out by the scan options; listed when the status bar is double-clicked
+                        skipped.lock().unwrap().push((path, reason));
+                    },
                     ScanMessage::Completed { elapsed_ms, file_count: _ } => {
                         // Mark scan as complete
                         is_scanning.store(false, Ordering::SeqCst);
@@ -267,6 +439,17 @@ impl FileListWindow {
 
             RegisterClassExW(&wc);
 
+            let statistics_class = WNDCLASSEXW {
+                cbSize: std::mem::size_of::<WNDCLASSEXW>() as u32,
+                lpfnWndProc: Some(statistics_proc),
+                hInstance: instance.into(),
+                hCursor: LoadCursorW(None, IDC_ARROW)?,
+                hbrBackground: HBRUSH((COLOR_BTNFACE.0 + 1) as _),
+                lpszClassName: w!("FileListStatistics"),
+                ..Default::default()
+            };
+            RegisterClassExW(&statistics_class);
+
             self.hwnd = CreateWindowExW(
                 WS_EX_ACCEPTFILES, // Accept dropped files
                 window_class,
@@ -274,7 +457,7 @@ impl FileListWindow {
                 WS_OVERLAPPEDWINDOW | WS_VISIBLE,
                 CW_USEDEFAULT,
                 CW_USEDEFAULT,
-                1200,
+                1440,
                 800,
                 None,
                 None,
@@ -301,7 +484,7 @@ impl FileListWindow {
                 w!("EDIT"),
                 w!(""),
                 WS_CHILD | WS_VISIBLE | WS_BORDER | WINDOW_STYLE(ES_AUTOHSCROLL as u32),
-                10, 10, 300, 25,
+                10, 10, 272, 25,
                 self.hwnd,
                 HMENU(ID_SEARCHBOX as _),
                 instance,
@@ -407,6 +590,96 @@ impl FileListWindow {
                 None,
             )?;
 
+            // Toggle between file length and space used on disk
+            self.chk_allocated = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("BUTTON"),
+                w!("Disk usage"),
+                WS_CHILD | WS_VISIBLE | WINDOW_STYLE(BS_AUTOCHECKBOX as u32),
+                850, 10, 90, 25,
+                self.hwnd,
+                HMENU(ID_CHK_ALLOCATED as _),
+                instance,
+                None,
+            )?;
+
+            self.export_button = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("BUTTON"),
+                w!("Export..."),
+                WS_CHILD | WS_VISIBLE | WINDOW_STYLE(BS_PUSHBUTTON as u32),
+                950, 10, 80, 25,
+                self.hwnd,
+                HMENU(ID_EXPORT_BUTTON as _),
+                instance,
+                None,
+            )?;
+
+            // Saved searches and history, dropped down from beside the search box
+            self.searches_button = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("BUTTON"),
+                w!("▾"),
+                WS_CHILD | WS_VISIBLE | WINDOW_STYLE(BS_PUSHBUTTON as u32),
+                284, 10, 26, 25,
+                self.hwnd,
+                HMENU(ID_SEARCHES_BUTTON as _),
+                instance,
+                None,
+            )?;
+
+            // Typo-tolerant matching, ranked by relevance
+            self.chk_fuzzy = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("BUTTON"),
+                w!("Fuzzy"),
+                WS_CHILD | WS_VISIBLE | WINDOW_STYLE(BS_AUTOCHECKBOX as u32),
+                1040, 10, 60, 25,
+                self.hwnd,
+                HMENU(ID_CHK_FUZZY as _),
+                instance,
+                None,
+            )?;
+
+            // Accents are ignored unless ticked, so resume finds Résumé
+            self.chk_accents = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("BUTTON"),
+                w!("Accents"),
+                WS_CHILD | WS_VISIBLE | WINDOW_STYLE(BS_AUTOCHECKBOX as u32),
+                1105, 10, 70, 25,
+                self.hwnd,
+                HMENU(ID_CHK_ACCENTS as _),
+                instance,
+                None,
+            )?;
+
+            // Gathers the list under headings with subtotals
+            self.group_button = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("BUTTON"),
+                w!("Group by ▾"),
+                WS_CHILD | WS_VISIBLE | WINDOW_STYLE(BS_PUSHBUTTON as u32),
+                1180, 10, 150, 25,
+                self.hwnd,
+                HMENU(ID_GROUP_BUTTON as _),
+                instance,
+                None,
+            )?;
+
+            // Totals and breakdowns of the listed files
+            self.stats_button = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("BUTTON"),
+                w!("Statistics"),
+                WS_CHILD | WS_VISIBLE | WINDOW_STYLE(BS_PUSHBUTTON as u32),
+                1340, 10, 80, 25,
+                self.hwnd,
+                HMENU(ID_STATS_BUTTON as _),
+                instance,
+                None,
+            )?;
+
             self.list_view = CreateWindowExW(
                 WS_EX_CLIENTEDGE,
                 WC_LISTVIEW,
@@ -556,14 +829,33 @@ impl FileListWindow {
                     LPARAM(&column as *const _ as _),
                 );
                 col_index += 1;
+
+                let column = LVCOLUMNW {
+                    mask: LVCF_TEXT | LVCF_WIDTH | LVCF_FMT,
+                    fmt: LVCFMT_LEFT,
+                    cx: 90,
+                    pszText: PWSTR(w!("Category").as_ptr() as *mut _),
+                    ..Default::default()
+                };
+                SendMessageW(
+                    self.list_view,
+                    LVM_INSERTCOLUMNW,
+                    WPARAM(col_index),
+                    LPARAM(&column as *const _ as _),
+                );
+                col_index += 1;
             }
 
             if self.show_size.load(Ordering::SeqCst) {
+                let title = match self.size_mode() {
+                    SizeMode::Apparent => w!("Size"),
+                    SizeMode::Allocated => w!("Size on Disk"),
+                };
                 let column = LVCOLUMNW {
                     mask: LVCF_TEXT | LVCF_WIDTH | LVCF_FMT,
                     fmt: LVCFMT_LEFT,
                     cx: 100,
-                    pszText: PWSTR(w!("Size").as_ptr() as *mut _),
+                    pszText: PWSTR(title.as_ptr() as *mut _),
                     ..Default::default()
                 };
                 SendMessageW(
@@ -606,13 +898,52 @@ impl FileListWindow {
                     WPARAM(col_index),
                     LPARAM(&column as *const _ as _),
                 );
+                col_index += 1;
+            }
+
+            // Relevance score, only meaningful while fuzzy matching
+            if self.use_fuzzy.load(Ordering::SeqCst) {
+                let column = LVCOLUMNW {
+                    mask: LVCF_TEXT | LVCF_WIDTH | LVCF_FMT,
+                    fmt: LVCFMT_RIGHT,
+                    cx: 60,
+                    pszText: PWSTR(w!("Match").as_ptr() as *mut _),
+                    ..Default::default()
+                };
+                SendMessageW(
+                    self.list_view,
+                    LVM_INSERTCOLUMNW,
+                    WPARAM(col_index),
+                    LPARAM(&column as *const _ as _),
+                );
+                col_index += 1;
+            }
+
+            // Matching line numbers, while the search looks inside files
+            if self.show_lines.load(Ordering::SeqCst) {
+                let column = LVCOLUMNW {
+                    mask: LVCF_TEXT | LVCF_WIDTH | LVCF_FMT,
+                    fmt: LVCFMT_LEFT,
+                    cx: 150,
+                    pszText: PWSTR(w!("Lines").as_ptr() as *mut _),
+                    ..Default::default()
+                };
+                SendMessageW(
+                    self.list_view,
+                    LVM_INSERTCOLUMNW,
+                    WPARAM(col_index),
+                    LPARAM(&column as *const _ as _),
+                );
             }
         }
+        self.update_sort_arrows();
 
         Ok(())
     }
 
     pub fn load_directory(&mut self, path: &Path) {
+        self.scan_root = path.to_path_buf();
+        
         // Show loading message immediately and set window title
         unsafe {
             let title = format!("File List Generator - {}", path.display());
@@ -621,85 +952,146 @@ impl FileListWindow {
         }
     }
 
+    // The Type checkbox shows both the Type and Category columns
+    fn type_columns(&self) -> i32 {
+        if self.show_type.load(Ordering::SeqCst) { 2 } else { 0 }
+    }
+
+    fn size_mode(&self) -> SizeMode {
+        if self.use_allocated_size.load(Ordering::SeqCst) {
+            SizeMode::Allocated
+        } else {
+            SizeMode::Apparent
+        }
+    }
+
+    // Runs `f` on the file shown at `row` of the list view
+    fn with_file_at<R>(&self, row: usize, f: impl FnOnce(&FileEntry) -> R) -> Option<R> {
+        let files = self.files.lock().unwrap();
+        let filtered = self.filtered_files.lock().unwrap();
+        let grouped = self.grouped_rows.lock().unwrap();
+        row_file(&filtered, &grouped, row).and_then(|index| files.get(index)).map(|file| f(&file))
+    }
+
+    // Rows in the list view, counting group headings
+    fn row_count(&self) -> usize {
+        let filtered = self.filtered_files.lock().unwrap();
+        let grouped = self.grouped_rows.lock().unwrap();
+        grouped.as_ref().map_or(filtered.len(), GroupedRows::len)
+    }
+
+    fn is_heading_row(&self, row: usize) -> bool {
+        let grouped = self.grouped_rows.lock().unwrap();
+        matches!(grouped.as_ref().and_then(|grouped| grouped.row(row)), Some(ListRow::Heading(_)))
+    }
+
+    // Lays the listed files out under group headings again, after the list or
+    // its sort changed
+    fn regroup(&self) {
+        let group_by = *self.group_by.lock().unwrap();
+        let grouped = group_by.map(|by| {
+            let files = self.files.lock().unwrap();
+            let filtered = self.filtered_files.lock().unwrap();
+            // Groups follow the sort direction when sorting by what they group on
+            let descending = self.sort_keys.lock().unwrap().first()
+                .is_some_and(|key| key.field == by.sort_field() && !key.ascending);
+            GroupedRows::new(group::group_rows(&filtered, by, |index| files.entry(index), self.size_mode(), descending))
+        });
+        *self.grouped_rows.lock().unwrap() = grouped;
+    }
+
+    // Runs `f` with the listed rows set up for export the way the window shows them
+    fn with_list_export<T>(&self, f: impl FnOnce(&ListExport, &[usize], &FileStore) -> T) -> T {
+        let columns = self.column_fields();
+        let files = self.files.lock().unwrap();
+        let filtered = self.filtered_files.lock().unwrap();
+        let grouped = self.grouped_rows.lock().unwrap();
+        let scores = self.match_scores.lock().unwrap();
+        let lines = self.content_lines.lock().unwrap();
+        let export = ListExport {
+            columns: &columns,
+            context: SortContext { size_mode: self.size_mode(), scores: &scores, lines: &lines },
+            groups: grouped.as_ref().map(GroupedRows::groups),
+        };
+        f(&export, &filtered, &files)
+    }
+
+    // What each visible column sorts by, in column order
+    fn column_fields(&self) -> Vec<SortField> {
+        let mut fields = vec![SortField::Name];
+        if self.show_type.load(Ordering::SeqCst) {
+            fields.extend([SortField::Type, SortField::Category]);
+        }
+        if self.show_size.load(Ordering::SeqCst) {
+            fields.push(SortField::Size);
+        }
+        if self.show_modified.load(Ordering::SeqCst) {
+            fields.push(SortField::Modified);
+        }
+        if self.show_path.load(Ordering::SeqCst) {
+            fields.push(SortField::Path);
+        }
+        if self.use_fuzzy.load(Ordering::SeqCst) {
+            fields.push(SortField::Match);
+        }
+        if self.show_lines.load(Ordering::SeqCst) {
+            fields.push(SortField::Lines);
+        }
+        fields
+    }
+
+    // Drops sort keys for columns that are no longer shown
+    fn retain_visible_sort_keys(&self) {
+        let fields = self.column_fields();
+        self.sort_keys.lock().unwrap().retain(|key| fields.contains(&key.field));
+    }
+
+    // A plain click sorts by the column alone, or reverses it if it already is the
+    // only key; Shift+click adds it as a further key, or reverses it if it is one
+    fn click_sort_column(&self, column: i32, add: bool) {
+        let Some(&field) = self.column_fields().get(column as usize) else { return };
+        let mut keys = self.sort_keys.lock().unwrap();
+        let only = keys.len() == 1;
+        match keys.iter_mut().find(|key| key.field == field) {
+            Some(key) if add || only => key.ascending = !key.ascending,
+            _ if add => keys.push(SortKey::new(field, true)),
+            _ => *keys = vec![SortKey::new(field, true)],
+        }
+    }
+
     fn sort_files(&self) {
+        let all_files = self.files.lock().unwrap();
         let mut files = self.filtered_files.lock().unwrap();
-        let sort_col = *self.sort_column.lock().unwrap();
-        let ascending = self.sort_ascending.load(Ordering::SeqCst);
-        
-        if sort_col < 0 {
-            return; // No sorting
-        }
-        
-        // Map visible column index to actual column type
-        let mut actual_col = 0;
-        let mut mapped_col = 0;
-        
-        // Column 0 is always Name
-        if sort_col == 0 {
-            mapped_col = 0;
-        } else {
-            actual_col = 1;
-            if self.show_type.load(Ordering::SeqCst) {
-                if sort_col == actual_col {
-                    mapped_col = 1; // Type
-                }
-                actual_col += 1;
-            }
-            if self.show_size.load(Ordering::SeqCst) {
-                if sort_col == actual_col {
-                    mapped_col = 2; // Size
-                }
-                actual_col += 1;
-            }
-            if self.show_modified.load(Ordering::SeqCst) {
-                if sort_col == actual_col {
-                    mapped_col = 3; // Modified
-                }
-                actual_col += 1;
-            }
-            if self.show_path.load(Ordering::SeqCst) {
-                if sort_col == actual_col {
-                    mapped_col = 4; // Path
-                }
+        let keys = active_sort_keys(&self.sort_keys, &self.use_fuzzy);
+        let scores = self.match_scores.lock().unwrap();
+        let lines = self.content_lines.lock().unwrap();
+        let context = SortContext { size_mode: self.size_mode(), scores: &scores, lines: &lines };
+        sort::sort_rows(&mut files, &keys, |index| all_files.entry(index), &context);
+    }
+
+    // Shows an arrow in the header of each column being sorted by
+    fn update_sort_arrows(&self) {
+        unsafe {
+            let header = HWND(SendMessageW(self.list_view, LVM_GETHEADER, WPARAM(0), LPARAM(0)).0 as *mut _);
+            let keys = self.sort_keys.lock().unwrap().clone();
+            for (column, field) in self.column_fields().into_iter().enumerate() {
+                let mut item = HDITEMW { mask: HDI_FORMAT, ..Default::default() };
+                SendMessageW(header, HDM_GETITEMW, WPARAM(column), LPARAM(&mut item as *mut _ as _));
+                let arrow = match keys.iter().find(|key| key.field == field) {
+                    Some(key) if key.ascending => HDF_SORTUP.0,
+                    Some(_) => HDF_SORTDOWN.0,
+                    None => 0,
+                };
+                item.fmt = HEADER_CONTROL_FORMAT_FLAGS(item.fmt.0 & !(HDF_SORTUP.0 | HDF_SORTDOWN.0) | arrow);
+                SendMessageW(header, HDM_SETITEMW, WPARAM(column), LPARAM(&item as *const _ as _));
             }
         }
-        
-        files.sort_by(|a, b| {
-            let result = match mapped_col {
-                0 => a.name.to_lowercase().cmp(&b.name.to_lowercase()), // Name
-                1 => { // Type/Extension
-                    let ext_a = a.extension.as_deref().unwrap_or("");
-                    let ext_b = b.extension.as_deref().unwrap_or("");
-                    ext_a.cmp(ext_b)
-                }
-                2 => a.size.cmp(&b.size), // Size
-                3 => { // Modified
-                    match (a.modified, b.modified) {
-                        (Some(ta), Some(tb)) => ta.cmp(&tb),
-                        (Some(_), None) => std::cmp::Ordering::Less,
-                        (None, Some(_)) => std::cmp::Ordering::Greater,
-                        (None, None) => std::cmp::Ordering::Equal,
-                    }
-                }
-                4 => { // Path
-                    let path_a = a.path.parent().map(|p| p.to_string_lossy().to_lowercase()).unwrap_or_default();
-                    let path_b = b.path.parent().map(|p| p.to_string_lossy().to_lowercase()).unwrap_or_default();
-                    path_a.cmp(&path_b)
-                }
-                _ => std::cmp::Ordering::Equal,
-            };
-            
-            if ascending {
-                result
-            } else {
-                result.reverse()
-            }
-        });
     }
     
     fn refresh_list_view(&self) {
+        self.regroup();
         unsafe {
-            let count = self.filtered_files.lock().unwrap().len();
+            let count = self.row_count();
             SendMessageW(
                 self.list_view,
                 LVM_SETITEMCOUNT,
@@ -716,6 +1108,7 @@ impl FileListWindow {
         let mut animation_frame = self.scan_animation_frame.lock().unwrap();
         let is_scanning = self.is_scanning.load(Ordering::SeqCst);
         let elapsed_ms = self.scan_elapsed_ms.load(Ordering::SeqCst);
+        let skipped = self.skipped.lock().unwrap().len();
         
         let status = if is_scanning {
             // Currently scanning - show live progress
@@ -757,8 +1150,8 @@ impl FileListWindow {
             }
         } else {
             // Show all files with timing statistics
-            let total_size: u64 = files.iter().map(|f| f.size).sum();
-            let size_str = Self::format_file_size(total_size);
+            let total_size = files.total_size(self.size_mode());
+            let size_str = format_size(total_size);
             
             if elapsed_ms > 0 {
                 let elapsed_secs = elapsed_ms as f64 / 1000.0;
@@ -770,29 +1163,31 @@ impl FileListWindow {
             }
         };
         
+        let status = if skipped > 0 {
+            format!("{} • {} folders skipped (double-click for details)", status, skipped)
+        } else {
+            status
+        };
+        
+        let status = match files.index_memory() {
+            Some(bytes) if !is_scanning && !files.is_empty() => {
+                format!("{} • Search index: {}", status, format_size(bytes as u64))
+            }
+            _ => status,
+        };
+        
+        let status = match self.search_diagnostics.lock().unwrap().first() {
+            Some(error) if error.severity == Severity::Error => format!("⚠ Search error at {} • {}", error, status),
+            Some(warning) => format!("💡 Search hint at {} • {}", warning, status),
+            None => status,
+        };
+        
         unsafe {
             let wide = HSTRING::from(status);
             SetWindowTextW(self.status_bar, &wide).ok();
         }
     }
     
-    fn format_file_size(size: u64) -> String {
-        const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
-        let mut size = size as f64;
-        let mut unit_index = 0;
-        
-        while size >= 1024.0 && unit_index < UNITS.len() - 1 {
-            size /= 1024.0;
-            unit_index += 1;
-        }
-        
-        if unit_index == 0 {
-            format!("{} {}", size as u64, UNITS[unit_index])
-        } else {
-            format!("{:.1} {}", size, UNITS[unit_index])
-        }
-    }
-
     fn setup_search_thread(&mut self) {
         let (sender, receiver) = unbounded::<String>();
         self.search_sender = Some(sender);
@@ -801,38 +1196,144 @@ impl FileListWindow {
         let filtered_files = Arc::clone(&self.filtered_files);
         let filter = Arc::clone(&self.filter);
         let is_searching = Arc::clone(&self.is_searching);
+        let search_diagnostics = Arc::clone(&self.search_diagnostics);
+        let match_scores = Arc::clone(&self.match_scores);
+        let content_lines = Arc::clone(&self.content_lines);
+        let show_lines = Arc::clone(&self.show_lines);
+        let use_fuzzy = Arc::clone(&self.use_fuzzy);
+        let sort_keys = Arc::clone(&self.sort_keys);
+        let search_generation = Arc::clone(&self.search_generation);
         let hwnd = self.hwnd.0 as isize;
         
         thread::spawn(move || {
-            while let Ok(search_text) = receiver.recv() {
+            // The filter the listed results were built with, while they still are
+            let mut previous: Option<Filter> = None;
+            
+            while let Ok(mut search_text) = receiver.recv() {
+                // Skip straight to the latest keystroke
+                while let Ok(newer) = receiver.try_recv() {
+                    search_text = newer;
+                }
+                let generation = search_generation.load(Ordering::SeqCst);
+                let cancelled = || search_generation.load(Ordering::SeqCst) != generation;
                 is_searching.store(true, Ordering::SeqCst);
                 
-                // Update filter
-                filter.lock().unwrap().set_search(&search_text);
+                // Update filter; an invalid query keeps the current results and reports the error,
+                // and a valid one may still get hints about terms that look mistaken
+                let failed = filter.lock().unwrap().set_search(&search_text).is_err();
+                *search_diagnostics.lock().unwrap() = filter::diagnose(&search_text, &files.lock().unwrap());
+                if failed {
+                    is_searching.store(false, Ordering::SeqCst);
+                    unsafe {
+                        let hwnd = HWND(hwnd as *mut _);
+                        PostMessageW(hwnd, WM_UPDATE_SEARCH, WPARAM(0), LPARAM(0)).ok();
+                    }
+                    continue;
+                }
                 
-                // Perform filtering
-                let files_lock = files.lock().unwrap();
-                let filter_lock = filter.lock().unwrap();
+                // Rows past the snapshot are matched by the scan listener with the new filter
+                let current = filter.lock().unwrap().clone();
+                let snapshot_len = files.lock().unwrap().len();
+                let searches_content = current.searches_content();
+                
+                // A query that only narrows the last one re-checks just its results;
+                // otherwise the trigram index may rule most rows out
+                let candidates: Option<Vec<usize>> = match previous.take() {
+                    Some(last) if current.narrows(&last) => Some(
+                        filtered_files.lock().unwrap().iter().copied().filter(|&index| index < snapshot_len).collect()
+                    ),
+                    _ => current.candidates(&files.lock().unwrap()).map(|mut rows| {
+                        rows.retain(|&index| index < snapshot_len);
+                        rows
+                    }),
+                };
                 
-                let mut filtered = Vec::with_capacity(files_lock.len() / 2);
-                for file in files_lock.iter() {
-                    if filter_lock.matches(file) {
-                        filtered.push(file.clone());
+                // Perform filtering in parallel, a chunk at a time so the scan isn't held up
+                let total = candidates.as_ref().map_or(snapshot_len, Vec::len);
+                let mut matched: Vec<(usize, u32)> = Vec::new();
+                let mut lines: HashMap<usize, Vec<u32>> = HashMap::new();
+                let mut start = 0;
+                while start < total && !cancelled() {
+                    let end = (start + SEARCH_CHUNK).min(total);
+                    let row = |i: usize| candidates.as_ref().map_or(i, |rows| rows[i]);
+                    let chunk: Vec<(usize, u32, Vec<u32>)> = if searches_content {
+                        // Reading contents takes a while, so it's done on copies of the rows with
+                        // the store unlocked; a scan starting meanwhile cancels the search
+                        let files_lock = files.lock().unwrap();
+                        let entries: Vec<(usize, FileInfo)> = (start..end)
+                            .map(row)
+                            .filter_map(|index| Some((index, files_lock.get(index)?.to_info())))
+                            .collect();
+                        drop(files_lock);
+                        entries
+                            .into_par_iter()
+                            .filter_map(|(index, file)| {
+                                let score = current.score(&file)?;
+                                // Matched files are read once more for their line numbers
+                                Some((index, score, current.content_lines(&file)))
+                            })
+                            .collect()
+                    } else {
+                        let files_lock = files.lock().unwrap();
+                        let store = &*files_lock;
+                        (start..end)
+                            .into_par_iter()
+                            .map(row)
+                            .filter_map(|index| Some((index, current.score(&store.get(index)?)?, Vec::new())))
+                            .collect()
+                    };
+                    for (index, score, file_lines) in chunk {
+                        matched.push((index, score));
+                        if searches_content {
+                            lines.insert(index, file_lines);
+                        }
                     }
+                    start = end;
                 }
                 
-                drop(files_lock);
-                drop(filter_lock);
+                // Holding `files` keeps a new scan from clearing the store while the results go in
+                let files_lock = files.lock().unwrap();
+                if cancelled() {
+                    // A newer search is queued, and the list may now mix rows from two filters
+                    is_searching.store(false, Ordering::SeqCst);
+                    continue;
+                }
+                
+                // Update filtered files, keeping rows the scan added during the search
+                let mut filtered = filtered_files.lock().unwrap();
+                let added: Vec<usize> = filtered.iter().copied().filter(|&index| index >= snapshot_len).collect();
+                *filtered = matched.iter().map(|&(index, _)| index).chain(added).collect();
+                
+                let mut scores = match_scores.lock().unwrap();
+                if scores.len() < snapshot_len {
+                    scores.resize(snapshot_len, 0);
+                }
+                for &(index, score) in &matched {
+                    scores[index] = score;
+                }
                 
-                // Update filtered files
-                *filtered_files.lock().unwrap() = filtered;
+                // Lines for rows the scan added are already in the map
+                let mut all_lines = content_lines.lock().unwrap();
+                all_lines.retain(|&index, _| index >= snapshot_len);
+                all_lines.extend(lines);
+                
+                // A narrowed search comes back in the old order, but a new one is in scan order
+                let keys = active_sort_keys(&sort_keys, &use_fuzzy);
+                let context = SortContext { size_mode: current.size_mode(), scores: &scores, lines: &all_lines };
+                sort::sort_rows(&mut filtered, &keys, |index| files_lock.entry(index), &context);
+                drop(all_lines);
+                drop(scores);
+                drop(filtered);
+                drop(files_lock);
+                previous = Some(current);
                 
                 is_searching.store(false, Ordering::SeqCst);
+                let columns_changed = show_lines.swap(searches_content, Ordering::SeqCst) != searches_content;
                 
-                // Notify UI to update
+                // Notify UI to update, rebuilding the columns if Lines comes or goes
                 unsafe {
                     let hwnd = HWND(hwnd as *mut _);
-                    PostMessageW(hwnd, WM_UPDATE_SEARCH, WPARAM(0), LPARAM(0)).ok();
+                    PostMessageW(hwnd, WM_UPDATE_SEARCH, WPARAM(columns_changed as usize), LPARAM(0)).ok();
                 }
             }
         });
@@ -844,7 +1345,8 @@ impl FileListWindow {
             let len = GetWindowTextW(self.search_box, &mut buffer) as usize;
             let search_text = String::from_utf16_lossy(&buffer[..len]);
             
-            // Send search request to background thread
+            // Send search request to background thread, cancelling any still running
+            self.search_generation.fetch_add(1, Ordering::SeqCst);
             if let Some(ref sender) = self.search_sender {
                 let _ = sender.send(search_text.clone());
             }
@@ -869,8 +1371,7 @@ impl FileListWindow {
             );
             
             if selected.0 >= 0 {
-                if let Some(file) = self.filtered_files.lock().unwrap().get(selected.0 as usize) {
-                    let path_str = file.path.to_string_lossy();
+                if let Some(path_str) = self.with_file_at(selected.0 as usize, |file| file.full_path().to_string_lossy().to_string()) {
                     self.copy_to_clipboard(&path_str);
                 }
             }
@@ -907,110 +1408,650 @@ impl FileListWindow {
                 LPARAM(LVNI_SELECTED as _),
             );
             
-            if selected.0 < 0 {
-                return; // No selection
+            if selected.0 < 0 {
+                return; // No selection
+            }
+            
+            let menu = CreatePopupMenu().unwrap();
+            
+            AppendMenuW(menu, MF_STRING, ID_CTX_OPEN as usize, w!("Open\tEnter")).ok();
+            AppendMenuW(menu, MF_STRING, ID_CTX_OPEN_FOLDER as usize, w!("Open Containing Folder\tCtrl+O")).ok();
+            AppendMenuW(menu, MF_SEPARATOR, 0, w!("")).ok();
+            AppendMenuW(menu, MF_STRING, ID_CTX_COPY_PATH as usize, w!("Copy Full Path")).ok();
+            AppendMenuW(menu, MF_STRING, ID_CTX_COPY_NAME as usize, w!("Copy File Name")).ok();
+            
+            let mut cursor_pos = POINT::default();
+            GetCursorPos(&mut cursor_pos).ok();
+            
+            let cmd = TrackPopupMenuEx(
+                menu,
+                (TPM_RETURNCMD | TPM_RIGHTBUTTON).0,
+                cursor_pos.x,
+                cursor_pos.y,
+                self.hwnd,
+                None,
+            );
+            
+            DestroyMenu(menu).ok();
+            
+            match cmd.0 as i32 {
+                ID_CTX_OPEN => self.handle_list_item_activate(),
+                ID_CTX_OPEN_FOLDER => self.open_containing_folder(),
+                ID_CTX_COPY_PATH => self.copy_selected_path(),
+                ID_CTX_COPY_NAME => self.copy_selected_name(),
+                _ => {}
+            }
+        }
+    }
+    
+    fn copy_list_to_clipboard(&self) {
+        let text = self.with_list_export(|export, rows, files| {
+            (!rows.is_empty()).then(|| export.to_table(rows, |index| files.entry(index), TableFormat::TSV))
+        });
+        if let Some(text) = text {
+            self.copy_to_clipboard(&text);
+        }
+    }
+    
+    fn show_export_menu(&self) {
+        unsafe {
+            let menu = CreatePopupMenu().unwrap();
+            
+            AppendMenuW(menu, MF_STRING, ID_EXPORT_TREEMAP_HTML as usize, w!("Size Treemap (HTML)...")).ok();
+            AppendMenuW(menu, MF_STRING, ID_EXPORT_TREE_JSON as usize, w!("Size Tree (JSON)...")).ok();
+            AppendMenuW(menu, MF_SEPARATOR, 0, None).ok();
+            AppendMenuW(menu, MF_STRING, ID_EXPORT_LIST_CSV as usize, w!("File List (CSV)...")).ok();
+            AppendMenuW(menu, MF_STRING, ID_EXPORT_LIST_TSV as usize, w!("File List (TSV)...")).ok();
+            AppendMenuW(menu, MF_STRING, ID_EXPORT_LIST_HTML as usize, w!("File List (HTML)...")).ok();
+            AppendMenuW(menu, MF_STRING, ID_EXPORT_LIST_JSON as usize, w!("File List (JSON)...")).ok();
+            AppendMenuW(menu, MF_STRING, ID_EXPORT_LIST_JSONL as usize, w!("File List (JSON Lines)...")).ok();
+            AppendMenuW(menu, MF_SEPARATOR, 0, None).ok();
+            let bom_flags = if self.export_bom.load(Ordering::SeqCst) { MF_STRING | MF_CHECKED } else { MF_STRING };
+            AppendMenuW(menu, bom_flags, ID_EXPORT_BOM as usize, w!("UTF-8 BOM in CSV/TSV (for Excel)")).ok();
+            
+            // Drop the menu down from the button
+            let mut rect = RECT::default();
+            GetWindowRect(self.export_button, &mut rect).ok();
+            
+            let cmd = TrackPopupMenuEx(
+                menu,
+                (TPM_RETURNCMD | TPM_LEFTBUTTON).0,
+                rect.left,
+                rect.bottom,
+                self.hwnd,
+                None,
+            );
+            
+            DestroyMenu(menu).ok();
+            
+            match cmd.0 as i32 {
+                ID_EXPORT_TREEMAP_HTML => self.export_size_tree(true),
+                ID_EXPORT_TREE_JSON => self.export_size_tree(false),
+                ID_EXPORT_LIST_CSV => self.export_list(ListFormat::Table(TableFormat::CSV)),
+                ID_EXPORT_LIST_TSV => self.export_list(ListFormat::Table(TableFormat::TSV)),
+                ID_EXPORT_LIST_HTML => self.export_list(ListFormat::Html),
+                ID_EXPORT_LIST_JSON => self.export_list(ListFormat::Json),
+                ID_EXPORT_LIST_JSONL => self.export_list(ListFormat::JsonLines),
+                ID_EXPORT_BOM => {
+                    self.export_bom.fetch_xor(true, Ordering::SeqCst);
+                }
+                _ => {}
+            }
+        }
+    }
+    
+    fn show_group_menu(&self) {
+        unsafe {
+            let menu = CreatePopupMenu().unwrap();
+            let current = *self.group_by.lock().unwrap();
+            let flags = |checked: bool| if checked { MF_STRING | MF_CHECKED } else { MF_STRING };
+            
+            AppendMenuW(menu, flags(current.is_none()), ID_GROUP_NONE as usize, w!("No Groups")).ok();
+            AppendMenuW(menu, MF_SEPARATOR, 0, None).ok();
+            for (i, by) in GroupBy::ALL.into_iter().enumerate() {
+                let label = HSTRING::from(by.label());
+                AppendMenuW(menu, flags(current == Some(by)), ID_GROUP_FIRST as usize + i, &label).ok();
+            }
+            
+            let mut rect = RECT::default();
+            GetWindowRect(self.group_button, &mut rect).ok();
+            
+            let cmd = TrackPopupMenuEx(
+                menu,
+                (TPM_RETURNCMD | TPM_LEFTBUTTON).0,
+                rect.left,
+                rect.bottom,
+                self.hwnd,
+                None,
+            );
+            
+            DestroyMenu(menu).ok();
+            
+            let group_by = match cmd.0 {
+                ID_GROUP_NONE => None,
+                cmd => match usize::try_from(cmd - ID_GROUP_FIRST).ok().and_then(|i| GroupBy::ALL.get(i)) {
+                    Some(&by) => Some(by),
+                    None => return,
+                },
+            };
+            *self.group_by.lock().unwrap() = group_by;
+            
+            let label = match group_by {
+                Some(by) => format!("Group: {} ▾", by.label()),
+                None => "Group by ▾".to_string(),
+            };
+            SetWindowTextW(self.group_button, &HSTRING::from(label)).ok();
+            self.refresh_list_view();
+        }
+    }
+    
+    fn search_text(&self) -> String {
+        unsafe {
+            let mut buffer = [0u16; 512];
+            let len = GetWindowTextW(self.search_box, &mut buffer) as usize;
+            String::from_utf16_lossy(&buffer[..len])
+        }
+    }
+    
+    // Adds the current search to the history once the user has settled on it
+    fn remember_search(&mut self) {
+        let text = self.search_text();
+        if text.trim().is_empty() || self.search_library.history.first().map(String::as_str) == Some(text.trim()) {
+            return;
+        }
+        self.search_library.record(&text);
+        self.save_search_library();
+    }
+    
+    fn save_search_library(&self) {
+        if let Some(path) = &self.search_library_path {
+            if let Err(e) = self.search_library.save(path) {
+                unsafe {
+                    SetWindowTextW(self.status_bar, &HSTRING::from(format!("Could not save searches: {}", e))).ok();
+                }
+            }
+        }
+    }
+    
+    fn show_searches_menu(&mut self) {
+        unsafe {
+            let menu = CreatePopupMenu().unwrap();
+            let current = self.search_text();
+            let library = &self.search_library;
+            
+            let save_flags = if current.trim().is_empty() { MF_STRING | MF_GRAYED } else { MF_STRING };
+            AppendMenuW(menu, save_flags, ID_SEARCH_SAVE as usize, w!("Save Current Search...")).ok();
+            
+            if !library.saved.is_empty() {
+                AppendMenuW(menu, MF_SEPARATOR, 0, None).ok();
+                for (i, search) in library.saved.iter().enumerate() {
+                    let label = format!("{}\t{}", search.name.replace('&', "&&"), search.query.replace('&', "&&"));
+                    AppendMenuW(menu, MF_STRING, ID_SEARCH_SAVED_FIRST as usize + i, &HSTRING::from(label)).ok();
+                }
+                let delete_menu = CreatePopupMenu().unwrap();
+                for (i, search) in library.saved.iter().enumerate() {
+                    AppendMenuW(delete_menu, MF_STRING, ID_SEARCH_DELETE_FIRST as usize + i, &HSTRING::from(search.name.replace('&', "&&"))).ok();
+                }
+                AppendMenuW(menu, MF_POPUP, delete_menu.0 as usize, w!("Delete Saved Search")).ok();
+            }
+            
+            if !library.history.is_empty() {
+                AppendMenuW(menu, MF_SEPARATOR, 0, None).ok();
+                AppendMenuW(menu, MF_STRING | MF_GRAYED, 0, w!("Recent Searches")).ok();
+                for (i, query) in library.history.iter().enumerate() {
+                    AppendMenuW(menu, MF_STRING, ID_SEARCH_HISTORY_FIRST as usize + i, &HSTRING::from(query.replace('&', "&&"))).ok();
+                }
+                AppendMenuW(menu, MF_STRING, ID_SEARCH_CLEAR_HISTORY as usize, w!("Clear History")).ok();
+            }
+            
+            // Drop the menu down from under the search box
+            let mut rect = RECT::default();
+            GetWindowRect(self.search_box, &mut rect).ok();
+            
+            let cmd = TrackPopupMenuEx(
+                menu,
+                (TPM_RETURNCMD | TPM_LEFTBUTTON).0,
+                rect.left,
+                rect.bottom,
+                self.hwnd,
+                None,
+            );
+            
+            // Destroying the menu destroys the submenu too
+            DestroyMenu(menu).ok();
+            
+            let cmd = cmd.0;
+            let position = |first: i32| usize::try_from(cmd - first).ok();
+            match cmd {
+                ID_SEARCH_SAVE => {
+                    // Suggest the name of a saved search this already is
+                    let existing = self.search_library.saved.iter()
+                        .find(|search| search.query == current.trim())
+                        .map(|search| search.name.clone())
+                        .unwrap_or_default();
+                    if let Some(name) = self.prompt_text("Save Search", &format!("Name for \"{}\":", current.trim()), &existing) {
+                        self.search_library.save_search(&name, &current);
+                        self.save_search_library();
+                    }
+                }
+                ID_SEARCH_CLEAR_HISTORY => {
+                    self.search_library.history.clear();
+                    self.save_search_library();
+                }
+                _ if cmd >= ID_SEARCH_HISTORY_FIRST => {
+                    if let Some(query) = position(ID_SEARCH_HISTORY_FIRST).and_then(|i| self.search_library.history.get(i)).cloned() {
+                        self.set_search_text(&query);
+                        self.remember_search();
+                    }
+                }
+                _ if cmd >= ID_SEARCH_DELETE_FIRST => {
+                    if let Some(name) = position(ID_SEARCH_DELETE_FIRST).and_then(|i| self.search_library.saved.get(i)).map(|s| s.name.clone()) {
+                        self.search_library.remove_search(&name);
+                        self.save_search_library();
+                    }
+                }
+                _ if cmd >= ID_SEARCH_SAVED_FIRST => {
+                    if let Some(query) = position(ID_SEARCH_SAVED_FIRST).and_then(|i| self.search_library.saved.get(i)).map(|s| s.query.clone()) {
+                        self.set_search_text(&query);
+                        self.remember_search();
+                    }
+                }
+                _ => {}
+            }
+            let _ = SetFocus(self.search_box);
+        }
+    }
+    
+    // A small modal box asking for one line of text: Enter accepts, Escape cancels
+    fn prompt_text(&self, title: &str, prompt: &str, initial: &str) -> Option<String> {
+        unsafe {
+            let instance = GetModuleHandleW(None).ok()?;
+            let (width, height) = (380, 140);
+            let mut owner = RECT::default();
+            GetWindowRect(self.hwnd, &mut owner).ok();
+            
+            // The standard dialog class gives the frame and keyboard focus handling
+            let dialog = CreateWindowExW(
+                WS_EX_DLGMODALFRAME,
+                w!("#32770"),
+                &HSTRING::from(title),
+                WS_POPUP | WS_CAPTION | WS_VISIBLE,
+                owner.left + (owner.right - owner.left - width) / 2,
+                owner.top + (owner.bottom - owner.top - height) / 2,
+                width, height,
+                self.hwnd,
+                None,
+                instance,
+                None,
+            ).ok()?;
+            
+            CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("STATIC"),
+                &HSTRING::from(prompt),
+                WS_CHILD | WS_VISIBLE | WINDOW_STYLE(SS_ENDELLIPSIS),
+                12, 12, width - 40, 20,
+                dialog,
+                None,
+                instance,
+                None,
+            ).ok();
+            let edit = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("EDIT"),
+                &HSTRING::from(initial),
+                WS_CHILD | WS_VISIBLE | WS_BORDER | WINDOW_STYLE(ES_AUTOHSCROLL as u32),
+                12, 36, width - 40, 24,
+                dialog,
+                None,
+                instance,
+                None,
+            ).ok();
+            CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("STATIC"),
+                w!("Enter to save, Escape to cancel"),
+                WS_CHILD | WS_VISIBLE,
+                12, 68, width - 40, 20,
+                dialog,
+                None,
+                instance,
+                None,
+            ).ok();
+            let Some(edit) = edit else {
+                DestroyWindow(dialog).ok();
+                return None;
+            };
+            
+            let _ = EnableWindow(self.hwnd, false);
+            let _ = SetFocus(edit);
+            SendMessageW(edit, EM_SETSEL, WPARAM(0), LPARAM(-1));
+            
+            let mut accepted = false;
+            let mut msg = MSG::default();
+            loop {
+                if !GetMessageW(&mut msg, None, 0, 0).as_bool() {
+                    // Leave the quit for the main loop
+                    PostQuitMessage(msg.wParam.0 as i32);
+                    break;
+                }
+                if msg.message == WM_KEYDOWN && msg.hwnd == edit {
+                    match msg.wParam.0 {
+                        0x0D => {
+                            accepted = true;
+                            break;
+                        }
+                        0x1B => break,
+                        _ => {}
+                    }
+                }
+                let _ = TranslateMessage(&msg);
+                DispatchMessageW(&msg);
+            }
+            
+            let mut buffer = [0u16; 256];
+            let len = GetWindowTextW(edit, &mut buffer) as usize;
+            let text = String::from_utf16_lossy(&buffer[..len]).trim().to_string();
+            
+            let _ = EnableWindow(self.hwnd, true);
+            DestroyWindow(dialog).ok();
+            
+            (accepted && !text.is_empty()).then_some(text)
+        }
+    }
+    
+    // Offers completions for the word before the caret in the search box
+    fn show_completion_menu(&self) {
+        unsafe {
+            let mut buffer = [0u16; 512];
+            let len = GetWindowTextW(self.search_box, &mut buffer) as usize;
+            let text = String::from_utf16_lossy(&buffer[..len]);
+            
+            // Edit controls count UTF-16 units; completions count characters
+            let mut caret = 0u32;
+            SendMessageW(self.search_box, EM_GETSEL, WPARAM(0), LPARAM(&mut caret as *mut u32 as isize));
+            let caret = (caret as usize).min(len);
+            let cursor = String::from_utf16_lossy(&buffer[..caret]).chars().count();
+            
+            let completions = filter::complete(&text, cursor, &self.files.lock().unwrap());
+            if completions.is_empty() {
+                return;
             }
             
             let menu = CreatePopupMenu().unwrap();
+            for (i, completion) in completions.iter().enumerate() {
+                let label = if completion.detail.is_empty() {
+                    completion.text.clone()
+                } else {
+                    format!("{}\t{}", completion.text, completion.detail)
+                };
+                AppendMenuW(menu, MF_STRING, i + 1, &HSTRING::from(label)).ok();
+            }
             
-            AppendMenuW(menu, MF_STRING, ID_CTX_OPEN as usize, w!("Open\tEnter")).ok();
-            AppendMenuW(menu, MF_STRING, ID_CTX_OPEN_FOLDER as usize, w!("Open Containing Folder\tCtrl+O")).ok();
-            AppendMenuW(menu, MF_SEPARATOR, 0, w!("")).ok();
-            AppendMenuW(menu, MF_STRING, ID_CTX_COPY_PATH as usize, w!("Copy Full Path")).ok();
-            AppendMenuW(menu, MF_STRING, ID_CTX_COPY_NAME as usize, w!("Copy File Name")).ok();
-            
-            let mut cursor_pos = POINT::default();
-            GetCursorPos(&mut cursor_pos).ok();
+            // Drop the menu down from the caret
+            let mut point = POINT::default();
+            GetCaretPos(&mut point).ok();
+            let _ = ClientToScreen(self.search_box, &mut point);
             
             let cmd = TrackPopupMenuEx(
                 menu,
-                (TPM_RETURNCMD | TPM_RIGHTBUTTON).0,
-                cursor_pos.x,
-                cursor_pos.y,
+                (TPM_RETURNCMD | TPM_LEFTBUTTON).0,
+                point.x,
+                point.y + 20,
                 self.hwnd,
                 None,
             );
             
             DestroyMenu(menu).ok();
             
-            match cmd.0 as i32 {
-                ID_CTX_OPEN => self.handle_list_item_activate(),
-                ID_CTX_OPEN_FOLDER => self.open_containing_folder(),
-                ID_CTX_COPY_PATH => self.copy_selected_path(),
-                ID_CTX_COPY_NAME => self.copy_selected_name(),
-                _ => {}
-            }
+            let Some(choice) = (cmd.0 as usize).checked_sub(1).and_then(|i| completions.get(i)) else {
+                return;
+            };
+            
+            // Replace the start of the word up to the caret; the edit triggers a search
+            let start: usize = text.chars().take(choice.start).map(char::len_utf16).sum();
+            SendMessageW(self.search_box, EM_SETSEL, WPARAM(start), LPARAM(caret as isize));
+            let replacement = HSTRING::from(choice.text.as_str());
+            SendMessageW(self.search_box, EM_REPLACESEL, WPARAM(1), LPARAM(replacement.as_ptr() as isize));
         }
     }
     
-    fn copy_list_to_clipboard(&self) {
-        let files = self.filtered_files.lock().unwrap();
-        if files.is_empty() {
+    // Folder size breakdown of the files currently in the list
+    fn export_size_tree(&self, as_html: bool) {
+        let (filter, extension) = if as_html {
+            ("HTML Files (*.html)\0*.html\0All Files (*.*)\0*.*\0\0", "html")
+        } else {
+            ("JSON Files (*.json)\0*.json\0All Files (*.*)\0*.*\0\0", "json")
+        };
+        
+        let Some(target) = self.prompt_save_path("size-treemap", filter, extension) else {
             return;
-        }
-
-        let mut result = String::new();
+        };
         
-        // Add header
-        result.push_str("File List\n");
-        result.push_str("=========\n\n");
+        let tree = {
+            let files = self.files.lock().unwrap();
+            let filtered = self.filtered_files.lock().unwrap();
+            DirNode::build(&self.scan_root, filtered.iter().map(|&i| files.entry(i)), self.size_mode())
+        };
         
-        // Add column headers
-        result.push_str("Name\t");
-        if self.show_type.load(Ordering::SeqCst) {
-            result.push_str("Type\t");
-        }
-        if self.show_size.load(Ordering::SeqCst) {
-            result.push_str("Size\t");
-        }
-        if self.show_modified.load(Ordering::SeqCst) {
-            result.push_str("Modified\t");
-        }
-        if self.show_path.load(Ordering::SeqCst) {
-            result.push_str("Path");
-        }
-        result.push_str("\n");
+        // Cap the files listed per folder so huge folders don't bloat the page
+        let contents = if as_html {
+            tree.to_html(200)
+        } else {
+            serde_json::to_string_pretty(&tree.to_json(1000)).unwrap_or_default()
+        };
         
-        // Add separator
-        for _ in 0..80 {
-            result.push('-');
-        }
-        result.push_str("\n");
+        self.write_export(&target, contents.as_bytes());
+    }
+    
+    fn export_list(&self, format: ListFormat) {
+        let (filter, extension) = match format {
+            ListFormat::Table(TableFormat::CSV) => ("CSV Files (*.csv)\0*.csv\0All Files (*.*)\0*.*\0\0", "csv"),
+            ListFormat::Table(_) => ("TSV Files (*.tsv)\0*.tsv\0Text Files (*.txt)\0*.txt\0All Files (*.*)\0*.*\0\0", "tsv"),
+            ListFormat::Html => ("HTML Files (*.html)\0*.html\0All Files (*.*)\0*.*\0\0", "html"),
+            ListFormat::Json => ("JSON Files (*.json)\0*.json\0All Files (*.*)\0*.*\0\0", "json"),
+            ListFormat::JsonLines => ("JSON Lines Files (*.jsonl)\0*.jsonl\0All Files (*.*)\0*.*\0\0", "jsonl"),
+        };
+        let format = match format {
+            ListFormat::Table(table) => ListFormat::Table(table.with_bom(self.export_bom.load(Ordering::SeqCst))),
+            format => format,
+        };
         
-        // Add file data
-        for file in files.iter() {
-            result.push_str(&format!("{} {}\t", file.get_icon(), file.name));
-            
-            if self.show_type.load(Ordering::SeqCst) {
-                let type_str = file.extension.clone()
-                    .unwrap_or_else(|| "File".to_string())
-                    .to_uppercase();
-                result.push_str(&format!("{}\t", type_str));
+        let Some(target) = self.prompt_save_path("file-list", filter, extension) else {
+            return;
+        };
+        
+        let complete = !self.is_scanning.load(Ordering::SeqCst);
+        let query = self.search_text();
+        let skipped_folders = self.skipped.lock().unwrap().len();
+        let mut contents = Vec::new();
+        self.with_list_export(|list, rows, files| {
+            let summary = ScanSummary {
+                root: self.scan_root.clone(),
+                exported_at: iso_time(SystemTime::now()),
+                query,
+                size_mode: list.context.size_mode,
+                scanned_files: files.len(),
+                scanned_size: files.total_size(list.context.size_mode),
+                skipped_folders,
+                complete,
+                scan_ms: complete.then(|| self.scan_elapsed_ms.load(Ordering::SeqCst) as u64),
+            };
+            // Writing to memory can't fail
+            let _ = list.write(&mut contents, format, &summary, rows, |index| files.entry(index));
+        });
+        
+        self.write_export(&target, &contents);
+    }
+    
+    // Opens the statistics window, or brings it forward if it is already open
+    fn show_statistics(&mut self) {
+        unsafe {
+            if IsWindow(self.statistics_window).as_bool() {
+                let _ = SetForegroundWindow(self.statistics_window);
+                return;
             }
+            let Ok(instance) = GetModuleHandleW(None) else {
+                return;
+            };
+            let Ok(window) = CreateWindowExW(
+                WINDOW_EX_STYLE::default(),
+                w!("FileListStatistics"),
+                w!("Statistics - File List Generator"),
+                WS_OVERLAPPEDWINDOW | WS_VISIBLE,
+                CW_USEDEFAULT,
+                CW_USEDEFAULT,
+                640,
+                600,
+                self.hwnd,
+                None,
+                instance,
+                None,
+            ) else {
+                return;
+            };
             
-            if self.show_size.load(Ordering::SeqCst) {
-                result.push_str(&format!("{}\t", file.size_formatted()));
+            let text = CreateWindowExW(
+                WS_EX_CLIENTEDGE,
+                w!("EDIT"),
+                w!(""),
+                WS_CHILD | WS_VISIBLE | WS_VSCROLL | WS_HSCROLL
+                    | WINDOW_STYLE((ES_MULTILINE | ES_READONLY | ES_AUTOVSCROLL | ES_AUTOHSCROLL) as u32),
+                0, 0, 0, 0,
+                window,
+                HMENU(ID_STATS_TEXT as _),
+                instance,
+                None,
+            ).unwrap_or_default();
+            for (id, label) in [(ID_STATS_SAVE_JSON, w!("Save JSON...")), (ID_STATS_SAVE_HTML, w!("Save HTML..."))] {
+                CreateWindowExW(
+                    WINDOW_EX_STYLE::default(),
+                    w!("BUTTON"),
+                    label,
+                    WS_CHILD | WS_VISIBLE | WINDOW_STYLE(BS_PUSHBUTTON as u32),
+                    0, 0, 0, 0,
+                    window,
+                    HMENU(id as _),
+                    instance,
+                    None,
+                ).ok();
             }
             
-            if self.show_modified.load(Ordering::SeqCst) {
-                result.push_str(&format!("{}\t", file.modified_formatted()));
+            // The report lines up in columns, so it needs a fixed-width font
+            if self.statistics_font.is_invalid() {
+                self.statistics_font = CreateFontW(
+                    -13, 0, 0, 0, FW_NORMAL.0 as i32, 0, 0, 0,
+                    DEFAULT_CHARSET.0 as u32, OUT_DEFAULT_PRECIS.0 as u32, CLIP_DEFAULT_PRECIS.0 as u32, CLEARTYPE_QUALITY.0 as u32,
+                    (FIXED_PITCH.0 | FF_MODERN.0) as u32,
+                    w!("Consolas"),
+                );
             }
+            SendMessageW(text, WM_SETFONT, WPARAM(self.statistics_font.0 as usize), LPARAM(1));
+            layout_statistics(window);
             
-            if self.show_path.load(Ordering::SeqCst) {
-                let path_str = file.path.parent()
-                    .map(|p| p.to_string_lossy().to_string())
-                    .unwrap_or_default();
-                result.push_str(&path_str);
+            self.statistics_window = window;
+            self.statistics_text = text;
+            self.update_statistics(true);
+        }
+    }
+    
+    // Recomputes the open statistics for the listed files; while a scan is adding
+    // files that happens at most once a second unless `force`d
+    fn update_statistics(&self, force: bool) {
+        unsafe {
+            if !IsWindow(self.statistics_window).as_bool() {
+                return;
             }
-            
-            result.push_str("\n");
         }
+        let mut updated = self.statistics_updated.lock().unwrap();
+        let recent = updated.is_some_and(|at| at.elapsed() < Duration::from_secs(1));
+        if !force && recent && self.is_scanning.load(Ordering::SeqCst) {
+            return;
+        }
+        *updated = Some(Instant::now());
+        drop(updated);
+        
+        let report = self.compute_statistics().to_text();
+        unsafe {
+            SetWindowTextW(self.statistics_text, &HSTRING::from(report)).ok();
+        }
+    }
+    
+    fn compute_statistics(&self) -> Statistics {
+        let files = self.files.lock().unwrap();
+        let filtered = self.filtered_files.lock().unwrap();
+        Statistics::compute(filtered.iter().map(|&index| files.entry(index)), self.size_mode(), SystemTime::now())
+    }
+    
+    fn export_statistics(&self, as_html: bool) {
+        let (filter, extension) = if as_html {
+            ("HTML Files (*.html)\0*.html\0All Files (*.*)\0*.*\0\0", "html")
+        } else {
+            ("JSON Files (*.json)\0*.json\0All Files (*.*)\0*.*\0\0", "json")
+        };
+        
+        let Some(target) = self.prompt_save_path("statistics", filter, extension) else {
+            return;
+        };
+        
+        let statistics = self.compute_statistics();
+        let contents = if as_html {
+            statistics.to_html(&self.scan_root.display().to_string())
+        } else {
+            serde_json::to_string_pretty(&statistics.to_json()).unwrap_or_default()
+        };
+        
+        self.write_export(&target, contents.as_bytes());
+    }
+    
+    // Shows a Save As dialog and returns the chosen path
+    fn prompt_save_path(&self, default_name: &str, filter: &str, extension: &str) -> Option<PathBuf> {
+        let mut buffer = [0u16; 1024];
+        for (slot, c) in buffer.iter_mut().zip(default_name.encode_utf16()) {
+            *slot = c;
+        }
+        let filter = HSTRING::from(filter);
+        let extension = HSTRING::from(extension);
+        
+        let mut ofn = OPENFILENAMEW {
+            lStructSize: std::mem::size_of::<OPENFILENAMEW>() as u32,
+            hwndOwner: self.hwnd,
+            lpstrFilter: PCWSTR(filter.as_ptr()),
+            lpstrFile: PWSTR(buffer.as_mut_ptr()),
+            nMaxFile: buffer.len() as u32,
+            lpstrDefExt: PCWSTR(extension.as_ptr()),
+            Flags: OFN_OVERWRITEPROMPT | OFN_PATHMUSTEXIST | OFN_EXPLORER,
+            ..Default::default()
+        };
         
-        // Add summary
-        result.push_str(&format!("\nTotal: {} files\n", files.len()));
+        unsafe {
+            if !GetSaveFileNameW(&mut ofn).as_bool() {
+                return None;
+            }
+        }
         
-        drop(files);
-        self.copy_to_clipboard(&result);
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        Some(PathBuf::from(String::from_utf16_lossy(&buffer[..len])))
+    }
+    
+    fn write_export(&self, target: &Path, contents: &[u8]) {
+        unsafe {
+            match std::fs::write(target, contents) {
+                Ok(()) => {
+                    let status = format!("Exported to {}", target.display());
+                    SetWindowTextW(self.status_bar, &HSTRING::from(status)).ok();
+                }
+                Err(err) => {
+                    let message = format!("Could not write {}:\n{}", target.display(), err);
+                    MessageBoxW(
+                        self.hwnd,
+                        &HSTRING::from(message),
+                        w!("Export - File List Generator"),
+                        MB_OK | MB_ICONERROR,
+                    );
+                }
+            }
+        }
     }
     
     fn copy_selected_name(&self) {
@@ -1023,8 +2064,8 @@ impl FileListWindow {
             );
             
             if selected.0 >= 0 {
-                if let Some(file) = self.filtered_files.lock().unwrap().get(selected.0 as usize) {
-                    self.copy_to_clipboard(&file.name);
+                if let Some(name) = self.with_file_at(selected.0 as usize, |file| file.name().to_string()) {
+                    self.copy_to_clipboard(&name);
                 }
             }
         }
@@ -1040,15 +2081,14 @@ impl FileListWindow {
             );
             
             if selected.0 >= 0 {
-                let files = self.filtered_files.lock().unwrap();
-                if let Some(file) = files.get(selected.0 as usize) {
-                    let folder_path = if file.is_dir {
-                        file.path.clone()
+                let folder_path = self.with_file_at(selected.0 as usize, |file| {
+                    if file.is_dir() {
+                        file.full_path().into_owned()
                     } else {
-                        file.path.parent().map(|p| p.to_path_buf()).unwrap_or_else(|| file.path.clone())
-                    };
-                    drop(files); // Release lock before spawning thread
-                    
+                        file.parent().to_path_buf()
+                    }
+                });
+                if let Some(folder_path) = folder_path {
                     thread::spawn(move || {
                         let path_str = folder_path.to_string_lossy();
                         let path_hstring = HSTRING::from(path_str.as_ref());
@@ -1078,14 +2118,8 @@ impl FileListWindow {
             );
             
             if selected.0 >= 0 {
-                let files = self.filtered_files.lock().unwrap();
-                if let Some(file) = files.get(selected.0 as usize) {
-                    let path = file.path.clone();
-                    let is_dir = file.is_dir;
-                    
-                    // Drop the lock before spawning thread
-                    drop(files);
-                    
+                let target = self.with_file_at(selected.0 as usize, |file| (file.full_path().into_owned(), file.is_dir()));
+                if let Some((path, is_dir)) = target {
                     // Open file/folder in a separate thread to prevent UI freeze
                     thread::spawn(move || {
                         let path_str = path.to_string_lossy();
@@ -1120,7 +2154,7 @@ impl FileListWindow {
 
     fn select_first_item(&self) {
         unsafe {
-            let count = self.filtered_files.lock().unwrap().len();
+            let count = self.row_count();
             if count > 0 {
                 // Clear all selections first
                 SendMessageW(
@@ -1287,11 +2321,38 @@ impl FileListWindow {
         }
     }
     
+    fn show_skipped_dialog(&self) {
+        let skipped = self.skipped.lock().unwrap();
+        if skipped.is_empty() {
+            return;
+        }
+        
+        let mut text = String::from("These folders were not scanned:\n\n");
+        for (path, reason) in skipped.iter().take(30) {
+            text.push_str(&format!("• {} ({})\n", path.display(), reason));
+        }
+        if skipped.len() > 30 {
+            text.push_str(&format!("\n...and {} more", skipped.len() - 30));
+        }
+        drop(skipped);
+        
+        unsafe {
+            MessageBoxW(
+                self.hwnd,
+                &HSTRING::from(text),
+                w!("Skipped Folders - File List Generator"),
+                MB_OK | MB_ICONINFORMATION,
+            );
+        }
+    }
+    
     fn show_help_dialog(&self) {
         unsafe {
             let help_text = "File List Generator - Keyboard Shortcuts & Tips\n\n\
                             KEYBOARD SHORTCUTS:\n\
                             • Ctrl+F: Focus search box\n\
+                            • Ctrl+Space: Suggest fields, extensions, folders and units\n\
+                            • Alt+Down or ▾: Saved searches and recent searches\n\
                             • Ctrl+O: Open containing folder\n\
                             • Enter: Open selected file/folder\n\
                             • Escape: Clear search\n\
@@ -1301,12 +2362,32 @@ impl FileListWindow {
                             • filename - Search by name\n\
                             • .txt - Search by extension\n\
                             • >10mb - Files larger than 10MB\n\
-                            • <1kb - Files smaller than 1KB\n\n\
+                            • <1kb - Files smaller than 1KB\n\
+                            • 10mb..100mb - Sizes in a range (also >=, <=)\n\
+                            • ext:pdf size:>5mb path:drawings - All must match\n\
+                            • ext:dwg OR ext:dxf - Either may match\n\
+                            • type:image, type:document,video - By file category\n\
+                            • contains:\"FT-101\" - Text inside files, with matching lines\n\
+                            • -name:backup or NOT name:backup - Exclude\n\
+                            • modified:>=2025-01-01 - Changed since a date\n\
+                            • modified:thisweek, modified:<30d - Recently changed\n\
+                            • (a OR b) \"exact phrase\" - Group and quote\n\
+                            • *.dwg, drawings/**/*.pdf - Wildcard patterns\n\
+                            • /DWG-\\d{4}/ - Regular expression\n\
+                            • Tick 'Fuzzy' to find sitplncur as site plan current\n\
+                            • resume finds Résumé; tick 'Accents' to match accents exactly\n\
+                            • Matched text is highlighted in the Name and Path columns\n\n\
                             FEATURES:\n\
                             • Double-click to open files/folders\n\
                             • Drag & drop folders to scan them\n\
                             • Use checkboxes to show/hide columns\n\
-                            • Click 'Copy List' to export all data\n\n\
+                            • Click a column header to sort; Shift+click to add more sort columns\n\
+                            • Click 'Copy List' to copy the list as tab-separated values\n\
+                            • Click 'Export...' for a folder size treemap or to save the list as CSV, TSV, JSON or HTML\n\
+                            • Click 'Group by' to group the list with subtotals\n\
+                            • Click 'Statistics' for totals, breakdowns and the largest files\n\
+                            • Tick 'Disk usage' to show space used on disk instead of file length\n\
+                            • Double-click the status bar to list skipped folders\n\n\
                             Developed by David Landry";
             
             MessageBoxW(
@@ -1319,9 +2400,12 @@ impl FileListWindow {
     }
     
     fn start_new_scan(&mut self, path: std::path::PathBuf) {
-        // Clear existing data
+        // Clear existing data; a search still running was for the old rows
+        self.search_generation.fetch_add(1, Ordering::SeqCst);
         self.files.lock().unwrap().clear();
         self.filtered_files.lock().unwrap().clear();
+        self.match_scores.lock().unwrap().clear();
+        self.content_lines.lock().unwrap().clear();
         
         // Update UI
         self.load_directory(&path);
@@ -1331,8 +2415,9 @@ impl FileListWindow {
         self.set_update_receiver(receiver);
         
         let scanner_path = path.clone();
+        let options = self.scan_options.clone();
         std::thread::spawn(move || {
-            let scanner = crate::scanner::Scanner::with_sender(sender);
+            let scanner = crate::scanner::Scanner::with_sender(sender).with_options(options);
             scanner.scan_directory(&scanner_path);
         });
     }
@@ -1347,7 +2432,7 @@ impl FileListWindow {
                     // Request item notifications
                     LRESULT(CDRF_NOTIFYITEMDRAW as isize)
                 }
-                CDDS_ITEMPREPAINT => {
+                CDDS_ITEMPREPAINT | CDDS_SUBITEMPREPAINT => {
                     let item_index = (*lpnmlvcd).nmcd.dwItemSpec;
                     
                     // Alternating row colors
@@ -1357,46 +2442,166 @@ impl FileListWindow {
                         (*lpnmlvcd).clrTextBk = 0xFFFFFF; // White for odd rows
                     }
                     
+                    if self.is_heading_row(item_index) {
+                        (*lpnmlvcd).clrTextBk = GROUP_HEADING_COLOR;
+                    }
+                    
                     // Use standard black text for all files
                     (*lpnmlvcd).clrText = 0x000000;
                     
-                    LRESULT(CDRF_NEWFONT as isize)
+                    if stage == CDDS_ITEMPREPAINT {
+                        // Ask for each cell so matches can be highlighted
+                        LRESULT((CDRF_NEWFONT | CDRF_NOTIFYSUBITEMDRAW) as isize)
+                    } else if self.draw_match_highlights(&*lpnmlvcd) {
+                        LRESULT(CDRF_SKIPDEFAULT as isize)
+                    } else {
+                        LRESULT(CDRF_NEWFONT as isize)
+                    }
                 }
                 _ => LRESULT(0)
             }
         }
     }
 
+    // Draws a Name or Path cell with the parts the search matched on a highlight
+    // background. Returns false to let the list view draw the cell itself.
+    fn draw_match_highlights(&self, draw: &NMLVCUSTOMDRAW) -> bool {
+        let row = draw.nmcd.dwItemSpec;
+        let column = draw.iSubItem;
+        let path_column = self.show_path.load(Ordering::SeqCst).then(|| {
+            1 + self.type_columns() + [&self.show_size, &self.show_modified]
+                .iter()
+                .filter(|flag| flag.load(Ordering::SeqCst))
+                .count() as i32
+        });
+        if column != 0 && Some(column) != path_column {
+            return false;
+        }
+
+        unsafe {
+            // Selected rows keep the standard selection colors
+            let state = SendMessageW(self.list_view, LVM_GETITEMSTATE, WPARAM(row), LPARAM(LVIS_SELECTED.0 as isize));
+            if state.0 as u32 & LVIS_SELECTED.0 != 0 {
+                return false;
+            }
+        }
+
+        let (prefix, text, ranges) = {
+            let files = self.files.lock().unwrap();
+            let filter = self.filter.lock().unwrap();
+            let filtered = self.filtered_files.lock().unwrap();
+            let grouped = self.grouped_rows.lock().unwrap();
+            let Some(file) = row_file(&filtered, &grouped, row).and_then(|i| files.get(i)) else {
+                return false;
+            };
+            let Some(ranges) = filter.match_ranges(&file).filter(|ranges| !ranges.is_empty()) else {
+                return false;
+            };
+            if column == 0 {
+                // The Name column shows the icon and a space before the name
+                (format!("{} ", file.get_icon()), file.name().to_string(), ranges.name)
+            } else {
+                (String::new(), file.parent().to_string_lossy().to_string(), ranges.path)
+            }
+        };
+        if ranges.is_empty() {
+            return false;
+        }
+
+        // Split the text into runs that are either all matched or all unmatched
+        let offset = prefix.encode_utf16().count();
+        let ranges = filter::utf16_ranges(&text, &ranges);
+        let mut runs: Vec<(Vec<u16>, bool)> = Vec::new();
+        for (i, unit) in prefix.encode_utf16().chain(text.encode_utf16()).enumerate() {
+            let matched = i >= offset && ranges.iter().any(|r| r.contains(&(i - offset)));
+            if runs.last().map(|(_, m)| *m) != Some(matched) {
+                runs.push((Vec::new(), matched));
+            }
+            runs.last_mut().unwrap().0.push(unit);
+        }
+
+        unsafe {
+            let hdc = draw.nmcd.hdc;
+            let mut rect = RECT { top: column, left: LVIR_LABEL as i32, ..Default::default() };
+            SendMessageW(self.list_view, LVM_GETSUBITEMRECT, WPARAM(row), LPARAM(&mut rect as *mut _ as isize));
+
+            let brush = CreateSolidBrush(COLORREF(draw.clrTextBk));
+            FillRect(hdc, &rect, brush);
+            let _ = DeleteObject(brush);
+
+            SetTextColor(hdc, COLORREF(draw.clrText));
+            SetBkColor(hdc, COLORREF(HIGHLIGHT_COLOR));
+
+            // Match the list view's own text padding
+            let mut x = rect.left + if column == 0 { 2 } else { 6 };
+            for (run, matched) in &runs {
+                let mut size = SIZE::default();
+                let _ = GetTextExtentPoint32W(hdc, run, &mut size);
+                let y = rect.top + (rect.bottom - rect.top - size.cy) / 2;
+                SetBkMode(hdc, if *matched { OPAQUE } else { TRANSPARENT });
+                let _ = ExtTextOutW(hdc, x, y, ETO_CLIPPED, Some(&rect), PCWSTR(run.as_ptr()), run.len() as u32, None);
+                x += size.cx;
+                if x >= rect.right {
+                    break;
+                }
+            }
+        }
+        true
+    }
+
     fn handle_display_info(&self, info: *mut NMLVDISPINFOW) {
         unsafe {
             let info = &mut *info;
             let index = info.item.iItem as usize;
+            let files = self.files.lock().unwrap();
+            let filtered = self.filtered_files.lock().unwrap();
+            let grouped = self.grouped_rows.lock().unwrap();
+            
+            // Group headings show the group and its subtotals, with the total under Size
+            if let Some(ListRow::Heading(group)) = grouped.as_ref().and_then(|grouped| grouped.row(index)) {
+                if info.item.mask & LVIF_TEXT != LIST_VIEW_ITEM_FLAGS(0) {
+                    let size_column = self.column_fields().iter().position(|&field| field == SortField::Size);
+                    let text = match info.item.iSubItem {
+                        0 => format!("{} — {}", group.label, group.summary()),
+                        column if Some(column as usize) == size_column => format_size(group.size),
+                        _ => String::new(),
+                    };
+                    write_display_text(info, &text);
+                }
+                return;
+            }
             
-            if let Some(file) = self.filtered_files.lock().unwrap().get(index) {
+            if let Some(file) = row_file(&filtered, &grouped, index).and_then(|i| files.get(i)) {
                 if info.item.mask & LVIF_TEXT != LIST_VIEW_ITEM_FLAGS(0) {
                     let mut col_index = 0;
                     let mut text = String::new();
                     
                     // Name column is always first
                     if info.item.iSubItem == col_index {
-                        text = format!("{} {}", file.get_icon(), file.name);
+                        text = format!("{} {}", file.get_icon(), file.name());
                     }
                     col_index += 1;
                     
                     // Type column
                     if self.show_type.load(Ordering::SeqCst) {
                         if info.item.iSubItem == col_index {
-                            text = file.extension.clone()
-                                .unwrap_or_else(|| "File".to_string())
+                            text = file.extension()
+                                .unwrap_or("File")
                                 .to_uppercase();
                         }
                         col_index += 1;
+
+                        // Category column
+                        if info.item.iSubItem == col_index {
+                            text = file.category().to_string();
+                        }
+                        col_index += 1;
                     }
                     
                     // Size column
                     if self.show_size.load(Ordering::SeqCst) {
                         if info.item.iSubItem == col_index {
-                            text = file.size_formatted();
+                            text = file.size_formatted(self.size_mode());
                         }
                         col_index += 1;
                     }
@@ -1412,21 +2617,27 @@ impl FileListWindow {
                     // Path column
                     if self.show_path.load(Ordering::SeqCst) {
                         if info.item.iSubItem == col_index {
-                            text = file.path.parent()
-                                .map(|p| p.to_string_lossy().to_string())
-                                .unwrap_or_default();
+                            text = file.parent().to_string_lossy().to_string();
+                        }
+                        col_index += 1;
+                    }
+                    
+                    // Match column
+                    if self.use_fuzzy.load(Ordering::SeqCst) {
+                        if info.item.iSubItem == col_index {
+                            let scores = self.match_scores.lock().unwrap();
+                            text = scores.get(file.index()).copied().unwrap_or(0).to_string();
                         }
+                        col_index += 1;
                     }
                     
-                    let wide = text.encode_utf16().chain(std::iter::once(0))
-                        .take(info.item.cchTextMax as usize)
-                        .collect::<Vec<_>>();
+                    // Lines column
+                    if self.show_lines.load(Ordering::SeqCst) && info.item.iSubItem == col_index {
+                        let lines = self.content_lines.lock().unwrap();
+                        text = format_lines(lines.get(&file.index()).map_or(&[][..], Vec::as_slice));
+                    }
                     
-                    std::ptr::copy_nonoverlapping(
-                        wide.as_ptr(),
-                        info.item.pszText.0,
-                        wide.len().min(info.item.cchTextMax as usize)
-                    );
+                    write_display_text(info, &text);
                 }
             }
         }
@@ -1442,6 +2653,11 @@ impl FileListWindow {
                     let focused = GetFocus();
                     
                     match key {
+                        // Escape in the statistics window closes it
+                        0x1B if !self.statistics_window.is_invalid() && GetAncestor(msg.hwnd, GA_ROOT) == self.statistics_window => {
+                            DestroyWindow(self.statistics_window).ok();
+                            continue;
+                        }
                         // Escape - Clear search from anywhere
                         0x1B => {
                             SetWindowTextW(self.search_box, w!("")).ok();
@@ -1452,16 +2668,23 @@ impl FileListWindow {
                         }
                         // Enter in search box - Focus list and select first item
                         0x0D if focused == self.search_box => {
+                            self.remember_search();
                             let _ = SetFocus(self.list_view);
                             self.select_first_item();
                             continue;
                         }
                         // Down arrow in search box - Move to list
                         0x28 if focused == self.search_box => { // VK_DOWN
+                            self.remember_search();
                             let _ = SetFocus(self.list_view);
                             self.select_first_item();
                             continue;
                         }
+                        // Ctrl+Space in search box - Suggest completions for the word being typed
+                        0x20 if focused == self.search_box && GetKeyState(VK_CONTROL.0 as i32) < 0 => {
+                            self.show_completion_menu();
+                            continue;
+                        }
                         // Tab - Navigate between search and list
                         0x09 => { // VK_TAB
                             if focused == self.search_box {
@@ -1477,6 +2700,12 @@ impl FileListWindow {
                     }
                 }
                 
+                // Alt+Down in search box - Saved searches and history, as in a combo box
+                if msg.message == WM_SYSKEYDOWN && msg.wParam.0 == 0x28 && GetFocus() == self.search_box {
+                    self.show_searches_menu();
+                    continue;
+                }
+                
                 let _ = TranslateMessage(&msg);
                 DispatchMessageW(&msg);
             }
@@ -1485,6 +2714,103 @@ impl FileListWindow {
     }
 }
 
+// Copies a cell's text into the list view's buffer, cut to fit
+unsafe fn write_display_text(info: &mut NMLVDISPINFOW, text: &str) {
+    let wide = text.encode_utf16().chain(std::iter::once(0))
+        .take(info.item.cchTextMax as usize)
+        .collect::<Vec<_>>();
+    
+    std::ptr::copy_nonoverlapping(
+        wide.as_ptr(),
+        info.item.pszText.0,
+        wide.len().min(info.item.cchTextMax as usize)
+    );
+}
+
+// The file index listed at `row`, or None past the end or on a group heading
+fn row_file(filtered: &[usize], grouped: &Option<GroupedRows>, row: usize) -> Option<usize> {
+    match grouped {
+        Some(grouped) => match grouped.row(row)? {
+            ListRow::File(index) => Some(index),
+            ListRow::Heading(_) => None,
+        },
+        None => filtered.get(row).copied(),
+    }
+}
+
+// The order results are kept in: the user's sort columns, or best matches first
+// while fuzzy matching without any
+fn active_sort_keys(sort_keys: &Mutex<Vec<SortKey>>, use_fuzzy: &AtomicBool) -> Vec<SortKey> {
+    let keys = sort_keys.lock().unwrap().clone();
+    if keys.is_empty() && use_fuzzy.load(Ordering::SeqCst) {
+        vec![SortKey::new(SortField::Match, false)]
+    } else {
+        keys
+    }
+}
+
+// Rows of `batch` that `filter` keeps, with their scores, and the matching line
+// numbers of each when it searches contents
+fn match_batch(filter: &Filter, batch: &[FileInfo]) -> (Vec<(usize, u32)>, Vec<(usize, Vec<u32>)>) {
+    let matched: Vec<(usize, u32)> = batch.iter()
+        .enumerate()
+        .filter_map(|(i, item)| filter.score(item).map(|score| (i, score)))
+        .collect();
+    let lines = if filter.searches_content() {
+        matched.iter().map(|&(i, _)| (i, filter.content_lines(&batch[i]))).collect()
+    } else {
+        Vec::new()
+    };
+    (matched, lines)
+}
+
+// "3, 17, 42", cut short for files that match on many lines
+fn format_lines(lines: &[u32]) -> String {
+    const SHOWN: usize = 20;
+    let mut text = lines.iter().take(SHOWN).map(u32::to_string).collect::<Vec<_>>().join(", ");
+    if lines.len() > SHOWN {
+        text.push_str(&format!(" … (+{} more)", lines.len() - SHOWN));
+    }
+    text
+}
+
+// Report text filling the statistics window above a row of save buttons
+unsafe fn layout_statistics(window: HWND) {
+    let mut client = RECT::default();
+    GetClientRect(window, &mut client).ok();
+    let (width, height) = (client.right, client.bottom);
+    if let Ok(text) = GetDlgItem(window, ID_STATS_TEXT) {
+        SetWindowPos(text, None, 0, 0, width, height - 40, SWP_NOZORDER).ok();
+    }
+    for (i, id) in [ID_STATS_SAVE_JSON, ID_STATS_SAVE_HTML].into_iter().enumerate() {
+        if let Ok(button) = GetDlgItem(window, id) {
+            SetWindowPos(button, None, 8 + i as i32 * 108, height - 33, 100, 25, SWP_NOZORDER).ok();
+        }
+    }
+}
+
+unsafe extern "system" fn statistics_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match msg {
+        WM_SIZE => {
+            layout_statistics(hwnd);
+            LRESULT(0)
+        }
+        WM_COMMAND => {
+            // The save buttons are handled by the main window, which has the list
+            if let Ok(owner) = GetWindow(hwnd, GW_OWNER) {
+                SendMessageW(owner, WM_COMMAND, wparam, lparam);
+            }
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
 unsafe extern "system" fn window_proc(
     hwnd: HWND,
     msg: u32,
@@ -1531,9 +2857,10 @@ unsafe extern "system" fn window_proc(
             SetWindowPos(
                 window.search_box,
                 None,
-                10, 10, 300, 25,
+                10, 10, 272, 25,
                 SWP_NOZORDER,
             ).ok();
+            SetWindowPos(window.searches_button, None, 284, 10, 26, 25, SWP_NOZORDER).ok();
             
             // Keep button positions
             SetWindowPos(
@@ -1556,6 +2883,12 @@ unsafe extern "system" fn window_proc(
             SetWindowPos(window.chk_modified, None, 650, 10, 80, 25, SWP_NOZORDER).ok();
             SetWindowPos(window.chk_path, None, 740, 10, 60, 25, SWP_NOZORDER).ok();
             SetWindowPos(window.help_button, None, 810, 10, 30, 25, SWP_NOZORDER).ok();
+            SetWindowPos(window.chk_allocated, None, 850, 10, 90, 25, SWP_NOZORDER).ok();
+            SetWindowPos(window.export_button, None, 950, 10, 80, 25, SWP_NOZORDER).ok();
+            SetWindowPos(window.chk_fuzzy, None, 1040, 10, 60, 25, SWP_NOZORDER).ok();
+            SetWindowPos(window.chk_accents, None, 1105, 10, 70, 25, SWP_NOZORDER).ok();
+            SetWindowPos(window.group_button, None, 1180, 10, 150, 25, SWP_NOZORDER).ok();
+            SetWindowPos(window.stats_button, None, 1340, 10, 80, 25, SWP_NOZORDER).ok();
             
             // Resize list view (leave room for signature and status bar)
             SetWindowPos(
@@ -1594,6 +2927,24 @@ unsafe extern "system" fn window_proc(
                 ID_HELP_BUTTON => {
                     window.show_help_dialog();
                 }
+                ID_EXPORT_BUTTON => {
+                    window.show_export_menu();
+                }
+                ID_SEARCHES_BUTTON => {
+                    window.show_searches_menu();
+                }
+                ID_GROUP_BUTTON => {
+                    window.show_group_menu();
+                }
+                ID_STATS_BUTTON => {
+                    window.show_statistics();
+                }
+                ID_STATS_SAVE_JSON => {
+                    window.export_statistics(false);
+                }
+                ID_STATS_SAVE_HTML => {
+                    window.export_statistics(true);
+                }
                 ID_CHK_TYPE | ID_CHK_SIZE | ID_CHK_MODIFIED | ID_CHK_PATH => {
                     // Update visibility flags
                     let state = SendMessageW(HWND(lparam.0 as *mut _), BM_GETCHECK, WPARAM(0), LPARAM(0));
@@ -1608,8 +2959,39 @@ unsafe extern "system" fn window_proc(
                     }
                     
                     // Recreate columns and refresh list
+                    window.retain_visible_sort_keys();
+                    window.setup_list_columns().ok();
+                    window.refresh_list_view();
+                }
+                ID_CHK_FUZZY => {
+                    let fuzzy = SendMessageW(window.chk_fuzzy, BM_GETCHECK, WPARAM(0), LPARAM(0)).0 == BST_CHECKED as isize;
+                    window.use_fuzzy.store(fuzzy, Ordering::SeqCst);
+                    // Sorting by the Match column makes no sense once it's gone
+                    window.retain_visible_sort_keys();
+                    window.filter.lock().unwrap().set_fuzzy(fuzzy);
+                    
+                    window.setup_list_columns().ok();
+                    window.handle_search();
+                }
+                ID_CHK_ACCENTS => {
+                    let match_accents = SendMessageW(window.chk_accents, BM_GETCHECK, WPARAM(0), LPARAM(0)).0 == BST_CHECKED as isize;
+                    // The store's keys and the filter's terms have to be normalized the same way
+                    window.files.lock().unwrap().set_match_accents(match_accents);
+                    window.filter.lock().unwrap().set_match_accents(match_accents);
+                    window.handle_search();
+                }
+                ID_CHK_ALLOCATED => {
+                    let state = SendMessageW(window.chk_allocated, BM_GETCHECK, WPARAM(0), LPARAM(0));
+                    window.use_allocated_size.store(state.0 == BST_CHECKED as isize, Ordering::SeqCst);
+                    window.filter.lock().unwrap().set_size_mode(window.size_mode());
+                    
+                    // Sizes, size filters and the size sort all change meaning
                     window.setup_list_columns().ok();
+                    window.handle_search();
+                    window.sort_files();
                     window.refresh_list_view();
+                    window.update_statistics(true);
+                    window.update_status_bar();
                 }
                 _ => {}
             }
@@ -1632,6 +3014,8 @@ unsafe extern "system" fn window_proc(
                 NM_DBLCLK => {
                     if (*nmhdr).idFrom == ID_LISTVIEW as usize {
                         window.handle_list_item_activate();
+                    } else if (*nmhdr).idFrom == ID_STATUSBAR as usize {
+                        window.show_skipped_dialog();
                     }
                 }
                 NM_RCLICK => {
@@ -1646,22 +3030,11 @@ unsafe extern "system" fn window_proc(
                     }
                 }
                 LVN_COLUMNCLICK => {
-                    // Get the column that was clicked
+                    // Shift+click sorts by more than one column
                     let nm_listview = lparam.0 as *const NMLISTVIEW;
-                    let clicked_col = (*nm_listview).iSubItem;
-                    
-                    // Update sort state
-                    let mut current_sort_col = window.sort_column.lock().unwrap();
-                    if *current_sort_col == clicked_col {
-                        // Toggle sort direction
-                        let current_ascending = window.sort_ascending.load(Ordering::SeqCst);
-                        window.sort_ascending.store(!current_ascending, Ordering::SeqCst);
-                    } else {
-                        // New column, default to ascending
-                        *current_sort_col = clicked_col;
-                        window.sort_ascending.store(true, Ordering::SeqCst);
-                    }
-                    drop(current_sort_col);
+                    let add = GetKeyState(VK_SHIFT.0 as i32) < 0;
+                    window.click_sort_column((*nm_listview).iSubItem, add);
+                    window.update_sort_arrows();
                     
                     // Sort and refresh
                     window.sort_files();
@@ -1674,11 +3047,19 @@ unsafe extern "system" fn window_proc(
         }
         WM_UPDATE_LIST => {
             window.refresh_list_view();
+            // Scan batches arrive many times a second; the statistics keep up once a second
+            window.update_statistics(false);
             window.update_status_bar();
             LRESULT(0)
         }
         WM_UPDATE_SEARCH => {
+            if wparam.0 != 0 {
+                // Sorting by the Lines column makes no sense once it's gone
+                window.retain_visible_sort_keys();
+                window.setup_list_columns().ok();
+            }
             window.refresh_list_view();
+            window.update_statistics(true);
             window.update_status_bar();
             // Auto-select first item if search box has focus
             if GetFocus() == window.search_box {
@@ -1687,6 +3068,8 @@ unsafe extern "system" fn window_proc(
             LRESULT(0)
         }
         WM_DESTROY => {
+            window.remember_search();
+            
             // Unregister hotkeys
             let _ = UnregisterHotKey(hwnd, 1);
             let _ = UnregisterHotKey(hwnd, 3);
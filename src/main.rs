@@ -1,42 +1,156 @@
 #![windows_subsystem = "windows"]
 
 mod scanner;
+mod category;
+mod content;
+mod export;
 mod gui;
 mod filter;
+mod fuzzy;
+mod group;
+mod normalize;
+mod query;
+mod searches;
+mod sort;
+mod stats;
+mod store;
+mod mounts;
+mod tree;
+mod trigram;
 
+use std::collections::HashMap;
 use std::env;
-use std::path::PathBuf;
-use windows::core::Result;
+use std::path::{Path, PathBuf};
+use once_cell::sync::OnceCell;
+use windows::core::{Result, HSTRING};
+use windows::Win32::Foundation::HWND;
+use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+use windows::Win32::UI::WindowsAndMessaging::{MessageBoxW, MB_ICONERROR, MB_OK};
 use crossbeam_channel::unbounded;
 
 fn main() -> Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut scan_options = scanner::ScanOptions::default();
+    let mut target_arg = None;
+    let mut search_index = true;
+    let mut query_arg = None;
+    let mut export_arg = None;
+    let mut columns_arg = None;
+    let mut bom = false;
     
-    let target_path = if args.len() > 1 {
-        PathBuf::from(&args[1])
-    } else {
-        env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--same-device" || arg == "--one-file-system" {
+            scan_options.same_device = true;
+        } else if let Some(types) = arg.strip_prefix("--skip-fs=") {
+            // Comma-separated filesystem types; an empty list enters every mount
+            scan_options.skip_fs_types = types.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect();
+        } else if arg == "--no-index" {
+            search_index = false;
+        } else if let Some(query) = arg.strip_prefix("--query=") {
+            query_arg = Some(query.to_string());
+        } else if arg == "--query" {
+            query_arg = args.next();
+        } else if let Some(target) = arg.strip_prefix("--export=") {
+            export_arg = Some(PathBuf::from(target));
+        } else if arg == "--export" {
+            export_arg = args.next().map(PathBuf::from);
+        } else if let Some(columns) = arg.strip_prefix("--columns=") {
+            columns_arg = Some(columns.to_string());
+        } else if arg == "--columns" {
+            columns_arg = args.next();
+        } else if arg == "--bom" {
+            bom = true;
+        } else if target_arg.is_none() {
+            target_arg = Some(arg);
+        }
+    }
+    
+    let target_path = match target_arg {
+        Some(arg) => PathBuf::from(arg),
+        None => env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
     };
 
     if !target_path.exists() {
-        eprintln!("Error: Path '{}' does not exist", target_path.display());
-        return Ok(());
+        report_error(&format!("Path '{}' does not exist", target_path.display()));
+        std::process::exit(2);
     }
 
     if !target_path.is_dir() {
-        eprintln!("Error: Path '{}' is not a directory", target_path.display());
-        return Ok(());
+        report_error(&format!("Path '{}' is not a directory", target_path.display()));
+        std::process::exit(2);
+    }
+
+    // Extra extension categories, read before anything is classified
+    if let Some(config) = env::current_exe().ok().and_then(|exe| exe.parent().map(|dir| dir.join("categories.json"))) {
+        if let Err(e) = category::load_config(&config) {
+            report(&format!("Warning: {}", e));
+        }
+    }
+
+    // Saved searches and history; `--query @name` runs a saved search
+    let searches_path = searches::SearchLibrary::default_path();
+    let library = match searches_path.as_deref().map(searches::SearchLibrary::load) {
+        Some(Ok(library)) => library,
+        Some(Err(e)) => {
+            report(&format!("Warning: {}", e));
+            searches::SearchLibrary::default()
+        }
+        None => searches::SearchLibrary::default(),
+    };
+    let initial_query = match query_arg.map(|query| library.resolve(&query)) {
+        Some(Ok(query)) => Some(query),
+        Some(Err(e)) => {
+            report_error(&e.to_string());
+            std::process::exit(2);
+        }
+        None => None,
+    };
+
+    // `--export` writes the list to a file instead of opening the window
+    if let Some(export_path) = export_arg {
+        let columns = match columns_arg.as_deref().map(export::parse_columns) {
+            Some(Ok(columns)) if !columns.is_empty() => columns,
+            Some(Err(e)) => {
+                report_error(&e);
+                std::process::exit(2);
+            }
+            _ => vec![sort::SortField::Name, sort::SortField::Type, sort::SortField::Size, sort::SortField::Modified, sort::SortField::Path],
+        };
+        let format = match export::ListFormat::for_path(&export_path) {
+            export::ListFormat::Table(table) => export::ListFormat::Table(table.with_bom(bom)),
+            format => format,
+        };
+        let code = match export_list(&target_path, scan_options, initial_query.as_deref(), &columns, format, &export_path) {
+            Ok(count) => {
+                report(&format!("Exported {} files to {}", count, export_path.display()));
+                0
+            }
+            Err(e) => {
+                report_error(&e);
+                1
+            }
+        };
+        std::process::exit(code);
     }
 
     let (sender, receiver) = unbounded();
     
     let mut window = gui::FileListWindow::new()?;
     
+    window.set_scan_options(scan_options.clone());
+    window.set_search_index(search_index);
+    window.set_search_library(library, searches_path);
+    if let Some(query) = initial_query {
+        window.set_search_text(&query);
+    }
     window.set_update_receiver(receiver);
     
     let scanner_path = target_path.clone();
     std::thread::spawn(move || {
-        let scanner = scanner::Scanner::with_sender(sender);
+        let scanner = scanner::Scanner::with_sender(sender).with_options(scan_options);
         scanner.scan_directory(&scanner_path);
     });
 
@@ -45,4 +159,88 @@ fn main() -> Result<()> {
     window.run_message_loop()?;
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// A windows-subsystem program starts without a console, so messages go to the
+// console it was started from, if any. Attached once; false when there's none.
+fn console() -> bool {
+    static ATTACHED: OnceCell<bool> = OnceCell::new();
+    *ATTACHED.get_or_init(|| unsafe { AttachConsole(ATTACH_PARENT_PROCESS).is_ok() })
+}
+
+// A status or warning line, which only a console shows
+fn report(message: &str) {
+    if console() {
+        eprintln!("{}", message);
+    }
+}
+
+// An error that stops the program, shown in a message box when started without a console
+fn report_error(message: &str) {
+    if console() {
+        eprintln!("Error: {}", message);
+    } else {
+        unsafe {
+            MessageBoxW(HWND::default(), &HSTRING::from(message), &HSTRING::from("File List Generator"), MB_OK | MB_ICONERROR);
+        }
+    }
+}
+
+// Scans `root` and writes the files matching `query`, sorted by folder and name,
+// to `target`. Returns how many files were written.
+fn export_list(root: &Path, options: scanner::ScanOptions, query: Option<&str>, columns: &[sort::SortField], format: export::ListFormat, target: &Path) -> std::result::Result<usize, String> {
+    let mut filter = filter::Filter::new();
+    if let Some(query) = query {
+        filter.set_search(query).map_err(|e| format!("Invalid query: {}", e))?;
+    }
+
+    // Batches are stored as they arrive rather than piling up in the channel until
+    // the scan ends; the channel closes when the scanner is done with it
+    let (sender, receiver) = unbounded();
+    let scan_root = root.to_path_buf();
+    let scan = std::thread::spawn(move || {
+        scanner::Scanner::with_sender(sender).with_options(options).scan_directory(&scan_root);
+    });
+    let mut files = store::FileStore::new();
+    let mut skipped_folders = 0;
+    let mut scan_ms = None;
+    for message in receiver {
+        match message {
+            scanner::ScanMessage::Batch(batch) => files.extend(batch),
+            scanner::ScanMessage::Skipped { .. } => skipped_folders += 1,
+            scanner::ScanMessage::Completed { elapsed_ms, .. } => scan_ms = Some(elapsed_ms as u64),
+            scanner::ScanMessage::Started => {}
+        }
+    }
+    scan.join().map_err(|_| "The scan stopped unexpectedly".to_string())?;
+
+    let mut rows: Vec<usize> = (0..files.len()).filter(|&index| filter.score(&files.entry(index)).is_some()).collect();
+    let lines: HashMap<usize, Vec<u32>> = if filter.searches_content() && columns.contains(&sort::SortField::Lines) {
+        rows.iter().map(|&index| (index, filter.content_lines(&files.entry(index)))).collect()
+    } else {
+        HashMap::new()
+    };
+    let context = sort::SortContext { size_mode: filter.size_mode(), scores: &[], lines: &lines };
+    let keys = [sort::SortKey::new(sort::SortField::Path, true), sort::SortKey::new(sort::SortField::Name, true)];
+    sort::sort_rows(&mut rows, &keys, |index| files.entry(index), &context);
+
+    let summary = export::ScanSummary {
+        root: root.to_path_buf(),
+        exported_at: scanner::iso_time(std::time::SystemTime::now()),
+        query: query.unwrap_or_default().to_string(),
+        size_mode: context.size_mode,
+        scanned_files: files.len(),
+        scanned_size: files.total_size(context.size_mode),
+        skipped_folders,
+        complete: true,
+        scan_ms,
+    };
+    let list = export::ListExport { columns, context, groups: None };
+    let write = |target: &Path| -> std::io::Result<()> {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(target)?);
+        list.write(&mut out, format, &summary, &rows, |index| files.entry(index))?;
+        std::io::Write::flush(&mut out)
+    };
+    write(target).map_err(|e| format!("Could not write {}: {}", target.display(), e))?;
+    Ok(rows.len())
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+
+// Identifies the filesystem a path lives on: the device number on Unix, the
+// volume serial number on Windows.
+#[cfg(unix)]
+pub fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::symlink_metadata(path).ok().map(|m| m.dev())
+}
+
+#[cfg(windows)]
+pub fn device_id(path: &Path) -> Option<u64> {
+    let root = windows_fs::volume_root(path)?;
+    windows_fs::volume_serial(&root).map(u64::from)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+// Bytes a file actually occupies on disk, as `du` reports it. Unix counts 512-byte
+// blocks; on Windows compressed and sparse files are asked for their on-disk size,
+// and everything is rounded up to the volume's cluster size.
+#[cfg(unix)]
+pub fn allocated_size(_path: &Path, metadata: &Metadata, _cluster_size: Option<u64>) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(windows)]
+pub fn allocated_size(path: &Path, metadata: &Metadata, cluster_size: Option<u64>) -> u64 {
+    use std::os::windows::fs::MetadataExt;
+    const FILE_ATTRIBUTE_SPARSE_FILE: u32 = 0x200;
+    const FILE_ATTRIBUTE_COMPRESSED: u32 = 0x800;
+
+    let on_disk = if metadata.file_attributes() & (FILE_ATTRIBUTE_SPARSE_FILE | FILE_ATTRIBUTE_COMPRESSED) != 0 {
+        windows_fs::compressed_size(path).unwrap_or(metadata.len())
+    } else {
+        metadata.len()
+    };
+
+    match cluster_size {
+        Some(cluster) if cluster > 0 => on_disk.div_ceil(cluster).saturating_mul(cluster),
+        _ => on_disk,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn allocated_size(_path: &Path, metadata: &Metadata, _cluster_size: Option<u64>) -> u64 {
+    metadata.len()
+}
+
+// Allocation unit of the volume holding `path`; only needed on Windows
+#[cfg(windows)]
+pub fn cluster_size(path: &Path) -> Option<u64> {
+    let root = windows_fs::volume_root(path)?;
+    windows_fs::cluster_size(&root)
+}
+
+#[cfg(not(windows))]
+pub fn cluster_size(_path: &Path) -> Option<u64> {
+    None
+}
+
+// Cluster sizes of the volumes a scan passes through, looked up once per volume
+#[derive(Default)]
+pub struct ClusterSizes {
+    by_device: HashMap<u64, Option<u64>>,
+}
+
+impl ClusterSizes {
+    // Cluster size of the volume holding `path`
+    pub fn volume(&mut self, path: &Path) -> Option<u64> {
+        let device = device_id(path)?;
+        *self.by_device.entry(device).or_insert_with(|| cluster_size(path))
+    }
+
+    // Cluster size for the folder `dir`, found in a folder using `parent`. Only a
+    // mount point can start another volume, and those are reparse points.
+    #[cfg(windows)]
+    pub fn dir(&mut self, dir: &Path, parent: Option<u64>) -> Option<u64> {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+        match std::fs::symlink_metadata(dir) {
+            Ok(metadata) if metadata.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT != 0 => self.volume(dir).or(parent),
+            _ => parent,
+        }
+    }
+
+    // Sizes on disk don't depend on the cluster size here
+    #[cfg(not(windows))]
+    pub fn dir(&mut self, _dir: &Path, parent: Option<u64>) -> Option<u64> {
+        parent
+    }
+}
+
+// Mount points below which the scanner may need to stop, keyed by path
+pub struct MountTable {
+    fs_types: HashMap<PathBuf, String>,
+}
+
+impl MountTable {
+    #[cfg(target_os = "linux")]
+    pub fn load() -> Self {
+        let fs_types = std::fs::read_to_string("/proc/self/mountinfo")
+            .map(|text| parse_mountinfo(&text))
+            .unwrap_or_default();
+        MountTable { fs_types }
+    }
+
+    // Windows volumes are looked up on demand in `mount_fs_type`
+    #[cfg(not(target_os = "linux"))]
+    pub fn load() -> Self {
+        MountTable { fs_types: HashMap::new() }
+    }
+
+    // Returns the filesystem type if `dir` is itself a mount point
+    #[cfg(not(windows))]
+    pub fn mount_fs_type(&self, dir: &Path) -> Option<String> {
+        self.fs_types.get(dir).cloned()
+    }
+
+    #[cfg(windows)]
+    pub fn mount_fs_type(&self, dir: &Path) -> Option<String> {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+        if let Some(fs_type) = self.fs_types.get(dir) {
+            return Some(fs_type.clone());
+        }
+
+        // Mounted volumes show up as reparse points; skip the volume lookup for plain folders
+        let attributes = std::fs::symlink_metadata(dir).ok()?.file_attributes();
+        if attributes & FILE_ATTRIBUTE_REPARSE_POINT == 0 {
+            return None;
+        }
+
+        let root = windows_fs::volume_root(dir)?;
+        if root.trim_end_matches('\\') != dir.to_string_lossy().trim_end_matches('\\') {
+            return None;
+        }
+        windows_fs::volume_fs_type(&root)
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn parse_mountinfo(text: &str) -> HashMap<PathBuf, String> {
+    // Each line: id parent major:minor root mount-point options [optional...] - fstype source super-options
+    let mut fs_types = HashMap::new();
+    for line in text.lines() {
+        let Some((left, right)) = line.split_once(" - ") else {
+            continue;
+        };
+        let mount_point = left.split(' ').nth(4);
+        let fs_type = right.split(' ').next();
+        if let (Some(mount_point), Some(fs_type)) = (mount_point, fs_type) {
+            fs_types.insert(PathBuf::from(unescape_mount_path(mount_point)), fs_type.to_string());
+        }
+    }
+    fs_types
+}
+
+// mountinfo escapes space, tab, newline and backslash as three-digit octal
+#[cfg(target_os = "linux")]
+fn unescape_mount_path(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() && bytes[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b)) {
+            let value = (bytes[i + 1] - b'0') * 64 + (bytes[i + 2] - b'0') * 8 + (bytes[i + 3] - b'0');
+            out.push(value);
+            i += 4;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(windows)]
+mod windows_fs {
+    use std::path::Path;
+    use windows::core::HSTRING;
+    use windows::Win32::Storage::FileSystem::{
+        GetCompressedFileSizeW, GetDiskFreeSpaceW, GetDriveTypeW, GetVolumeInformationW, GetVolumePathNameW,
+    };
+
+    const DRIVE_REMOTE: u32 = 4;
+
+    fn from_wide(buffer: &[u16]) -> String {
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        String::from_utf16_lossy(&buffer[..len])
+    }
+
+    // Root of the volume containing `path`, e.g. "C:\" or "C:\Mounts\Data\"
+    pub fn volume_root(path: &Path) -> Option<String> {
+        let mut buffer = vec![0u16; 1024];
+        unsafe {
+            GetVolumePathNameW(&HSTRING::from(path), &mut buffer).ok()?;
+        }
+        Some(from_wide(&buffer))
+    }
+
+    pub fn volume_serial(root: &str) -> Option<u32> {
+        let mut serial = 0u32;
+        unsafe {
+            GetVolumeInformationW(&HSTRING::from(root), None, Some(&mut serial as *mut u32), None, None, None).ok()?;
+        }
+        Some(serial)
+    }
+
+    pub fn cluster_size(root: &str) -> Option<u64> {
+        let mut sectors_per_cluster = 0u32;
+        let mut bytes_per_sector = 0u32;
+        unsafe {
+            GetDiskFreeSpaceW(
+                &HSTRING::from(root),
+                Some(&mut sectors_per_cluster as *mut u32),
+                Some(&mut bytes_per_sector as *mut u32),
+                None,
+                None,
+            ).ok()?;
+        }
+        Some(u64::from(sectors_per_cluster) * u64::from(bytes_per_sector))
+    }
+
+    pub fn compressed_size(path: &Path) -> Option<u64> {
+        const INVALID_FILE_SIZE: u32 = u32::MAX;
+        let mut high = 0u32;
+        let low = unsafe { GetCompressedFileSizeW(&HSTRING::from(path), Some(&mut high as *mut u32)) };
+        // Treat INVALID_FILE_SIZE with no high word as failure, at the cost of files of exactly that size
+        if low == INVALID_FILE_SIZE && high == 0 {
+            return None;
+        }
+        Some((u64::from(high) << 32) | u64::from(low))
+    }
+
+    // Lowercase filesystem name ("ntfs", "refs", ...), or "cifs" for network shares
+    pub fn volume_fs_type(root: &str) -> Option<String> {
+        let root = HSTRING::from(root);
+        unsafe {
+            if GetDriveTypeW(&root) == DRIVE_REMOTE {
+                return Some("cifs".to_string());
+            }
+            let mut name = [0u16; 64];
+            GetVolumeInformationW(&root, None, None, None, None, Some(&mut name)).ok()?;
+            Some(from_wide(&name).to_lowercase())
+        }
+    }
+}
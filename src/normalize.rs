@@ -0,0 +1,123 @@
+use unicode_normalization::char::{decompose_compatible, is_combining_mark};
+
+// Search compares text in one canonical form so a name matches however it was
+// typed or stored: compatibility-decomposed, so NFC and NFD spellings agree and
+// full-width letters match their ASCII forms, then lowercased. With `fold_accents`
+// accents and other combining marks are dropped as well, so `resume` finds `Résumé`.
+pub fn search_form(text: &str, fold_accents: bool) -> String {
+    if text.is_ascii() {
+        return text.to_ascii_lowercase();
+    }
+    let mut form = String::with_capacity(text.len());
+    for c in text.chars() {
+        fold_char(c, fold_accents, |folded| form.push(folded));
+    }
+    form
+}
+
+// The search form of `text` as characters, each paired with the index of the
+// character in `text` it came from, for mapping matches back onto the original
+pub fn search_chars(text: &str, fold_accents: bool) -> Vec<(char, usize)> {
+    let mut chars = Vec::with_capacity(text.len());
+    for (index, c) in text.chars().enumerate() {
+        fold_char(c, fold_accents, |folded| chars.push((folded, index)));
+    }
+    chars
+}
+
+// A single character's base letter in lowercase, ignoring accents; fuzzy matching
+// compares one character at a time with this
+pub fn base_char(c: char) -> char {
+    if c.is_ascii() {
+        return c.to_ascii_lowercase();
+    }
+    let mut base = None;
+    decompose_compatible(c, |part| {
+        base.get_or_insert(part);
+    });
+    let base = base.unwrap_or(c);
+    base.to_lowercase().next().unwrap_or(base)
+}
+
+fn fold_char(c: char, fold_accents: bool, mut emit: impl FnMut(char)) {
+    if c.is_ascii() {
+        emit(c.to_ascii_lowercase());
+        return;
+    }
+    decompose_compatible(c, |part| {
+        if fold_accents && is_combining_mark(part) {
+            return;
+        }
+        part.to_lowercase().for_each(&mut emit);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NFC: &str = "R\u{e9}sum\u{e9}";
+    const NFD: &str = "Re\u{301}sume\u{301}";
+
+    fn folded(text: &str) -> String {
+        search_form(text, true)
+    }
+
+    fn kept(text: &str) -> String {
+        search_form(text, false)
+    }
+
+    #[test]
+    fn nfc_and_nfd_agree() {
+        for fold_accents in [false, true] {
+            assert_eq!(search_form(NFC, fold_accents), search_form(NFD, fold_accents));
+        }
+        assert_eq!(kept(NFC), "re\u{301}sume\u{301}");
+        assert_eq!(kept("\u{c5}ngstr\u{f6}m"), kept("A\u{30a}ngstro\u{308}m"));
+    }
+
+    #[test]
+    fn accent_folding() {
+        assert_eq!(folded(NFC), "resume");
+        assert_eq!(folded(NFD), "resume");
+        assert_ne!(kept(NFC), "resume");
+        assert_eq!(folded("Caf\u{e9} na\u{ef}ve \u{c7}a"), "cafe naive ca");
+        // Letters that aren't accented forms are kept either way
+        assert_eq!(folded("Stra\u{df}e \u{f8}l \u{142}\u{f3}d\u{17a}"), "stra\u{df}e \u{f8}l \u{142}odz");
+        assert_eq!(folded("\u{414}\u{43e}\u{43a}\u{443}\u{43c}\u{435}\u{43d}\u{442}"), "\u{434}\u{43e}\u{43a}\u{443}\u{43c}\u{435}\u{43d}\u{442}");
+    }
+
+    #[test]
+    fn compatibility_forms() {
+        // Full-width letters and digits
+        assert_eq!(kept("\u{ff30}\u{ff44}\u{ff46}\u{ff11}\u{ff12}"), "pdf12");
+        // Ligatures, superscripts and circled digits
+        assert_eq!(kept("\u{fb01}le"), "file");
+        assert_eq!(kept("m\u{b2}"), "m2");
+        assert_eq!(kept("\u{2460}"), "1");
+        // Half-width katakana become full-width
+        assert_eq!(kept("\u{ff76}"), "\u{30ab}");
+        assert_eq!(folded("ASCII Only.TXT"), "ascii only.txt");
+    }
+
+    #[test]
+    fn search_chars_map_back() {
+        // Each folded character points at the character of the original it came from
+        let chars = search_chars(NFC, false);
+        let text: String = chars.iter().map(|&(c, _)| c).collect();
+        assert_eq!(text, kept(NFC));
+        assert_eq!(chars.iter().map(|&(_, i)| i).collect::<Vec<_>>(), [0, 1, 1, 2, 3, 4, 5, 5]);
+
+        let folded = search_chars("\u{fb01}\u{e9}", true);
+        assert_eq!(folded, [('f', 0), ('i', 0), ('e', 1)]);
+    }
+
+    #[test]
+    fn base_chars() {
+        assert_eq!(base_char('\u{c9}'), 'e');
+        assert_eq!(base_char('\u{ff21}'), 'a');
+        assert_eq!(base_char('Z'), 'z');
+        assert_eq!(base_char('\u{df}'), '\u{df}');
+        assert_eq!(base_char('7'), '7');
+    }
+}
@@ -0,0 +1,1258 @@
+use std::fmt;
+use std::time::SystemTime;
+use chrono::{DateTime, Datelike, Duration, Local, Months, NaiveDate, TimeZone};
+use regex::{Regex, RegexBuilder};
+use crate::category::FileCategory;
+
+// Parsed search box text. Terms next to each other are ANDed; `OR`, `NOT`/`-`
+// and parentheses work as usual, with NOT binding tightest and OR loosest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Query {
+    All,
+    And(Vec<Query>),
+    Or(Vec<Query>),
+    Not(Box<Query>),
+    Term(Predicate),
+}
+
+impl Query {
+    // Whether every file this query matches is also matched by `previous`, so a
+    // search can re-check just the previous results instead of every file.
+    // Conservative: `false` whenever that can't be shown from the terms alone.
+    pub fn narrows(&self, previous: &Query) -> bool {
+        match (self, previous) {
+            (_, Query::All) => true,
+            _ if self == previous => true,
+            (_, Query::And(required)) => required.iter().all(|p| self.narrows(p)),
+            (Query::Or(branches), _) => branches.iter().all(|b| b.narrows(previous)),
+            (_, Query::Or(branches)) => branches.iter().any(|b| self.narrows(b)),
+            (Query::And(terms), _) => terms.iter().any(|t| t.narrows(previous)),
+            (Query::Not(inner), Query::Not(excluded)) => excluded.narrows(inner),
+            (Query::Term(term), Query::Term(previous)) => term.narrows(previous),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    // Case-insensitive substring; `text` is already lowercase
+    Text { field: TextField, text: String },
+    // Glob or `/regex/`, case-insensitive
+    Pattern { field: TextField, pattern: Pattern },
+    // Exact extension match against any of the listed extensions (lowercase, no dot)
+    Extension(Vec<String>),
+    // Any of the listed categories, e.g. `type:image,video`
+    Category(Vec<FileCategory>),
+    // A line of the file's contents contains the text; `text` is already lowercase
+    Contains(String),
+    Size(SizeRange),
+    Modified(TimeRange),
+}
+
+impl Predicate {
+    fn narrows(&self, previous: &Predicate) -> bool {
+        match (self, previous) {
+            // Longer text only matches where the shorter text it contains does, and
+            // every field is part of what `Any` searches
+            (Predicate::Text { field, text }, Predicate::Text { field: previous_field, text: previous_text }) => {
+                (field == previous_field || *previous_field == TextField::Any) && text.contains(previous_text.as_str())
+            }
+            (Predicate::Extension(extensions), Predicate::Extension(previous)) => {
+                extensions.iter().all(|ext| previous.contains(ext))
+            }
+            (Predicate::Category(categories), Predicate::Category(previous)) => {
+                categories.iter().all(|category| previous.contains(category))
+            }
+            (Predicate::Contains(text), Predicate::Contains(previous)) => text.contains(previous.as_str()),
+            (Predicate::Size(range), Predicate::Size(previous)) => {
+                range.min >= previous.min && range.max <= previous.max
+            }
+            (Predicate::Modified(range), Predicate::Modified(previous)) => {
+                let start = match (range.start, previous.start) {
+                    (_, None) => true,
+                    (Some(start), Some(previous)) => start >= previous,
+                    (None, Some(_)) => false,
+                };
+                let end = match (range.end, previous.end) {
+                    (_, None) => true,
+                    (Some(end), Some(previous)) => end <= previous,
+                    (None, Some(_)) => false,
+                };
+                start && end
+            }
+            _ => self == previous,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextField {
+    // Name, full path or extension
+    Any,
+    Name,
+    // Containing folder
+    Path,
+    // Folder and name together
+    FullPath,
+}
+
+// A compiled glob or regular expression. Compared by its source so parsed
+// queries can still be compared.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    regex: Regex,
+}
+
+impl Pattern {
+    pub fn is_match(&self, text: &str) -> bool {
+        self.regex.is_match(text)
+    }
+
+    // Byte ranges of every match in `text`
+    pub fn find_ranges<'t>(&'t self, text: &'t str) -> impl Iterator<Item = std::ops::Range<usize>> + 't {
+        self.regex.find_iter(text).map(|m| m.range())
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str()
+    }
+}
+
+// Inclusive byte bounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SizeRange {
+    pub min: u64,
+    pub max: u64,
+}
+
+impl SizeRange {
+    pub fn contains(&self, size: u64) -> bool {
+        size >= self.min && size <= self.max
+    }
+}
+
+// Half-open interval `[start, end)`; a missing bound is unbounded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeRange {
+    pub start: Option<SystemTime>,
+    pub end: Option<SystemTime>,
+}
+
+impl TimeRange {
+    pub fn contains(&self, time: SystemTime) -> bool {
+        self.start.is_none_or(|start| time >= start) && self.end.is_none_or(|end| time < end)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    // Character offset into the query text
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {}: {}", self.position + 1, self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+pub fn parse(text: &str) -> Result<Query, QueryError> {
+    parse_at(text, Local::now())
+}
+
+// `parse` with relative dates resolved against `now`
+fn parse_at(text: &str, now: DateTime<Local>) -> Result<Query, QueryError> {
+    let tokens = tokenize(text)?;
+    let mut parser = Parser { text, tokens, pos: 0, now };
+
+    if parser.tokens.is_empty() {
+        return Ok(Query::All);
+    }
+
+    let query = parser.parse_or()?;
+    match parser.peek() {
+        None => Ok(query),
+        Some(token) if token.kind == TokenKind::RParen => {
+            Err(parser.error_at(token.start, "unmatched ')'"))
+        }
+        Some(token) => Err(parser.error_at(token.start, "unexpected input")),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Not,
+    // A bare word or "quoted phrase"
+    Word { text: String, quoted: bool },
+    // `field:value`; the field name is lowercase
+    Field { field: String, value: String, quoted: bool },
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    // Byte offset into the query text
+    start: usize,
+}
+
+pub const FIELDS: &[&str] = &["name", "ext", "extension", "path", "type", "contains", "size", "modified"];
+// Named periods `modified:` accepts
+pub const PERIODS: &[&str] = &["today", "yesterday", "thisweek", "lastweek", "thismonth", "lastmonth", "thisyear", "lastyear"];
+// Units `parse_size` accepts, longest-established first
+pub const SIZE_UNITS: &[&str] = &["kb", "mb", "gb", "tb", "kib", "mib", "gib", "tib", "b", "k", "m", "g", "t"];
+
+// A search term as written, for hints about the query rather than running it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Word {
+    // Character offset into the query text
+    pub position: usize,
+    // Lowercase field name for `field:value` terms
+    pub field: Option<String>,
+    // The value, or the whole word for plain terms, unquoted
+    pub text: String,
+    pub quoted: bool,
+}
+
+// The terms of `text`, leaving out operators and parentheses; empty if the text
+// doesn't tokenize
+pub fn words(text: &str) -> Vec<Word> {
+    let tokens = tokenize(text).unwrap_or_default();
+    tokens.into_iter()
+        .filter_map(|token| {
+            let position = char_offset(text, token.start);
+            match token.kind {
+                TokenKind::Word { text, quoted } => Some(Word { position, field: None, text, quoted }),
+                TokenKind::Field { field, value, quoted } => Some(Word { position, field: Some(field), text: value, quoted }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        match c {
+            // `(1)` or `(copy).pdf` is part of a name rather than a group
+            '(' if !is_parenthesized_word(text, start) => {
+                chars.next();
+                tokens.push(Token { kind: TokenKind::LParen, start });
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token { kind: TokenKind::RParen, start });
+            }
+            '"' => {
+                chars.next();
+                let (phrase, _) = read_quoted(text, &mut chars, start)?;
+                tokens.push(Token { kind: TokenKind::Word { text: phrase, quoted: true }, start });
+            }
+            '-' if is_negation(text, start) => {
+                chars.next();
+                tokens.push(Token { kind: TokenKind::Not, start });
+            }
+            // `/regex/` may contain spaces and parentheses, so read it whole
+            '/' if regex_end(text, start).is_some() => {
+                let end = regex_end(text, start).unwrap();
+                skip_to(&mut chars, end);
+                tokens.push(Token { kind: TokenKind::Word { text: text[start..end].to_string(), quoted: false }, start });
+            }
+            _ => {
+                let mut end = start;
+                let mut kind = None;
+                // Parentheses opened within the word, which its own ')'s close
+                let mut depth = 0;
+                while let Some(&(i, c)) = chars.peek() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    match c {
+                        '(' => depth += 1,
+                        ')' if depth > 0 => depth -= 1,
+                        // Only a ')' that ends the word closes a group
+                        ')' if ends_term(text, i + 1) => break,
+                        _ => {}
+                    }
+                    chars.next();
+                    end = i + c.len_utf8();
+
+                    // `field:"quoted value"`
+                    if c == ':' && chars.peek().map(|&(_, n)| n) == Some('"') {
+                        let field = text[start..i].to_lowercase();
+                        if FIELDS.contains(&field.as_str()) {
+                            let (quote_start, _) = chars.next().unwrap();
+                            let (value, quote_end) = read_quoted(text, &mut chars, quote_start)?;
+                            end = quote_end;
+                            kind = Some(TokenKind::Field { field, value, quoted: true });
+                            break;
+                        }
+                    }
+
+                    // `field:/regex/`
+                    if c == ':' && chars.peek().map(|&(_, n)| n) == Some('/') {
+                        let field = text[start..i].to_lowercase();
+                        if let Some(regex_end) = regex_end(text, i + 1).filter(|_| FIELDS.contains(&field.as_str())) {
+                            skip_to(&mut chars, regex_end);
+                            end = regex_end;
+                            kind = Some(TokenKind::Field { field, value: text[i + 1..regex_end].to_string(), quoted: false });
+                            break;
+                        }
+                    }
+                }
+
+                let word = &text[start..end];
+                let kind = kind.unwrap_or_else(|| match word {
+                    "OR" => TokenKind::Or,
+                    "AND" => TokenKind::And,
+                    "NOT" => TokenKind::Not,
+                    _ => match word.split_once(':') {
+                        Some((field, value)) if FIELDS.contains(&field.to_lowercase().as_str()) => {
+                            TokenKind::Field { field: field.to_lowercase(), value: value.to_string(), quoted: false }
+                        }
+                        _ => TokenKind::Word { text: word.to_string(), quoted: false },
+                    },
+                });
+                tokens.push(Token { kind, start });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+// Whether the '(' at `open` is closed again before the next space, as in
+// `(1)` or `(copy).pdf`, making it text rather than a group. A single field
+// term, negation or regex in parentheses still groups.
+fn is_parenthesized_word(text: &str, open: usize) -> bool {
+    let mut depth = 0;
+    for (i, c) in text[open..].char_indices() {
+        match c {
+            c if c.is_whitespace() => return false,
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    let inner = &text[open + 1..open + i];
+                    let field = inner.split_once(':').is_some_and(|(field, _)| FIELDS.contains(&field.to_lowercase().as_str()));
+                    return !(field || inner.starts_with('-') || is_regex_literal(inner));
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+// Whether a term ending at byte `at` is followed by a space, a ')' or the end
+fn ends_term(text: &str, at: usize) -> bool {
+    text[at..].chars().next().is_none_or(|c| c.is_whitespace() || c == ')')
+}
+
+// A leading '-' negates the next term, but a lone '-' is just text
+fn is_negation(text: &str, at: usize) -> bool {
+    let before_ok = text[..at].chars().next_back().is_none_or(|c| c.is_whitespace() || c == '(');
+    let after_ok = text[at + 1..].chars().next().is_some_and(|c| !c.is_whitespace() && c != ')');
+    before_ok && after_ok
+}
+
+// Reads up to the closing quote; `open` is the byte offset of the opening quote.
+// Returns the unquoted text and the byte offset just past the closing quote.
+fn read_quoted(
+    text: &str,
+    chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>,
+    open: usize,
+) -> Result<(String, usize), QueryError> {
+    let mut value = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, i + 1)),
+            '\\' if chars.peek().map(|&(_, n)| n) == Some('"') => {
+                chars.next();
+                value.push('"');
+            }
+            _ => value.push(c),
+        }
+    }
+    Err(QueryError {
+        position: char_offset(text, open),
+        message: "missing closing quote".to_string(),
+    })
+}
+
+// For a '/' at `open`, the byte offset just past the matching closing '/', if the
+// closing slash ends the term. `\/` stays inside the pattern.
+fn regex_end(text: &str, open: usize) -> Option<usize> {
+    let mut chars = text[open + 1..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '/' => {
+                let after = open + 1 + i + 1;
+                return (i > 0 && ends_term(text, after)).then_some(after);
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn skip_to(chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>, end: usize) {
+    while chars.peek().is_some_and(|&(i, _)| i < end) {
+        chars.next();
+    }
+}
+
+fn char_offset(text: &str, byte: usize) -> usize {
+    text[..byte].chars().count()
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+    // Relative dates like `today` and `30d` resolve against the time the query was compiled
+    now: DateTime<Local>,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn error_at(&self, byte: usize, message: &str) -> QueryError {
+        QueryError {
+            position: char_offset(self.text, byte),
+            message: message.to_string(),
+        }
+    }
+
+    fn end_offset(&self) -> usize {
+        self.text.len()
+    }
+
+    fn parse_or(&mut self) -> Result<Query, QueryError> {
+        let mut branches = vec![self.parse_and()?];
+        while let Some(token) = self.peek() {
+            if token.kind != TokenKind::Or {
+                break;
+            }
+            self.next();
+            branches.push(self.parse_and()?);
+        }
+        Ok(if branches.len() == 1 { branches.pop().unwrap() } else { Query::Or(branches) })
+    }
+
+    fn parse_and(&mut self) -> Result<Query, QueryError> {
+        let mut terms = Vec::new();
+        loop {
+            match self.peek().map(|t| &t.kind) {
+                None | Some(TokenKind::Or) | Some(TokenKind::RParen) => break,
+                Some(TokenKind::And) => {
+                    let token = self.next().unwrap();
+                    if terms.is_empty() {
+                        return Err(self.error_at(token.start, "AND needs a term on its left"));
+                    }
+                    if matches!(self.peek().map(|t| &t.kind), None | Some(TokenKind::Or) | Some(TokenKind::RParen)) {
+                        return Err(self.error_at(token.start, "AND needs a term on its right"));
+                    }
+                }
+                Some(_) => terms.push(self.parse_unary()?),
+            }
+        }
+
+        match terms.len() {
+            0 => {
+                let at = self.peek().map_or(self.end_offset(), |t| t.start);
+                Err(self.error_at(at, "expected a search term"))
+            }
+            1 => Ok(terms.pop().unwrap()),
+            _ => Ok(Query::And(terms)),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, QueryError> {
+        let token = match self.next() {
+            Some(token) => token,
+            None => return Err(self.error_at(self.end_offset(), "expected a search term")),
+        };
+
+        match token.kind {
+            TokenKind::Not => {
+                if self.peek().is_none() {
+                    return Err(self.error_at(token.start, "NOT needs a term to exclude"));
+                }
+                Ok(Query::Not(Box::new(self.parse_unary()?)))
+            }
+            TokenKind::LParen => {
+                let inner = self.parse_or()?;
+                match self.next() {
+                    Some(Token { kind: TokenKind::RParen, .. }) => Ok(inner),
+                    _ => Err(self.error_at(token.start, "missing closing ')'")),
+                }
+            }
+            TokenKind::Word { text, quoted } => self.word_term(&text, quoted, token.start),
+            TokenKind::Field { field, value, quoted } => self.field_term(&field, &value, quoted, token.start),
+            TokenKind::RParen => Err(self.error_at(token.start, "unmatched ')'")),
+            TokenKind::Or => Err(self.error_at(token.start, "OR needs a term on its left")),
+            TokenKind::And => Err(self.error_at(token.start, "AND needs a term on its left")),
+        }
+    }
+
+    fn word_term(&self, word: &str, quoted: bool, start: usize) -> Result<Query, QueryError> {
+        // Bare `>10mb`, `<=1kb` and `10mb..100mb` work as size filters
+        if !quoted && (word.starts_with('>') || word.starts_with('<') || is_size_range(word)) {
+            return self.size_term(word, start);
+        }
+        // `/regex/` tries the name and the full path; a glob with a folder separator
+        // matches the full path, otherwise just the name
+        if !quoted && is_regex_literal(word) {
+            return self.regex_term(TextField::Any, word, start);
+        }
+        if !quoted && is_glob(word) {
+            let field = if word.contains(['/', '\\']) { TextField::FullPath } else { TextField::Name };
+            return self.glob_term(field, word, start);
+        }
+        Ok(Query::Term(Predicate::Text { field: TextField::Any, text: word.to_lowercase() }))
+    }
+
+    fn field_term(&self, field: &str, value: &str, quoted: bool, start: usize) -> Result<Query, QueryError> {
+        // Point errors at the value rather than the field name
+        let value_start = start + field.len() + 1;
+        if value.is_empty() {
+            return Err(self.error_at(start, &format!("expected a value after {}:", field)));
+        }
+
+        // Patterns under `path:` match the full path so they can name the file too
+        if !quoted && (field == "name" || field == "path") {
+            let pattern_field = if field == "name" { TextField::Name } else { TextField::FullPath };
+            if is_regex_literal(value) {
+                return self.regex_term(pattern_field, value, value_start);
+            }
+            if is_glob(value) {
+                return self.glob_term(pattern_field, value, value_start);
+            }
+        }
+
+        match field {
+            "name" => Ok(Query::Term(Predicate::Text { field: TextField::Name, text: value.to_lowercase() })),
+            "path" => Ok(Query::Term(Predicate::Text { field: TextField::Path, text: value.to_lowercase() })),
+            "ext" | "extension" => {
+                let extensions: Vec<String> = value.split(',')
+                    .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+                    .filter(|e| !e.is_empty())
+                    .collect();
+                if extensions.is_empty() {
+                    return Err(self.error_at(value_start, "expected an extension such as pdf"));
+                }
+                Ok(Query::Term(Predicate::Extension(extensions)))
+            }
+            "type" => {
+                let mut categories = Vec::new();
+                let mut offset = value_start;
+                for name in value.split(',') {
+                    if !name.trim().is_empty() {
+                        let category = FileCategory::from_name(name.trim()).ok_or_else(|| {
+                            self.error_at(offset, "unknown type; try document, image, video, audio, code, archive, executable, folder, shortcut or other")
+                        })?;
+                        categories.push(category);
+                    }
+                    offset += name.len() + 1;
+                }
+                if categories.is_empty() {
+                    return Err(self.error_at(value_start, "expected a type such as image"));
+                }
+                Ok(Query::Term(Predicate::Category(categories)))
+            }
+            "contains" => Ok(Query::Term(Predicate::Contains(value.to_lowercase()))),
+            "size" => self.size_term(value, value_start),
+            "modified" => self.modified_term(value, value_start),
+            _ => unreachable!("tokenizer only produces known fields"),
+        }
+    }
+
+    fn regex_term(&self, field: TextField, literal: &str, start: usize) -> Result<Query, QueryError> {
+        let source = &literal[1..literal.len() - 1];
+        let pattern = self.compile_pattern(source, start)?;
+        Ok(Query::Term(Predicate::Pattern { field, pattern }))
+    }
+
+    fn glob_term(&self, field: TextField, glob: &str, start: usize) -> Result<Query, QueryError> {
+        let source = glob_to_regex(glob, field == TextField::FullPath)
+            .map_err(|(offset, message)| self.error_at(start + offset, message))?;
+        let pattern = self.compile_pattern(&source, start)?;
+        Ok(Query::Term(Predicate::Pattern { field, pattern }))
+    }
+
+    fn compile_pattern(&self, source: &str, start: usize) -> Result<Pattern, QueryError> {
+        RegexBuilder::new(source)
+            .case_insensitive(true)
+            .build()
+            .map(|regex| Pattern { regex })
+            .map_err(|error| {
+                // Syntax errors come with a multi-line caret diagram; keep only the summary
+                let detail = error.to_string();
+                let summary = detail.lines().last().unwrap_or_default().trim_start_matches("error: ").to_string();
+                self.error_at(start, &format!("invalid regular expression: {}", summary))
+            })
+    }
+
+    fn size_term(&self, value: &str, start: usize) -> Result<Query, QueryError> {
+        if let Some((low, high)) = value.split_once("..") {
+            return self.size_range_term(low, high, start);
+        }
+
+        let (op, number) = split_comparison(value);
+        let number_start = start + (value.len() - value.trim_start_matches(['>', '<', '=', ' ']).len());
+        let size = self.size_value(number, number_start)?;
+
+        let range = match op {
+            ">" => SizeRange { min: size.saturating_add(1), max: u64::MAX },
+            ">=" => SizeRange { min: size, max: u64::MAX },
+            "<" => match size.checked_sub(1) {
+                Some(max) => SizeRange { min: 0, max },
+                None => SizeRange { min: 1, max: 0 },
+            },
+            "<=" => SizeRange { min: 0, max: size },
+            _ => SizeRange { min: size, max: size },
+        };
+        Ok(Query::Term(Predicate::Size(range)))
+    }
+
+    // `10mb..100mb`, inclusive; either end may be left open
+    fn size_range_term(&self, low: &str, high: &str, start: usize) -> Result<Query, QueryError> {
+        let high_start = start + low.len() + 2;
+        if low.trim().is_empty() && high.trim().is_empty() {
+            return Err(self.error_at(start, "a size range needs at least one end, e.g. 10mb..100mb"));
+        }
+
+        let min = if low.trim().is_empty() { 0 } else { self.size_value(low, start)? };
+        let max = if high.trim().is_empty() { u64::MAX } else { self.size_value(high, high_start)? };
+        if min > max {
+            return Err(self.error_at(start, "the start of a size range is larger than its end"));
+        }
+        Ok(Query::Term(Predicate::Size(SizeRange { min, max })))
+    }
+
+    fn size_value(&self, text: &str, start: usize) -> Result<u64, QueryError> {
+        parse_size(text).map_err(|error| match error {
+            SizeError::Invalid => self.error_at(start, &format!("'{}' is not a size (try 10mb, 4MiB or 500kb)", text.trim())),
+            SizeError::TooLarge => self.error_at(start, &format!("'{}' is too large", text.trim())),
+        })
+    }
+
+    fn modified_term(&self, value: &str, start: usize) -> Result<Query, QueryError> {
+        if let Some((low, high)) = value.split_once("..") {
+            return self.modified_range_term(low, high, start);
+        }
+
+        let (op, date_text) = split_comparison(value);
+        let date_start = start + (value.len() - value.trim_start_matches(['>', '<', '=', ' ']).len());
+        let range = match self.date_value(date_text, date_start)? {
+            DateValue::Period(period) => match op {
+                ">" => TimeRange { start: period.end, end: None },
+                ">=" => TimeRange { start: period.start, end: None },
+                "<" => TimeRange { start: None, end: period.start },
+                "<=" => TimeRange { start: None, end: period.end },
+                _ => period,
+            },
+            // Ages compare how old the file is: `<30d` is newer, `>30d` older
+            DateValue::Age(instant) => match op {
+                ">" | ">=" => TimeRange { start: None, end: Some(instant) },
+                _ => TimeRange { start: Some(instant), end: None },
+            },
+        };
+        Ok(Query::Term(Predicate::Modified(range)))
+    }
+
+    // `2024-01-01..2024-06-30` covers both end days; either end may be left open.
+    // Ages work too, in either order: `modified:30d..7d`.
+    fn modified_range_term(&self, low: &str, high: &str, start: usize) -> Result<Query, QueryError> {
+        if low.trim().is_empty() && high.trim().is_empty() {
+            return Err(self.error_at(start, "a date range needs at least one end, e.g. 2024-01-01..2024-06-30"));
+        }
+
+        let end = |text: &str, at: usize| -> Result<Option<DateValue>, QueryError> {
+            if text.trim().is_empty() {
+                Ok(None)
+            } else {
+                self.date_value(text, at).map(Some)
+            }
+        };
+        let range = match (end(low, start)?, end(high, start + low.len() + 2)?) {
+            // Older or newer first, two ages are the span between them
+            (Some(DateValue::Age(a)), Some(DateValue::Age(b))) => TimeRange { start: Some(a.min(b)), end: Some(a.max(b)) },
+            (low, high) => {
+                let range = TimeRange {
+                    start: low.and_then(|value| value.period().start),
+                    end: high.and_then(|value| value.period().end),
+                };
+                if let (Some(from), Some(to)) = (range.start, range.end) {
+                    if from >= to {
+                        return Err(self.error_at(start, "the start of a date range is later than its end"));
+                    }
+                }
+                range
+            }
+        };
+        Ok(Query::Term(Predicate::Modified(range)))
+    }
+
+    fn date_value(&self, text: &str, start: usize) -> Result<DateValue, QueryError> {
+        let text = text.trim();
+        self.parse_date_value(&text.to_lowercase()).ok_or_else(|| {
+            self.error_at(start, &format!("'{}' is not a date (try 2025-01-01, 2024, today, lastweek or 30d)", text))
+        })
+    }
+
+    fn parse_date_value(&self, text: &str) -> Option<DateValue> {
+        let today = self.now.date_naive();
+        let monday = today - Duration::days(i64::from(today.weekday().num_days_from_monday()));
+        let month_start = today.with_day(1)?;
+        let year_start = month_start.with_month(1)?;
+
+        let period = match text {
+            "today" => day_period(today),
+            "yesterday" => day_period(today.pred_opt()?),
+            "thisweek" => span_period(monday, monday + Duration::days(7)),
+            "lastweek" => span_period(monday - Duration::days(7), monday),
+            "thismonth" => span_period(month_start, month_start.checked_add_months(Months::new(1))?),
+            "lastmonth" => span_period(month_start.checked_sub_months(Months::new(1))?, month_start),
+            "thisyear" => span_period(year_start, year_start.checked_add_months(Months::new(12))?),
+            "lastyear" => span_period(year_start.checked_sub_months(Months::new(12))?, year_start),
+            _ => return self.parse_age(text).or_else(|| parse_calendar_date(text)),
+        };
+        Some(DateValue::Period(period))
+    }
+
+    // `12h`, `30d`, `2w`, `6mo`, `1y` before now
+    fn parse_age(&self, text: &str) -> Option<DateValue> {
+        let split = text.find(|c: char| !c.is_ascii_digit())?;
+        let count: u32 = text[..split].parse().ok()?;
+        let instant = match &text[split..] {
+            "h" => self.now.checked_sub_signed(Duration::try_hours(i64::from(count))?),
+            "d" => self.now.checked_sub_signed(Duration::try_days(i64::from(count))?),
+            "w" => self.now.checked_sub_signed(Duration::try_weeks(i64::from(count))?),
+            "mo" => self.now.checked_sub_months(Months::new(count)),
+            "y" => self.now.checked_sub_months(Months::new(count.checked_mul(12)?)),
+            _ => None,
+        }?;
+        Some(DateValue::Age(SystemTime::from(instant)))
+    }
+}
+
+enum DateValue {
+    // A calendar span such as a day, month or year
+    Period(TimeRange),
+    // A point in time some distance before now
+    Age(SystemTime),
+}
+
+impl DateValue {
+    // An age as the instant it names
+    fn period(self) -> TimeRange {
+        match self {
+            DateValue::Period(period) => period,
+            DateValue::Age(instant) => TimeRange { start: Some(instant), end: Some(instant) },
+        }
+    }
+}
+
+// `2024`, `2024-05` or `2024-05-17`
+fn parse_calendar_date(text: &str) -> Option<DateValue> {
+    let parts: Vec<&str> = text.split('-').collect();
+    let numbers: Vec<u32> = parts.iter().map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    if parts[0].len() != 4 {
+        return None;
+    }
+    let year = numbers[0] as i32;
+
+    let period = match numbers[..] {
+        [_] => {
+            let first = NaiveDate::from_ymd_opt(year, 1, 1)?;
+            span_period(first, NaiveDate::from_ymd_opt(year + 1, 1, 1)?)
+        }
+        [_, month] => {
+            let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+            span_period(first, first.checked_add_months(Months::new(1))?)
+        }
+        [_, month, day] => day_period(NaiveDate::from_ymd_opt(year, month, day)?),
+        _ => return None,
+    };
+    Some(DateValue::Period(period))
+}
+
+fn day_period(date: NaiveDate) -> TimeRange {
+    TimeRange { start: local_midnight(date), end: date.succ_opt().and_then(local_midnight) }
+}
+
+// From the start of `first` up to, but not including, the start of `end`
+fn span_period(first: NaiveDate, end: NaiveDate) -> TimeRange {
+    TimeRange { start: local_midnight(first), end: local_midnight(end) }
+}
+
+fn is_regex_literal(word: &str) -> bool {
+    word.len() > 2 && word.starts_with('/') && word.ends_with('/')
+}
+
+// Brackets alone don't make a glob, so `[final]` still finds names containing it;
+// with a `*` or `?` as well they are a character class, as in `[ab]*.dwg`
+fn is_glob(word: &str) -> bool {
+    word.contains(['*', '?'])
+}
+
+// Translates a glob into an anchored regex. `*` and `?` stay within one folder,
+// `**` crosses folders, `[abc]`/`[!abc]` are character classes, and `/` and `\`
+// both match either separator. Path globs only need to match the end of the path,
+// starting at a folder boundary.
+// Errors carry the byte offset of the problem within the glob.
+fn glob_to_regex(glob: &str, is_path: bool) -> Result<String, (usize, &'static str)> {
+    const SEPARATOR: &str = r"[/\\]";
+    const NOT_SEPARATOR: &str = r"[^/\\]";
+
+    let mut regex = String::from(if is_path { r"(?:^|[/\\])" } else { "^" });
+    let mut chars = glob.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '*' if chars.peek().map(|&(_, n)| n) == Some('*') => {
+                chars.next();
+                // `**/` also matches no folders at all
+                if chars.peek().is_some_and(|&(_, n)| n == '/' || n == '\\') {
+                    chars.next();
+                    regex.push_str(&format!("(?:.*{})?", SEPARATOR));
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str(&format!("{}*", NOT_SEPARATOR)),
+            '?' => regex.push_str(NOT_SEPARATOR),
+            '/' | '\\' => regex.push_str(SEPARATOR),
+            '[' => {
+                let mut class = String::from("[");
+                if chars.peek().is_some_and(|&(_, n)| n == '!' || n == '^') {
+                    chars.next();
+                    class.push('^');
+                }
+                let mut closed = false;
+                let mut first = true;
+                for (_, n) in chars.by_ref() {
+                    match n {
+                        // A ']' right after the opening bracket is literal
+                        ']' if !first => {
+                            closed = true;
+                            break;
+                        }
+                        '\\' | '[' | ']' | '^' | '&' | '~' => {
+                            class.push('\\');
+                            class.push(n);
+                        }
+                        _ => class.push(n),
+                    }
+                    first = false;
+                }
+                if !closed {
+                    return Err((i, "missing closing ']' in pattern"));
+                }
+                class.push(']');
+                regex.push_str(&class);
+            }
+            _ => regex.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+        }
+    }
+
+    regex.push('$');
+    Ok(regex)
+}
+
+// Only treat a bare word with `..` as a range when both ends read as sizes
+fn is_size_range(word: &str) -> bool {
+    match word.split_once("..") {
+        Some((low, high)) => {
+            (!low.is_empty() || !high.is_empty())
+                && (low.is_empty() || parse_size(low).is_ok())
+                && (high.is_empty() || parse_size(high).is_ok())
+        }
+        None => false,
+    }
+}
+
+// Splits a leading comparison operator (>, >=, <, <=, =) off a value
+fn split_comparison(value: &str) -> (&str, &str) {
+    for op in [">=", "<=", ">", "<", "="] {
+        if let Some(rest) = value.strip_prefix(op) {
+            return (op, rest.trim());
+        }
+    }
+    ("", value.trim())
+}
+
+// Start of the given day in local time, the zone `modified_formatted` displays in
+fn local_midnight(date: NaiveDate) -> Option<SystemTime> {
+    let midnight = date.and_hms_opt(0, 0, 0)?;
+    Local.from_local_datetime(&midnight)
+        .earliest()
+        .map(SystemTime::from)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeError {
+    Invalid,
+    TooLarge,
+}
+
+// Parses sizes like `500`, `12b`, `1.5gb`, `10MB` or `4MiB`. SI units (kb, mb, gb, tb)
+// are powers of 1000 and IEC units (kib, mib, gib, tib) powers of 1024; the short
+// k/m/g/t forms stay binary, matching how sizes are displayed.
+pub fn parse_size(s: &str) -> Result<u64, SizeError> {
+    let s = s.trim().to_lowercase();
+
+    let split = s.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(s.len());
+    let (number_part, unit_part) = (s[..split].trim(), s[split..].trim());
+    if number_part.is_empty() {
+        return Err(SizeError::Invalid);
+    }
+
+    let multiplier: u64 = match unit_part {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000u64.pow(2),
+        "gb" => 1000u64.pow(3),
+        "tb" => 1000u64.pow(4),
+        "k" | "kib" => 1 << 10,
+        "m" | "mib" => 1 << 20,
+        "g" | "gib" => 1 << 30,
+        "t" | "tib" => 1 << 40,
+        _ => return Err(SizeError::Invalid),
+    };
+
+    // Whole numbers stay exact; fractions go through f64
+    if let Ok(number) = number_part.parse::<u64>() {
+        return number.checked_mul(multiplier).ok_or(SizeError::TooLarge);
+    }
+    let number: f64 = number_part.parse().map_err(|_| SizeError::Invalid)?;
+    if !number.is_finite() || number < 0.0 {
+        return Err(SizeError::Invalid);
+    }
+    let bytes = number * multiplier as f64;
+    if bytes >= u64::MAX as f64 {
+        return Err(SizeError::TooLarge);
+    }
+    Ok(bytes.round() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Query {
+        Query::Term(Predicate::Text { field: TextField::Any, text: text.to_string() })
+    }
+
+    fn not(query: Query) -> Query {
+        Query::Not(Box::new(query))
+    }
+
+    fn pattern(query: &str) -> (TextField, Pattern) {
+        match parse(query) {
+            Ok(Query::Term(Predicate::Pattern { field, pattern })) => (field, pattern),
+            other => panic!("{} parsed as {:?}", query, other),
+        }
+    }
+
+    fn error(query: &str) -> QueryError {
+        parse(query).expect_err(query)
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(parse("").unwrap(), Query::All);
+        assert_eq!(parse("a b").unwrap(), Query::And(vec![text("a"), text("b")]));
+        assert_eq!(parse("a AND b").unwrap(), parse("a b").unwrap());
+        // OR binds loosest, NOT tightest
+        assert_eq!(
+            parse("a b OR c").unwrap(),
+            Query::Or(vec![Query::And(vec![text("a"), text("b")]), text("c")])
+        );
+        assert_eq!(
+            parse("NOT a OR b").unwrap(),
+            Query::Or(vec![not(text("a")), text("b")])
+        );
+        assert_eq!(
+            parse("a (b OR c)").unwrap(),
+            Query::And(vec![text("a"), Query::Or(vec![text("b"), text("c")])])
+        );
+        assert_eq!(parse("NOT (a b)").unwrap(), not(Query::And(vec![text("a"), text("b")])));
+        // Operators are only operators in capitals
+        assert_eq!(parse("a or b").unwrap(), Query::And(vec![text("a"), text("or"), text("b")]));
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!(parse("-a").unwrap(), not(text("a")));
+        assert_eq!(parse("a -b").unwrap(), Query::And(vec![text("a"), not(text("b"))]));
+        assert_eq!(parse("-(a OR b)").unwrap(), not(Query::Or(vec![text("a"), text("b")])));
+        // A lone or inner '-' is text
+        assert_eq!(parse("a - b").unwrap(), Query::And(vec![text("a"), text("-"), text("b")]));
+        assert_eq!(parse("site-plan").unwrap(), text("site-plan"));
+        assert_eq!(error("NOT").message, "NOT needs a term to exclude");
+    }
+
+    #[test]
+    fn unbalanced_parentheses() {
+        let missing = error("(a OR b");
+        assert_eq!((missing.position, missing.message.as_str()), (0, "missing closing ')'"));
+        let unmatched = error("a OR b )");
+        assert_eq!((unmatched.position, unmatched.message.as_str()), (7, "unmatched ')'"));
+        assert_eq!(error("a b)").message, "unmatched ')'");
+        assert_eq!(error("( )").message, "expected a search term");
+        assert_eq!(error("OR a").message, "expected a search term");
+        assert_eq!(error("a AND").message, "AND needs a term on its right");
+    }
+
+    #[test]
+    fn parentheses_in_names() {
+        assert_eq!(parse("Report (1)").unwrap(), Query::And(vec![text("report"), text("(1)")]));
+        assert_eq!(parse("Report(1)").unwrap(), text("report(1)"));
+        assert_eq!(parse("(copy).pdf").unwrap(), text("(copy).pdf"));
+        assert_eq!(parse("plan(a)b").unwrap(), text("plan(a)b"));
+        // A name ending in ')' inside a group
+        assert_eq!(
+            parse("(Report(1) OR b)").unwrap(),
+            Query::Or(vec![text("report(1)"), text("b")])
+        );
+        // A single field term in parentheses still groups
+        assert_eq!(parse("(ext:pdf)").unwrap(), Query::Term(Predicate::Extension(vec!["pdf".into()])));
+        assert_eq!(parse("(-a)").unwrap(), not(text("a")));
+    }
+
+    #[test]
+    fn quoted_values() {
+        assert_eq!(parse("\"site plan\"").unwrap(), text("site plan"));
+        assert_eq!(parse("\"a OR b\"").unwrap(), text("a or b"));
+        assert_eq!(parse("\"say \\\"hi\\\"\"").unwrap(), text("say \"hi\""));
+        assert_eq!(
+            parse("name:\"Site Plan\"").unwrap(),
+            Query::Term(Predicate::Text { field: TextField::Name, text: "site plan".into() })
+        );
+        // Quoted globs and sizes are plain text
+        assert_eq!(parse("\"*.pdf\"").unwrap(), text("*.pdf"));
+        assert_eq!(parse("\">10mb\"").unwrap(), text(">10mb"));
+        let unclosed = error("a \"b c");
+        assert_eq!((unclosed.position, unclosed.message.as_str()), (2, "missing closing quote"));
+    }
+
+    #[test]
+    fn fields_and_values() {
+        // Unknown fields are just text with a colon in it
+        assert_eq!(parse("foo:bar").unwrap(), text("foo:bar"));
+        assert_eq!(parse("C:\\Temp").unwrap(), text("c:\\temp"));
+        assert_eq!(
+            parse("EXT:.PDF,dwg").unwrap(),
+            Query::Term(Predicate::Extension(vec!["pdf".into(), "dwg".into()]))
+        );
+        assert_eq!(
+            parse("type:image,video").unwrap(),
+            Query::Term(Predicate::Category(vec![FileCategory::Image, FileCategory::Video]))
+        );
+
+        assert_eq!(error("ext:").message, "expected a value after ext:");
+        let bad_type = error("type:image,bogus");
+        assert_eq!(bad_type.position, 11);
+        assert!(bad_type.message.starts_with("unknown type"));
+        let bad_size = error("size:>lots");
+        assert_eq!(bad_size.position, 6);
+        assert!(bad_size.message.contains("'lots' is not a size"));
+        assert!(error("size:10mb..1mb").message.contains("larger than its end"));
+        let reversed = error("size:>1 modified:2024-06-30..2024-01-01");
+        assert_eq!(reversed.position, 17);
+        assert!(reversed.message.contains("later than its end"));
+        assert!(error("modified:2024-01-02..2024-01-01").message.contains("later than its end"));
+        assert!(error("modified:7d..2024-01-01").message.contains("later than its end"));
+        assert!(parse("modified:2024-01-01..2024-01-01").is_ok());
+        assert!(parse("modified:2024..2024-03").is_ok());
+        assert!(parse("modified:30d..7d").is_ok() && parse("modified:7d..30d").is_ok());
+        assert!(error("modified:someday").message.contains("'someday' is not a date"));
+        assert!(error("modified:2024-13-01").message.contains("is not a date"));
+        assert!(error("size:99999999tb").message.contains("too large"));
+    }
+
+    #[test]
+    fn globs() {
+        let (field, glob) = pattern("*.PDF");
+        assert_eq!(field, TextField::Name);
+        assert!(glob.is_match("plan.pdf"));
+        assert!(!glob.is_match("plan.pdf.bak"));
+
+        let (field, glob) = pattern("site?.dwg");
+        assert_eq!(field, TextField::Name);
+        assert!(glob.is_match("site1.dwg"));
+        assert!(!glob.is_match("site10.dwg"));
+
+        let (_, class) = pattern("[ab]*.txt");
+        assert!(class.is_match("a1.txt"));
+        assert!(!class.is_match("c1.txt"));
+        let (_, negated) = pattern("[!ab]*");
+        assert!(negated.is_match("c"));
+        assert!(!negated.is_match("a"));
+
+        // A separator makes it a path glob; `**` crosses folders
+        let (field, path) = pattern("projects/**/*.dwg");
+        assert_eq!(field, TextField::FullPath);
+        assert!(path.is_match("C:\\work\\projects\\a\\b\\x.dwg"));
+        assert!(path.is_match("/work/projects/x.dwg"));
+        assert!(!path.is_match("/work/projects.dwg"));
+
+        let (field, _) = pattern("name:*plan*");
+        assert_eq!(field, TextField::Name);
+        assert_eq!(error("[ab*").message, "missing closing ']' in pattern");
+    }
+
+    #[test]
+    fn brackets_without_wildcards_are_text() {
+        assert_eq!(parse("[final]").unwrap(), text("[final]"));
+        assert_eq!(parse("report[1].pdf").unwrap(), text("report[1].pdf"));
+        assert_eq!(
+            parse("name:[draft]").unwrap(),
+            Query::Term(Predicate::Text { field: TextField::Name, text: "[draft]".into() })
+        );
+        let (_, glob) = pattern("[final]*");
+        assert!(glob.is_match("final.pdf"));
+    }
+
+    #[test]
+    fn regexes() {
+        let (field, regex) = pattern("/^IMG_\\d{4}\\.jpe?g$/");
+        assert_eq!(field, TextField::Any);
+        assert!(regex.is_match("img_1234.JPG"));
+        assert!(!regex.is_match("img_12.jpg"));
+
+        // Spaces and parentheses stay inside the regex
+        let (_, spaced) = pattern("/(a|b) c/");
+        assert!(spaced.is_match("b c"));
+
+        let (field, _) = pattern("path:/archive\\/20\\d\\d/");
+        assert_eq!(field, TextField::FullPath);
+        assert_eq!(parse("a/b").unwrap(), text("a/b"));
+        assert!(error("/(unclosed/").message.starts_with("invalid regular expression"));
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("1kb"), Ok(1000));
+        assert_eq!(parse_size("1KB"), Ok(1000));
+        for unit in ["k", "kib", "KiB"] {
+            assert_eq!(parse_size(&format!("1{}", unit)), Ok(1024));
+        }
+        // SI and IEC spellings of the same prefix differ
+        assert_ne!(parse_size("1mb"), parse_size("1mib"));
+        assert_eq!(parse_size("10mb"), Ok(10_000_000));
+        assert_eq!(parse_size("10mib"), Ok(10 << 20));
+        assert_eq!(parse_size("1.5 GB"), Ok(1_500_000_000));
+        assert_eq!(parse_size("1.5 GiB"), Ok(3 << 29));
+        assert_eq!(parse_size("2tb"), Ok(2_000_000_000_000));
+        assert_eq!(parse_size("2t"), Ok(2 << 40));
+        assert_eq!(parse_size("500"), Ok(500));
+        assert_eq!(parse_size("12b"), Ok(12));
+        assert_eq!(parse_size("mb"), Err(SizeError::Invalid));
+        assert_eq!(parse_size("10zb"), Err(SizeError::Invalid));
+    }
+
+    #[test]
+    fn size_overflow() {
+        // The largest whole number of terabytes that fits, and one more
+        let limit = u64::MAX / 1000u64.pow(4);
+        assert_eq!(parse_size(&format!("{}tb", limit)), Ok(limit * 1000u64.pow(4)));
+        assert_eq!(parse_size(&format!("{}tb", limit + 1)), Err(SizeError::TooLarge));
+        let limit = u64::MAX >> 40;
+        assert_eq!(parse_size(&format!("{}tib", limit)), Ok(limit << 40));
+        assert_eq!(parse_size(&format!("{}tib", limit + 1)), Err(SizeError::TooLarge));
+        assert_eq!(parse_size("99999999tb"), Err(SizeError::TooLarge));
+    }
+
+    // Wednesday 2025-06-11, mid-afternoon local time
+    fn pinned_now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 6, 11, 15, 30, 0).unwrap()
+    }
+
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> SystemTime {
+        Local.with_ymd_and_hms(year, month, day, hour, minute, second).unwrap().into()
+    }
+
+    fn modified(query: &str) -> TimeRange {
+        match parse_at(query, pinned_now()) {
+            Ok(Query::Term(Predicate::Modified(range))) => range,
+            other => panic!("{} parsed as {:?}", query, other),
+        }
+    }
+
+    #[test]
+    fn named_periods() {
+        // Midnight starts today; the next midnight belongs to tomorrow
+        let today = modified("modified:today");
+        assert!(today.contains(local(2025, 6, 11, 0, 0, 0)));
+        assert!(today.contains(local(2025, 6, 11, 23, 59, 59)));
+        assert!(!today.contains(local(2025, 6, 10, 23, 59, 59)));
+        assert!(!today.contains(local(2025, 6, 12, 0, 0, 0)));
+        assert_eq!(modified("modified:yesterday").end, today.start);
+
+        // Monday to Sunday of the week before
+        let last_week = modified("modified:lastweek");
+        assert_eq!(last_week.start, Some(local(2025, 6, 2, 0, 0, 0)));
+        assert_eq!(last_week.end, Some(local(2025, 6, 9, 0, 0, 0)));
+        assert_eq!(modified("modified:thisweek").start, last_week.end);
+        assert_eq!(modified("modified:lastmonth").start, Some(local(2025, 5, 1, 0, 0, 0)));
+        assert_eq!(modified("modified:thisyear").end, Some(local(2026, 1, 1, 0, 0, 0)));
+
+        // Before today, and from today on
+        assert_eq!(modified("modified:<today"), TimeRange { start: None, end: today.start });
+        assert_eq!(modified("modified:>=today"), TimeRange { start: today.start, end: None });
+    }
+
+    #[test]
+    fn ages() {
+        let now: SystemTime = pinned_now().into();
+        let days = |days: u64| now - std::time::Duration::from_secs(days * 24 * 60 * 60);
+        let second = std::time::Duration::from_secs(1);
+        let newer = modified("modified:<30d");
+        let older = modified("modified:>30d");
+        // A file exactly 30 days old counts as within the last 30 days, so every
+        // dated file falls on exactly one side
+        assert!(newer.contains(days(30)) && !older.contains(days(30)));
+        assert!(newer.contains(days(30) + second) && !older.contains(days(30) + second));
+        assert!(!newer.contains(days(30) - second) && older.contains(days(30) - second));
+        assert!(newer.contains(now));
+        assert_eq!(modified("modified:<=30d"), newer);
+        assert_eq!(modified("modified:>=30d"), older);
+        assert_eq!(modified("modified:12h").start, Some(now - std::time::Duration::from_secs(12 * 60 * 60)));
+
+        // Either order gives the span between two ages
+        let span = modified("modified:30d..7d");
+        assert_eq!(span, modified("modified:7d..30d"));
+        assert_eq!(span, TimeRange { start: Some(days(30)), end: Some(days(7)) });
+    }
+}
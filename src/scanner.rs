@@ -1,29 +1,51 @@
+use std::borrow::Cow;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, Instant};
 use rayon::prelude::*;
 use walkdir::{WalkDir, DirEntry};
 use crossbeam_channel::Sender;
+use serde::{Serialize, Serializer};
+use crate::category::{self, FileCategory};
+use crate::mounts::{self, ClusterSizes, MountTable};
+use crate::normalize;
 
 #[derive(Debug, Clone)]
 pub enum ScanMessage {
     Started,
     Batch(Vec<FileInfo>),
+    Skipped { path: PathBuf, reason: String },
     Completed { elapsed_ms: u128, file_count: usize },
 }
 
-#[derive(Debug, Clone)]
+// Serializes for scripts with the path as text and the date in ISO 8601
+#[derive(Debug, Clone, Serialize)]
 pub struct FileInfo {
+    #[serde(rename = "full_path", serialize_with = "serialize_path")]
     pub path: PathBuf,
     pub name: String,
     pub is_dir: bool,
     pub is_shortcut: bool,
     pub size: u64,
+    pub allocated_size: u64,
+    #[serde(serialize_with = "serialize_time")]
     pub modified: Option<SystemTime>,
     pub extension: Option<String>,
 }
 
+// Paths that aren't valid Unicode are written lossily rather than failing the export
+pub fn serialize_path<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&path.to_string_lossy())
+}
+
+fn serialize_time<S: Serializer>(time: &Option<SystemTime>, serializer: S) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serializer.serialize_str(&iso_time(*time)),
+        None => serializer.serialize_none(),
+    }
+}
+
 impl FileInfo {
-    fn from_entry(entry: &DirEntry) -> Option<Self> {
+    fn from_entry(entry: &DirEntry, cluster_size: Option<u64>) -> Option<Self> {
         let path = entry.path().to_path_buf();
         
         // Use entry.file_type() instead of full metadata for speed
@@ -42,12 +64,12 @@ impl FileInfo {
         let is_shortcut = extension.as_deref() == Some("lnk");
         
         // Only get full metadata for files (not directories) to save time
-        let (size, modified) = if !is_dir {
+        let (size, allocated_size, modified) = if !is_dir {
             entry.metadata().ok()
-                .map(|m| (m.len(), m.modified().ok()))
-                .unwrap_or((0, None))
+                .map(|m| (m.len(), mounts::allocated_size(&path, &m, cluster_size), m.modified().ok()))
+                .unwrap_or((0, 0, None))
         } else {
-            (0, None)
+            (0, 0, None)
         };
         
         Some(FileInfo {
@@ -55,73 +77,135 @@ impl FileInfo {
             is_dir,
             is_shortcut,
             size,
+            allocated_size,
             modified,
             extension,
             path,
         })
     }
+}
+
+// Which size to show and total: the file length, or the space it takes on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SizeMode {
+    #[default]
+    Apparent,
+    Allocated,
+}
+
+// Text that plain search terms are matched against, in `normalize::search_form`.
+// The full path is `prefix` followed by `name`; `prefix` is the containing folder
+// plus whatever separator joins it to the name.
+pub struct SearchKey<'a> {
+    pub name: Cow<'a, str>,
+    pub prefix: Cow<'a, str>,
+    pub folder_len: usize,
+}
+
+impl SearchKey<'_> {
+    pub fn new(name: &str, folder: &Path, fold_accents: bool) -> SearchKey<'static> {
+        let (prefix, folder_len) = SearchKey::folder_prefix(folder, fold_accents);
+        SearchKey {
+            name: Cow::Owned(normalize::search_form(name, fold_accents)),
+            prefix: Cow::Owned(prefix),
+            folder_len,
+        }
+    }
+
+    // The `prefix` for files in `folder`, and the length of its folder part
+    pub fn folder_prefix(folder: &Path, fold_accents: bool) -> (String, usize) {
+        let text = folder.to_string_lossy();
+        let joined = folder.join("");
+        let mut prefix = normalize::search_form(&text, fold_accents);
+        let folder_len = prefix.len();
+        prefix.push_str(&joined.to_string_lossy()[text.len()..]);
+        (prefix, folder_len)
+    }
+
+    pub fn folder(&self) -> &str {
+        &self.prefix[..self.folder_len]
+    }
+
+    // Whether the full path contains `needle`, without joining the two halves
+    pub fn path_contains(&self, needle: &str) -> bool {
+        self.prefix.contains(needle)
+            || self.name.contains(needle)
+            || needle.char_indices().skip(1).any(|(split, _)| {
+                self.prefix.ends_with(&needle[..split]) && self.name.starts_with(&needle[split..])
+            })
+    }
+}
+
+// Common view over a scanned file, implemented by `FileInfo` and by rows of the
+// compact `FileStore`, so filtering, sorting and display work on either.
+pub trait FileRecord {
+    fn name(&self) -> &str;
+    fn parent(&self) -> &Path;
+    fn full_path(&self) -> Cow<'_, Path>;
+    fn extension(&self) -> Option<&str>;
+    fn size(&self) -> u64;
+    fn allocated_size(&self) -> u64;
+    fn modified(&self) -> Option<SystemTime>;
+    fn is_dir(&self) -> bool;
+    fn is_shortcut(&self) -> bool;
+
+    // `FileStore` rows hand out keys computed once at insert; anything else
+    // normalizes on demand
+    fn search_key(&self, fold_accents: bool) -> SearchKey<'_> {
+        SearchKey::new(self.name(), self.parent(), fold_accents)
+    }
+
+    fn size_for(&self, mode: SizeMode) -> u64 {
+        match mode {
+            SizeMode::Apparent => self.size(),
+            SizeMode::Allocated => self.allocated_size(),
+        }
+    }
 
-    pub fn size_formatted(&self) -> String {
-        if self.is_dir {
+    fn size_formatted(&self, mode: SizeMode) -> String {
+        if self.is_dir() {
             String::new()
         } else {
-            format_size(self.size)
+            format_size(self.size_for(mode))
         }
     }
 
-    pub fn get_icon(&self) -> &str {
-        if self.is_dir {
-            return "📁";
+    fn category(&self) -> FileCategory {
+        self.category_and_icon().0
+    }
+
+    fn get_icon(&self) -> &'static str {
+        self.category_and_icon().1
+    }
+
+    // Category and icon together, from a single lookup of the extension
+    fn category_and_icon(&self) -> (FileCategory, &'static str) {
+        if self.is_dir() {
+            return (FileCategory::Folder, FileCategory::Folder.icon());
         }
-        
-        // Special icon for shortcuts
-        if self.is_shortcut {
-            return "🔗";
+        if self.is_shortcut() {
+            return (FileCategory::Shortcut, FileCategory::Shortcut.icon());
         }
+        let ext = self.extension().map(|s| s.to_lowercase());
+        category::lookup(ext.as_deref())
+    }
 
-        let ext = self.extension.as_ref().map(|s| s.to_lowercase());
-        match ext.as_deref() {
-            // Documents
-            Some("pdf") => "📄",
-            Some("doc") | Some("docx") => "📝",
-            Some("txt") | Some("md") | Some("rtf") => "📃",
-            Some("xls") | Some("xlsx") | Some("csv") => "📊",
-            Some("ppt") | Some("pptx") => "📽️",
-            
-            // Images
-            Some("jpg") | Some("jpeg") | Some("png") | Some("gif") | Some("bmp") | Some("svg") | Some("webp") => "🖼️",
-            Some("ico") => "🎨",
-            Some("psd") | Some("ai") => "🎨",
-            
-            // Video
-            Some("mp4") | Some("avi") | Some("mkv") | Some("mov") | Some("wmv") | Some("flv") => "🎬",
-            
-            // Audio
-            Some("mp3") | Some("wav") | Some("flac") | Some("aac") | Some("ogg") | Some("wma") => "🎵",
-            
-            // Code
-            Some("rs") | Some("py") | Some("js") | Some("ts") | Some("cpp") | Some("c") | Some("h") => "💻",
-            Some("html") | Some("css") | Some("scss") | Some("sass") => "🌐",
-            Some("json") | Some("xml") | Some("yaml") | Some("toml") => "⚙️",
-            Some("sql") | Some("db") => "🗄️",
-            
-            // Archives
-            Some("zip") | Some("rar") | Some("7z") | Some("tar") | Some("gz") => "📦",
-            
-            // Executables
-            Some("exe") | Some("msi") | Some("bat") | Some("cmd") => "⚡",
-            Some("dll") | Some("sys") => "🔧",
-            
-            // Special
-            Some("log") => "📋",
-            Some("bak") | Some("tmp") => "💾",
-            
-            _ => "📄"  // Default file icon
+    // An owned copy, for keeping hold of a file after the store is unlocked
+    fn to_info(&self) -> FileInfo {
+        FileInfo {
+            path: self.full_path().into_owned(),
+            name: self.name().to_string(),
+            is_dir: self.is_dir(),
+            is_shortcut: self.is_shortcut(),
+            size: self.size(),
+            allocated_size: self.allocated_size(),
+            modified: self.modified(),
+            extension: self.extension().map(str::to_string),
         }
     }
 
-    pub fn modified_formatted(&self) -> String {
-        self.modified
+    fn modified_formatted(&self) -> String {
+        self.modified()
             .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
             .map(|d| {
                 let datetime = chrono::DateTime::<chrono::Local>::from(
@@ -133,7 +217,50 @@ impl FileInfo {
     }
 }
 
-fn format_size(size: u64) -> String {
+impl FileRecord for FileInfo {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn parent(&self) -> &Path {
+        self.path.parent().unwrap_or_else(|| Path::new(""))
+    }
+
+    fn full_path(&self) -> Cow<'_, Path> {
+        Cow::Borrowed(&self.path)
+    }
+
+    fn extension(&self) -> Option<&str> {
+        self.extension.as_deref()
+    }
+
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn allocated_size(&self) -> u64 {
+        self.allocated_size
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    fn is_shortcut(&self) -> bool {
+        self.is_shortcut
+    }
+}
+
+// Local time with its UTC offset, e.g. `2025-01-15T09:30:00+01:00`
+pub fn iso_time(time: SystemTime) -> String {
+    chrono::DateTime::<chrono::Local>::from(time).to_rfc3339_opts(chrono::SecondsFormat::Secs, false)
+}
+
+pub fn format_size(size: u64) -> String {
     const UNITS: &[&str] = &["B", "KB", "MB", "GB", "TB"];
     let mut size = size as f64;
     let mut unit_index = 0;
@@ -150,20 +277,83 @@ fn format_size(size: u64) -> String {
     }
 }
 
+// Pseudo and kernel filesystems, which hold no user files. Network shares hold
+// real files, so they're only skipped when asked, e.g. `--skip-fs=nfs,cifs,...`
+const DEFAULT_SKIP_FS_TYPES: &[&str] = &[
+    "proc", "sysfs", "devpts", "devtmpfs", "cgroup", "cgroup2", "debugfs", "tracefs",
+    "securityfs", "configfs", "pstore", "bpf", "autofs", "mqueue", "hugetlbfs",
+    "fusectl", "binfmt_misc", "efivarfs", "rpc_pipefs",
+];
+
+#[derive(Debug, Clone)]
+pub struct ScanOptions {
+    // Do not descend into directories on a different device than the scan root
+    pub same_device: bool,
+    // Mount points with these filesystem types are skipped; "fuse" also covers "fuse.sshfs" etc.
+    pub skip_fs_types: Vec<String>,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            same_device: false,
+            skip_fs_types: DEFAULT_SKIP_FS_TYPES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+impl ScanOptions {
+    pub fn skips_fs_type(&self, fs_type: &str) -> bool {
+        self.skip_fs_types.iter().any(|skip| {
+            // Compared as bytes, since a multi-byte name needn't split where `skip` ends
+            fs_type.eq_ignore_ascii_case(skip)
+                || (fs_type.as_bytes().get(..skip.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(skip.as_bytes()))
+                    && fs_type.as_bytes().get(skip.len()) == Some(&b'.'))
+        })
+    }
+}
+
 pub struct Scanner {
     sender: Option<Sender<ScanMessage>>,
+    options: ScanOptions,
 }
 
 impl Scanner {
     pub fn new() -> Self {
-        Scanner { sender: None }
+        Scanner { sender: None, options: ScanOptions::default() }
     }
 
     pub fn with_sender(sender: Sender<ScanMessage>) -> Self {
-        Scanner { sender: Some(sender) }
+        Scanner { sender: Some(sender), options: ScanOptions::default() }
     }
 
-    pub fn scan_directory(&self, path: &Path) -> Vec<FileInfo> {
+    pub fn with_options(mut self, options: ScanOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    // Why a directory below the scan root should not be entered, if it should not
+    fn skip_reason(&self, entry: &DirEntry, root_device: Option<u64>, mounts: Option<&MountTable>) -> Option<String> {
+        if let Some(mounts) = mounts {
+            if let Some(fs_type) = mounts.mount_fs_type(entry.path()) {
+                if self.options.skips_fs_type(&fs_type) {
+                    return Some(format!("{} filesystem", fs_type));
+                }
+            }
+        }
+
+        if let Some(root_device) = root_device {
+            if mounts::device_id(entry.path()) != Some(root_device) {
+                return Some("different filesystem".to_string());
+            }
+        }
+
+        None
+    }
+
+    // Streams results to the sender in batches and returns the number of files found.
+    // Each batch is moved into the channel, so the receiver owns the only copy of every record.
+    pub fn scan_directory(&self, path: &Path) -> usize {
         let start_time = Instant::now();
         
         // Send start signal
@@ -173,42 +363,80 @@ impl Scanner {
         
         let batch_size = 100;
         let mut batch = Vec::with_capacity(batch_size);
-        let mut all_files = Vec::new();
+        let mut file_count = 0;
+
+        let root_device = if self.options.same_device {
+            mounts::device_id(path)
+        } else {
+            None
+        };
+        // The cluster size of each folder on the way down to the current entry, by
+        // depth, as mounted volumes below the root can use other sizes
+        let mut cluster_sizes = ClusterSizes::default();
+        let mut folder_clusters = vec![cluster_sizes.volume(path)];
+        let mount_table = if self.options.skip_fs_types.is_empty() {
+            None
+        } else {
+            Some(MountTable::load())
+        };
 
         let walker = WalkDir::new(path)
             .follow_links(false)
             .max_open(10)
             .into_iter()
+            .filter_entry(|e| {
+                if e.depth() == 0 || !e.file_type().is_dir() {
+                    return true;
+                }
+                match self.skip_reason(e, root_device, mount_table.as_ref()) {
+                    Some(reason) => {
+                        // Report skipped mounts instead of silently leaving them out
+                        if let Some(ref sender) = self.sender {
+                            let _ = sender.send(ScanMessage::Skipped {
+                                path: e.path().to_path_buf(),
+                                reason,
+                            });
+                        }
+                        false
+                    }
+                    None => true,
+                }
+            })
             .filter_map(|e| e.ok())
             .filter(|e| e.path() != path); 
 
         for entry in walker {
-            if let Some(file_info) = FileInfo::from_entry(&entry) {
+            let depth = entry.depth();
+            let cluster_size = folder_clusters[depth.saturating_sub(1).min(folder_clusters.len() - 1)];
+            if entry.file_type().is_dir() {
+                folder_clusters.truncate(depth);
+                folder_clusters.push(cluster_sizes.dir(entry.path(), cluster_size));
+                continue;
+            }
+
+            if let Some(file_info) = FileInfo::from_entry(&entry, cluster_size) {
                 // Skip directories - only include files
                 if file_info.is_dir {
                     continue;
                 }
                 
                 batch.push(file_info);
+                file_count += 1;
                 
                 if batch.len() >= batch_size {
-                    // Send batch without cloning
+                    // Hand the batch over and start a fresh one
+                    let full = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
                     if let Some(ref sender) = self.sender {
-                        let _ = sender.send(ScanMessage::Batch(batch.clone()));
+                        let _ = sender.send(ScanMessage::Batch(full));
                     }
-                    
-                    // Keep a local copy if needed
-                    all_files.extend(batch.drain(..));
-                    batch.reserve(batch_size);
                 }
             }
         }
 
         if !batch.is_empty() {
             if let Some(ref sender) = self.sender {
-                let _ = sender.send(ScanMessage::Batch(batch.clone()));
+                let _ = sender.send(ScanMessage::Batch(batch));
             }
-            all_files.extend(batch);
         }
         
         // Send completion signal with timing info
@@ -216,11 +444,11 @@ impl Scanner {
         if let Some(ref sender) = self.sender {
             let _ = sender.send(ScanMessage::Completed {
                 elapsed_ms: elapsed.as_millis(),
-                file_count: all_files.len(),
+                file_count,
             });
         }
 
-        all_files
+        file_count
     }
 
     pub fn scan_directory_parallel(&self, path: &Path) -> Vec<FileInfo> {
@@ -265,12 +493,54 @@ impl Scanner {
             .take(100000)  // Increased limit
             .collect();
 
+        let cluster_size = mounts::cluster_size(path);
         let files: Vec<_> = entries
             .par_iter()
-            .filter_map(|entry| FileInfo::from_entry(entry))
+            .filter_map(|entry| FileInfo::from_entry(entry, cluster_size))
             .filter(|f| !f.is_dir)  // Only include files, not directories
             .collect();
 
         files
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn skipping(types: &[&str]) -> ScanOptions {
+        ScanOptions { skip_fs_types: types.iter().map(|s| s.to_string()).collect(), ..ScanOptions::default() }
+    }
+
+    #[test]
+    fn default_skips_pseudo_filesystems_only() {
+        let options = ScanOptions::default();
+        for fs_type in ["proc", "sysfs", "cgroup2", "fusectl", "PROC"] {
+            assert!(options.skips_fs_type(fs_type), "{}", fs_type);
+        }
+        for fs_type in ["ext4", "ntfs", "nfs", "nfs4", "cifs", "smb3", "fuse.sshfs", "procfs"] {
+            assert!(!options.skips_fs_type(fs_type), "{}", fs_type);
+        }
+    }
+
+    #[test]
+    fn skipped_subtypes() {
+        let options = skipping(&["nfs", "fuse"]);
+        assert!(options.skips_fs_type("nfs"));
+        assert!(options.skips_fs_type("fuse.sshfs"));
+        assert!(options.skips_fs_type("FUSE.rclone"));
+        assert!(!options.skips_fs_type("nfs4"));
+        assert!(!options.skips_fs_type("fuseblk"));
+        assert!(!skipping(&[]).skips_fs_type("proc"));
+    }
+
+    #[test]
+    fn non_ascii_types() {
+        // Byte 2 falls inside `é`, which slicing the string there would panic on
+        let options = skipping(&["ab", "fu"]);
+        assert!(!options.skips_fs_type("a\u{e9}"));
+        assert!(!options.skips_fs_type("\u{e9}"));
+        assert!(!options.skips_fs_type("f\u{fc}se"));
+        assert!(skipping(&["\u{e9}t\u{e9}"]).skips_fs_type("\u{e9}t\u{e9}.x"));
+    }
+}
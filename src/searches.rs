@@ -0,0 +1,90 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+// Recent searches kept, newest first
+const MAX_HISTORY: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedSearch {
+    pub name: String,
+    pub query: String,
+}
+
+// Named searches and the most recently used ones, kept in the user's config
+// folder so they carry over between sessions and scanned folders
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchLibrary {
+    #[serde(default)]
+    pub saved: Vec<SavedSearch>,
+    #[serde(default)]
+    pub history: Vec<String>,
+}
+
+impl SearchLibrary {
+    // `%APPDATA%\FileListGenerator\searches.json`
+    pub fn default_path() -> Option<PathBuf> {
+        env::var_os("APPDATA").map(|dir| PathBuf::from(dir).join("FileListGenerator").join("searches.json"))
+    }
+
+    // A missing file is an empty library
+    pub fn load(path: &Path) -> Result<Self, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // Names match ignoring case
+    pub fn find(&self, name: &str) -> Option<&SavedSearch> {
+        self.saved.iter().find(|search| search.name.eq_ignore_ascii_case(name))
+    }
+
+    // Adds a saved search, replacing one with the same name; saved searches stay
+    // sorted by name
+    pub fn save_search(&mut self, name: &str, query: &str) {
+        let search = SavedSearch { name: name.trim().to_string(), query: query.trim().to_string() };
+        match self.saved.iter_mut().find(|s| s.name.eq_ignore_ascii_case(&search.name)) {
+            Some(existing) => *existing = search,
+            None => self.saved.push(search),
+        }
+        self.saved.sort_by_key(|s| s.name.to_lowercase());
+    }
+
+    pub fn remove_search(&mut self, name: &str) -> bool {
+        let before = self.saved.len();
+        self.saved.retain(|search| !search.name.eq_ignore_ascii_case(name));
+        self.saved.len() != before
+    }
+
+    // Moves `query` to the front of the history; blank queries aren't kept
+    pub fn record(&mut self, query: &str) {
+        let query = query.trim();
+        if query.is_empty() {
+            return;
+        }
+        self.history.retain(|previous| previous != query);
+        self.history.insert(0, query.to_string());
+        self.history.truncate(MAX_HISTORY);
+    }
+
+    // The query `@name` refers to, or `text` itself when it doesn't start with `@`
+    pub fn resolve(&self, text: &str) -> Result<String, String> {
+        match text.trim().strip_prefix('@') {
+            Some(name) => self.find(name)
+                .map(|search| search.query.clone())
+                .ok_or_else(|| format!("no saved search named '{}'", name)),
+            None => Ok(text.to_string()),
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::iter::Peekable;
+use std::str::Chars;
+use crate::normalize;
+use crate::scanner::{FileRecord, SizeMode};
+
+// What a list can be sorted by; each list column has one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortField {
+    Name,
+    Type,
+    Category,
+    Size,
+    Modified,
+    Path,
+    Match,
+    Lines,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: SortField,
+    pub ascending: bool,
+}
+
+impl SortKey {
+    pub fn new(field: SortField, ascending: bool) -> Self {
+        SortKey { field, ascending }
+    }
+}
+
+// Per-row values that aren't part of the file itself: fuzzy match scores indexed
+// by file, and the matching line numbers of a content search
+pub struct SortContext<'a> {
+    pub size_mode: SizeMode,
+    pub scores: &'a [u32],
+    pub lines: &'a HashMap<usize, Vec<u32>>,
+}
+
+impl SortContext<'_> {
+    fn score(&self, index: usize) -> u32 {
+        self.scores.get(index).copied().unwrap_or(0)
+    }
+
+    fn line_count(&self, index: usize) -> usize {
+        self.lines.get(&index).map_or(0, Vec::len)
+    }
+}
+
+// Sorts `rows` of file indexes by each key in turn, later keys breaking ties in
+// earlier ones. The sort is stable, so rows that compare equal on every key keep
+// their current order.
+pub fn sort_rows<R: FileRecord>(rows: &mut [usize], keys: &[SortKey], file: impl Fn(usize) -> R, context: &SortContext) {
+    if keys.is_empty() {
+        return;
+    }
+    rows.sort_by(|&a, &b| compare_rows(keys, a, b, &file, context));
+}
+
+// Adds `new_rows` to `rows`, which is already sorted by `keys`, keeping it sorted.
+// New rows go after any they tie with, just as appending them and sorting again
+// would put them, but each only costs a binary search rather than a full sort.
+pub fn merge_rows<R: FileRecord>(rows: &mut Vec<usize>, mut new_rows: Vec<usize>, keys: &[SortKey], file: impl Fn(usize) -> R, context: &SortContext) {
+    if keys.is_empty() {
+        rows.extend(new_rows);
+        return;
+    }
+    sort_rows(&mut new_rows, keys, &file, context);
+    let old = std::mem::take(rows);
+    rows.reserve(old.len() + new_rows.len());
+    let mut rest = &old[..];
+    for row in new_rows {
+        let at = rest.partition_point(|&existing| compare_rows(keys, existing, row, &file, context).is_le());
+        rows.extend_from_slice(&rest[..at]);
+        rows.push(row);
+        rest = &rest[at..];
+    }
+    rows.extend_from_slice(rest);
+}
+
+fn compare_rows<R: FileRecord>(keys: &[SortKey], a_index: usize, b_index: usize, file: &impl Fn(usize) -> R, context: &SortContext) -> Ordering {
+    let (a, b) = (file(a_index), file(b_index));
+    keys.iter()
+        .map(|key| {
+            let order = compare(key.field, (a_index, &a), (b_index, &b), context);
+            if key.ascending { order } else { order.reverse() }
+        })
+        .find(|order| order.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+// Ascending order of two files, given with their indexes, on one field
+pub fn compare<R: FileRecord>(field: SortField, (a_index, a): (usize, &R), (b_index, b): (usize, &R), context: &SortContext) -> Ordering {
+    match field {
+        SortField::Name => natural_cmp(a.name(), b.name()),
+        SortField::Type => a.extension().unwrap_or("").cmp(b.extension().unwrap_or("")),
+        SortField::Category => a.category().cmp(&b.category()),
+        SortField::Size => a.size_for(context.size_mode).cmp(&b.size_for(context.size_mode)),
+        // Files without a date go last
+        SortField::Modified => match (a.modified(), b.modified()) {
+            (Some(ta), Some(tb)) => ta.cmp(&tb),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+        SortField::Path => natural_cmp(&a.parent().to_string_lossy(), &b.parent().to_string_lossy()),
+        SortField::Match => context.score(a_index).cmp(&context.score(b_index)),
+        SortField::Lines => context.line_count(a_index).cmp(&context.line_count(b_index)),
+    }
+}
+
+// Orders text the way people read it: runs of digits compare by their value, so
+// `Drawing2` comes before `Drawing10`, and letters compare ignoring case and
+// accents. Text that differs only in those falls back to an exact comparison so
+// the order is still total.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a_chars, mut b_chars) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let order = match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let (x_digits, y_digits) = (digit_run(&mut a_chars), digit_run(&mut b_chars));
+                let (x_value, y_value) = (x_digits.trim_start_matches('0'), y_digits.trim_start_matches('0'));
+                x_value.len().cmp(&y_value.len()).then_with(|| x_value.cmp(y_value))
+            }
+            (Some(x), Some(y)) => {
+                a_chars.next();
+                b_chars.next();
+                normalize::base_char(x).cmp(&normalize::base_char(y))
+            }
+        };
+        if order.is_ne() {
+            return order;
+        }
+    }
+}
+
+fn digit_run(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        digits.push(c);
+    }
+    digits
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+    use super::*;
+    use crate::scanner::FileInfo;
+
+    fn file(path: &str, size: u64, secs: Option<u64>) -> FileInfo {
+        let path = PathBuf::from(path);
+        FileInfo {
+            name: path.file_name().unwrap().to_string_lossy().into(),
+            is_dir: false,
+            is_shortcut: false,
+            size,
+            allocated_size: size,
+            modified: secs.map(|secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs)),
+            extension: path.extension().map(|e| e.to_string_lossy().to_lowercase()),
+            path,
+        }
+    }
+
+    fn sorted(files: &[FileInfo], keys: &[SortKey]) -> Vec<usize> {
+        let lines = HashMap::new();
+        let context = SortContext { size_mode: SizeMode::Apparent, scores: &[], lines: &lines };
+        let mut rows: Vec<usize> = (0..files.len()).collect();
+        sort_rows(&mut rows, keys, |i| files[i].clone(), &context);
+        rows
+    }
+
+    #[test]
+    fn numbers_compare_by_value() {
+        assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_cmp("file10", "file9"), Ordering::Greater);
+        assert_eq!(natural_cmp("1.10", "1.9"), Ordering::Greater);
+        assert_eq!(natural_cmp("x99999999999999999999999", "x100000000000000000000000"), Ordering::Less);
+        let mut names = vec!["Drawing10.dwg", "drawing1.dwg", "Drawing2.dwg", "Drawing2a.dwg"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["drawing1.dwg", "Drawing2.dwg", "Drawing2a.dwg", "Drawing10.dwg"]);
+    }
+
+    #[test]
+    fn leading_zeros() {
+        assert_eq!(natural_cmp("file007", "file8"), Ordering::Less);
+        assert_eq!(natural_cmp("file010", "file9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a07b", "a7c"), Ordering::Less);
+        // Equal values still order, so the sort is total
+        assert_eq!(natural_cmp("file007", "file7"), Ordering::Less);
+        assert_eq!(natural_cmp("file7", "file007"), Ordering::Greater);
+    }
+
+    #[test]
+    fn case_and_accents() {
+        assert_eq!(natural_cmp("abc", "ABD"), Ordering::Less);
+        assert_eq!(natural_cmp("Zebra", "apple"), Ordering::Greater);
+        assert_eq!(natural_cmp("ab", "ABC"), Ordering::Less);
+        assert_eq!(natural_cmp("R\u{e9}sum\u{e9}", "resume2"), Ordering::Less);
+        assert_ne!(natural_cmp("ABC", "abc"), Ordering::Equal);
+        assert_eq!(natural_cmp("abc", "abc"), Ordering::Equal);
+    }
+
+    #[test]
+    fn secondary_keys_break_ties() {
+        let files = vec![
+            file("/b/Sheet10.pdf", 5, Some(3)),
+            file("/a/Sheet2.pdf", 5, None),
+            file("/b/Sheet2.pdf", 1, Some(1)),
+            file("/a/Sheet10.pdf", 1, Some(2)),
+        ];
+        let path_name = [SortKey::new(SortField::Path, true), SortKey::new(SortField::Name, true)];
+        assert_eq!(sorted(&files, &path_name), [1, 3, 2, 0]);
+        let path_desc = [SortKey::new(SortField::Path, false), SortKey::new(SortField::Name, true)];
+        assert_eq!(sorted(&files, &path_desc), [2, 0, 1, 3]);
+        let size_name = [SortKey::new(SortField::Size, false), SortKey::new(SortField::Name, false)];
+        assert_eq!(sorted(&files, &size_name), [0, 1, 3, 2]);
+        // Files without a date go last
+        assert_eq!(sorted(&files, &[SortKey::new(SortField::Modified, true)]), [2, 3, 0, 1]);
+    }
+
+    #[test]
+    fn stable() {
+        let files = vec![
+            file("/b/Sheet10.pdf", 5, None),
+            file("/a/Sheet2.pdf", 5, None),
+            file("/b/Sheet2.pdf", 1, None),
+            file("/a/Sheet10.pdf", 1, None),
+        ];
+        // Equal sizes keep their current order, whichever the direction
+        assert_eq!(sorted(&files, &[SortKey::new(SortField::Size, true)]), [2, 3, 0, 1]);
+        assert_eq!(sorted(&files, &[SortKey::new(SortField::Size, false)]), [0, 1, 2, 3]);
+        assert_eq!(sorted(&files, &[SortKey::new(SortField::Modified, false)]), [0, 1, 2, 3]);
+        assert_eq!(sorted(&files, &[]), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn scores_and_lines() {
+        let files = [file("/a/x.txt", 1, None), file("/a/y.txt", 1, None), file("/a/z.txt", 1, None)];
+        let lines = HashMap::from([(0, vec![1, 2, 3]), (2, vec![4])]);
+        let context = SortContext { size_mode: SizeMode::Apparent, scores: &[5, 9], lines: &lines };
+        let mut rows = vec![0, 1, 2];
+        sort_rows(&mut rows, &[SortKey::new(SortField::Match, false)], |i| files[i].clone(), &context);
+        assert_eq!(rows, [1, 0, 2]);
+        sort_rows(&mut rows, &[SortKey::new(SortField::Lines, true)], |i| files[i].clone(), &context);
+        assert_eq!(rows, [1, 2, 0]);
+    }
+
+    #[test]
+    fn merge_matches_sort() {
+        let mut files = Vec::new();
+        let mut seed = 7u64;
+        for i in 0..400 {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let folder = (seed >> 33) % 5;
+            let size = (seed >> 40) % 4;
+            files.push(file(&format!("/d{}/File{}.txt", folder, (seed >> 20) % 50 + i % 3), size, None));
+        }
+        let lines = HashMap::new();
+        let context = SortContext { size_mode: SizeMode::Apparent, scores: &[], lines: &lines };
+        for keys in [
+            vec![SortKey::new(SortField::Size, true)],
+            vec![SortKey::new(SortField::Path, false), SortKey::new(SortField::Name, true)],
+            vec![],
+        ] {
+            // Batches as a scan sends them, with every other file passing the search
+            let mut merged = Vec::new();
+            for batch in (0..files.len()).collect::<Vec<_>>().chunks(37) {
+                let rows = batch.iter().copied().filter(|i| i % 2 == 0).collect();
+                merge_rows(&mut merged, rows, &keys, |i| files[i].clone(), &context);
+            }
+            let mut expected: Vec<usize> = (0..files.len()).filter(|i| i % 2 == 0).collect();
+            sort_rows(&mut expected, &keys, |i| files[i].clone(), &context);
+            assert_eq!(merged, expected);
+        }
+    }
+}
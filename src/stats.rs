@@ -0,0 +1,538 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::SystemTime;
+use serde_json::{json, Value};
+use crate::category::FileCategory;
+use crate::scanner::{format_size, iso_time, FileInfo, FileRecord, SizeMode};
+use crate::tree::html_escape;
+
+// Files listed as the largest, oldest and newest
+pub const TOP_FILES: usize = 10;
+// Extensions shown in the report; the JSON has all of them
+const TOP_EXTENSIONS: usize = 15;
+const BAR_WIDTH: usize = 30;
+
+const KB: u64 = 1024;
+const MB: u64 = 1024 * KB;
+const GB: u64 = 1024 * MB;
+// Size histogram bins after the one for empty files, by exclusive upper bound
+const SIZE_BINS: &[(u64, &str)] = &[
+    (KB, "Under 1 KB"),
+    (100 * KB, "1 KB to 100 KB"),
+    (MB, "100 KB to 1 MB"),
+    (10 * MB, "1 MB to 10 MB"),
+    (100 * MB, "10 MB to 100 MB"),
+    (GB, "100 MB to 1 GB"),
+    (u64::MAX, "1 GB and over"),
+];
+// Age histogram bins by exclusive upper bound in days, before the one for files without a date
+const AGE_BINS: &[(u64, &str)] = &[
+    (1, "Under a day"),
+    (7, "1 to 7 days"),
+    (30, "1 to 4 weeks"),
+    (182, "1 to 6 months"),
+    (365, "6 to 12 months"),
+    (730, "1 to 2 years"),
+    (1826, "2 to 5 years"),
+    (u64::MAX, "Over 5 years"),
+];
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+// File count and total size of one extension or category
+pub struct Tally {
+    pub name: String,
+    pub count: usize,
+    pub size: u64,
+}
+
+pub struct Bin {
+    pub label: &'static str,
+    pub count: usize,
+    pub size: u64,
+}
+
+// Totals and breakdowns over a set of listed files. Folders are only counted;
+// everything else is over files.
+pub struct Statistics {
+    pub size_mode: SizeMode,
+    pub file_count: usize,
+    pub folder_count: usize,
+    pub total_size: u64,
+    // Biggest first
+    pub by_extension: Vec<Tally>,
+    pub by_category: Vec<Tally>,
+    pub largest: Vec<FileInfo>,
+    pub oldest: Vec<FileInfo>,
+    pub newest: Vec<FileInfo>,
+    pub size_histogram: Vec<Bin>,
+    pub age_histogram: Vec<Bin>,
+}
+
+impl Statistics {
+    // Ages are measured back from `now`
+    pub fn compute<R: FileRecord>(files: impl IntoIterator<Item = R>, size_mode: SizeMode, now: SystemTime) -> Statistics {
+        let mut file_count = 0;
+        let mut folder_count = 0;
+        let mut total_size = 0;
+        let mut extensions: HashMap<String, (usize, u64)> = HashMap::new();
+        let mut categories: HashMap<FileCategory, (usize, u64)> = HashMap::new();
+        let mut largest = Vec::new();
+        let mut oldest = Vec::new();
+        let mut newest = Vec::new();
+        let mut size_histogram = bins(Some("Empty"), SIZE_BINS, None);
+        let mut age_histogram = bins(None, AGE_BINS, Some("No date"));
+
+        for file in files {
+            if file.is_dir() {
+                folder_count += 1;
+                continue;
+            }
+            let size = file.size_for(size_mode);
+            file_count += 1;
+            total_size += size;
+
+            let ext = file.extension().unwrap_or("");
+            match extensions.get_mut(ext) {
+                Some(tally) => add(tally, size),
+                None => add(extensions.entry(ext.to_string()).or_default(), size),
+            }
+            add(categories.entry(file.category()).or_default(), size);
+
+            let size_bin = match size {
+                0 => 0,
+                _ => 1 + SIZE_BINS.iter().position(|&(limit, _)| size < limit).unwrap_or(SIZE_BINS.len() - 1),
+            };
+            size_histogram[size_bin].count += 1;
+            size_histogram[size_bin].size += size;
+
+            let age_bin = match file.modified() {
+                Some(modified) => {
+                    // Dates in the future count as new
+                    let days = now.duration_since(modified).map_or(0, |age| age.as_secs() / DAY_SECS);
+                    AGE_BINS.iter().position(|&(limit, _)| days < limit).unwrap_or(AGE_BINS.len() - 1)
+                }
+                None => AGE_BINS.len(),
+            };
+            age_histogram[age_bin].count += 1;
+            age_histogram[age_bin].size += size;
+
+            offer(&mut largest, size, &file);
+            if let Some(modified) = file.modified() {
+                offer(&mut oldest, Reverse(modified), &file);
+                offer(&mut newest, modified, &file);
+            }
+        }
+
+        Statistics {
+            size_mode,
+            file_count,
+            folder_count,
+            total_size,
+            by_extension: tallies(extensions.into_iter().map(|(ext, tally)| {
+                (if ext.is_empty() { "(none)".to_string() } else { ext.to_uppercase() }, tally)
+            })),
+            by_category: tallies(categories.into_iter().map(|(category, tally)| (category.to_string(), tally))),
+            largest: files_only(largest),
+            oldest: files_only(oldest),
+            newest: files_only(newest),
+            size_histogram,
+            age_histogram,
+        }
+    }
+
+    pub fn average_size(&self) -> u64 {
+        if self.file_count == 0 { 0 } else { self.total_size / self.file_count as u64 }
+    }
+
+    // Plain text report with bar charts, for a fixed-width font
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        out.push_str(&format!("Files: {}    Folders: {}\r\n", self.file_count, self.folder_count));
+        out.push_str(&format!("Total size: {}    Average file size: {}\r\n", format_size(self.total_size), format_size(self.average_size())));
+
+        for (title, tallies) in [("By category", &self.by_category[..]), ("By extension", &self.by_extension[..])] {
+            out.push_str(&format!("\r\n{:<24}{:>10}{:>12}\r\n", title, "Files", "Size"));
+            for tally in tallies.iter().take(TOP_EXTENSIONS) {
+                out.push_str(&format!("  {:<22}{:>10}{:>12}\r\n", tally.name, tally.count, format_size(tally.size)));
+            }
+            if tallies.len() > TOP_EXTENSIONS {
+                out.push_str(&format!("  ({} more)\r\n", tallies.len() - TOP_EXTENSIONS));
+            }
+        }
+
+        for (title, bins) in [("File sizes", &self.size_histogram), ("Last modified", &self.age_histogram)] {
+            out.push_str(&format!("\r\n{}\r\n", title));
+            let most = bins.iter().map(|bin| bin.count).max().unwrap_or(0).max(1);
+            for bin in bins.iter() {
+                let bar = "█".repeat((bin.count * BAR_WIDTH).div_ceil(most));
+                out.push_str(&format!("  {:<18}{:>10}  {}\r\n", bin.label, bin.count, bar));
+            }
+        }
+
+        out.push_str("\r\nLargest files\r\n");
+        for file in &self.largest {
+            out.push_str(&format!("  {:>10}  {}\r\n", format_size(file.size_for(self.size_mode)), file.path.display()));
+        }
+        for (title, files) in [("Oldest files", &self.oldest), ("Newest files", &self.newest)] {
+            out.push_str(&format!("\r\n{}\r\n", title));
+            for file in files {
+                out.push_str(&format!("  {}  {}\r\n", file.modified_formatted(), file.path.display()));
+            }
+        }
+        out
+    }
+
+    // Sizes in bytes and dates in ISO 8601, for scripts
+    pub fn to_json(&self) -> Value {
+        let tallies = |tallies: &[Tally]| -> Vec<Value> {
+            tallies.iter().map(|t| json!({ "name": t.name, "count": t.count, "size": t.size })).collect()
+        };
+        let files = |files: &[FileInfo]| -> Vec<Value> {
+            files.iter().map(|file| json!({
+                "path": file.path.to_string_lossy(),
+                "size": file.size_for(self.size_mode),
+                "modified": file.modified.map(iso_time),
+            })).collect()
+        };
+        let bins = |bins: &[Bin]| -> Vec<Value> {
+            bins.iter().map(|b| json!({ "label": b.label, "count": b.count, "size": b.size })).collect()
+        };
+        json!({
+            "size_mode": match self.size_mode {
+                SizeMode::Apparent => "apparent",
+                SizeMode::Allocated => "allocated",
+            },
+            "file_count": self.file_count,
+            "folder_count": self.folder_count,
+            "total_size": self.total_size,
+            "average_size": self.average_size(),
+            "by_category": tallies(&self.by_category),
+            "by_extension": tallies(&self.by_extension),
+            "largest": files(&self.largest),
+            "oldest": files(&self.oldest),
+            "newest": files(&self.newest),
+            "size_histogram": bins(&self.size_histogram),
+            "age_histogram": bins(&self.age_histogram),
+        })
+    }
+
+    // Self-contained page with the same sections as the text report
+    pub fn to_html(&self, title: &str) -> String {
+        let mut body = String::new();
+        body.push_str(&format!(
+            "<p>{} files and {} folders, {} in total, {} on average per file</p>\n",
+            self.file_count, self.folder_count, format_size(self.total_size), format_size(self.average_size())
+        ));
+
+        for (heading, tallies) in [("By category", &self.by_category[..]), ("By extension", &self.by_extension[..])] {
+            body.push_str(&format!("<h2>{}</h2>\n<table>\n<tr><th></th><th>Files</th><th>Size</th></tr>\n", heading));
+            for tally in tallies.iter().take(TOP_EXTENSIONS) {
+                body.push_str(&format!(
+                    "<tr><td>{}</td><td class=\"n\">{}</td><td class=\"n\">{}</td></tr>\n",
+                    html_escape(&tally.name), tally.count, format_size(tally.size)
+                ));
+            }
+            body.push_str("</table>\n");
+        }
+
+        for (heading, bins) in [("File sizes", &self.size_histogram), ("Last modified", &self.age_histogram)] {
+            body.push_str(&format!("<h2>{}</h2>\n<table>\n", heading));
+            let most = bins.iter().map(|bin| bin.count).max().unwrap_or(0).max(1);
+            for bin in bins.iter() {
+                body.push_str(&format!(
+                    "<tr><td>{}</td><td class=\"n\">{}</td><td><div class=\"bar\" style=\"width: {}px\"></div></td></tr>\n",
+                    bin.label, bin.count, bin.count * 300 / most
+                ));
+            }
+            body.push_str("</table>\n");
+        }
+
+        body.push_str("<h2>Largest files</h2>\n<table>\n");
+        for file in &self.largest {
+            body.push_str(&format!(
+                "<tr><td class=\"n\">{}</td><td>{}</td></tr>\n",
+                format_size(file.size_for(self.size_mode)), html_escape(&file.path.to_string_lossy())
+            ));
+        }
+        body.push_str("</table>\n");
+        for (heading, files) in [("Oldest files", &self.oldest), ("Newest files", &self.newest)] {
+            body.push_str(&format!("<h2>{}</h2>\n<table>\n", heading));
+            for file in files {
+                body.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td></tr>\n",
+                    file.modified_formatted(), html_escape(&file.path.to_string_lossy())
+                ));
+            }
+            body.push_str("</table>\n");
+        }
+
+        STATISTICS_TEMPLATE
+            .replace("__TITLE__", &html_escape(title))
+            .replace("__BODY__", &body)
+    }
+}
+
+fn add(tally: &mut (usize, u64), size: u64) {
+    tally.0 += 1;
+    tally.1 += size;
+}
+
+fn bins(first: Option<&'static str>, bounds: &[(u64, &'static str)], last: Option<&'static str>) -> Vec<Bin> {
+    first.into_iter()
+        .chain(bounds.iter().map(|&(_, label)| label))
+        .chain(last)
+        .map(|label| Bin { label, count: 0, size: 0 })
+        .collect()
+}
+
+fn tallies(entries: impl Iterator<Item = (String, (usize, u64))>) -> Vec<Tally> {
+    let mut tallies: Vec<Tally> = entries.map(|(name, (count, size))| Tally { name, count, size }).collect();
+    tallies.sort_by(|a, b| b.size.cmp(&a.size).then(b.count.cmp(&a.count)).then_with(|| a.name.cmp(&b.name)));
+    tallies
+}
+
+// Keeps `top` as the `TOP_FILES` files with the greatest keys, greatest first;
+// of files with equal keys the first seen stays ahead
+fn offer<K: Ord, R: FileRecord>(top: &mut Vec<(K, FileInfo)>, key: K, file: &R) {
+    if top.len() == TOP_FILES && top.last().is_some_and(|(last, _)| key <= *last) {
+        return;
+    }
+    let at = top.partition_point(|(existing, _)| *existing >= key);
+    top.insert(at, (key, file.to_info()));
+    top.truncate(TOP_FILES);
+}
+
+fn files_only<K>(top: Vec<(K, FileInfo)>) -> Vec<FileInfo> {
+    top.into_iter().map(|(_, file)| file).collect()
+}
+
+const STATISTICS_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Statistics - __TITLE__</title>
+<style>
+body { margin: 16px; font-family: "Segoe UI", Arial, sans-serif; font-size: 13px; }
+h1 { font-size: 18px; font-weight: normal; }
+h2 { font-size: 15px; margin-top: 24px; }
+table { border-collapse: collapse; }
+th, td { padding: 3px 10px; text-align: left; border-bottom: 1px solid #eee; white-space: nowrap; }
+th { background: #f4f4f4; border-bottom: 1px solid #ccc; }
+td.n { text-align: right; }
+.bar { height: 12px; background: #4a7fb5; }
+</style>
+</head>
+<body>
+<h1>Statistics &ndash; __TITLE__</h1>
+__BODY__</body>
+</html>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    // A fixed clock: 2025-06-01 00:00:00 UTC
+    const NOW: u64 = 1_748_736_000;
+
+    fn now() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(NOW)
+    }
+
+    fn ago(secs: u64) -> Option<SystemTime> {
+        Some(now() - Duration::from_secs(secs))
+    }
+
+    fn file(path: &str, size: u64, modified: Option<SystemTime>) -> FileInfo {
+        let path = PathBuf::from(path);
+        FileInfo {
+            name: path.file_name().unwrap().to_string_lossy().into(),
+            is_dir: false,
+            is_shortcut: false,
+            size,
+            allocated_size: size.div_ceil(4096).saturating_mul(4096),
+            modified,
+            extension: path.extension().map(|e| e.to_string_lossy().to_lowercase()),
+            path,
+        }
+    }
+
+    fn folder(path: &str) -> FileInfo {
+        FileInfo { is_dir: true, extension: None, ..file(path, 0, None) }
+    }
+
+    fn compute(files: &[FileInfo]) -> Statistics {
+        Statistics::compute(files.iter().cloned(), SizeMode::Apparent, now())
+    }
+
+    fn counts(bins: &[Bin]) -> Vec<(&'static str, usize)> {
+        bins.iter().filter(|bin| bin.count > 0).map(|bin| (bin.label, bin.count)).collect()
+    }
+
+    fn paths(files: &[FileInfo]) -> Vec<String> {
+        files.iter().map(|file| file.name.clone()).collect()
+    }
+
+    #[test]
+    fn size_bins() {
+        let stats = compute(&[
+            file("/a/empty.txt", 0, None),
+            file("/a/one.txt", 1, None),
+            file("/a/under.txt", KB - 1, None),
+            file("/a/kb.txt", KB, None),
+            file("/a/mb.txt", MB, None),
+            file("/a/gb.txt", GB - 1, None),
+        ]);
+        assert_eq!(counts(&stats.size_histogram), [
+            ("Empty", 1),
+            ("Under 1 KB", 2),
+            ("1 KB to 100 KB", 1),
+            ("1 MB to 10 MB", 1),
+            ("100 MB to 1 GB", 1),
+        ]);
+        assert_eq!(stats.size_histogram.len(), SIZE_BINS.len() + 1);
+        assert_eq!(stats.size_histogram[1].size, KB);
+
+        // The last bin has no upper bound
+        let stats = compute(&[file("/a/empty.bin", 0, None), file("/a/huge.bin", u64::MAX, None)]);
+        assert_eq!(counts(&stats.size_histogram), [("Empty", 1), ("1 GB and over", 1)]);
+        assert_eq!(stats.total_size, u64::MAX);
+        assert_eq!(stats.average_size(), u64::MAX / 2);
+    }
+
+    #[test]
+    fn age_bins() {
+        let stats = compute(&[
+            file("/a/now.txt", 1, ago(0)),
+            file("/a/almost_a_day.txt", 1, ago(DAY_SECS - 1)),
+            file("/a/future.txt", 1, Some(now() + Duration::from_secs(DAY_SECS))),
+            file("/a/a_day.txt", 1, ago(DAY_SECS)),
+            file("/a/thirty_days.txt", 1, ago(30 * DAY_SECS)),
+            file("/a/five_years.txt", 1, ago(1826 * DAY_SECS)),
+            file("/a/undated.txt", 1, None),
+        ]);
+        assert_eq!(counts(&stats.age_histogram), [
+            ("Under a day", 3),
+            ("1 to 7 days", 1),
+            ("1 to 6 months", 1),
+            ("Over 5 years", 1),
+            ("No date", 1),
+        ]);
+        assert_eq!(stats.age_histogram.len(), AGE_BINS.len() + 1);
+    }
+
+    #[test]
+    fn largest_are_cut_off() {
+        let files: Vec<FileInfo> = (1..=TOP_FILES as u64 + 2)
+            .map(|size| file(&format!("/a/{}.bin", size), size, None))
+            .chain([file("/a/tie.bin", 12, None)])
+            .collect();
+        let stats = compute(&files);
+        assert_eq!(stats.largest.len(), TOP_FILES);
+        // Of equal sizes the first seen comes first
+        assert_eq!(paths(&stats.largest[..3]), ["12.bin", "tie.bin", "11.bin"]);
+        assert_eq!(stats.largest.last().unwrap().size, 4);
+        assert!(stats.oldest.is_empty() && stats.newest.is_empty());
+    }
+
+    #[test]
+    fn oldest_and_newest_skip_undated_files() {
+        let stats = compute(&[
+            file("/a/old.txt", 1, ago(300 * DAY_SECS)),
+            file("/a/undated.txt", 1, None),
+            file("/a/new.txt", 1, ago(60)),
+            file("/a/middle.txt", 1, ago(DAY_SECS)),
+        ]);
+        assert_eq!(paths(&stats.oldest), ["old.txt", "middle.txt", "new.txt"]);
+        assert_eq!(paths(&stats.newest), ["new.txt", "middle.txt", "old.txt"]);
+    }
+
+    #[test]
+    fn tallies_by_extension_and_category() {
+        let stats = compute(&[
+            file("/a/a.pdf", 100, None),
+            file("/a/b.PDF", 50, None),
+            file("/a/c.jpg", 150, None),
+            file("/a/README", 10, None),
+            file("/a/d.png", 5, None),
+            folder("/a/sub"),
+        ]);
+        assert_eq!((stats.file_count, stats.folder_count, stats.total_size), (5, 1, 315));
+        let tallies = |tallies: &[Tally]| -> Vec<(String, usize, u64)> {
+            tallies.iter().map(|t| (t.name.clone(), t.count, t.size)).collect()
+        };
+        // Biggest first, then the most files
+        assert_eq!(tallies(&stats.by_extension), [
+            ("PDF".to_string(), 2, 150),
+            ("JPG".to_string(), 1, 150),
+            ("(none)".to_string(), 1, 10),
+            ("PNG".to_string(), 1, 5),
+        ]);
+        assert_eq!(tallies(&stats.by_category), [
+            ("Image".to_string(), 2, 155),
+            ("Document".to_string(), 2, 150),
+            ("Other".to_string(), 1, 10),
+        ]);
+    }
+
+    #[test]
+    fn allocated_sizes() {
+        let files = [file("/a/a.txt", 1, None), file("/a/b.txt", 5000, None)];
+        let stats = Statistics::compute(files.iter().cloned(), SizeMode::Allocated, now());
+        assert_eq!(stats.total_size, 3 * 4096);
+        assert_eq!(counts(&stats.size_histogram), [("1 KB to 100 KB", 2)]);
+    }
+
+    #[test]
+    fn json() {
+        let modified = ago(2 * DAY_SECS).unwrap();
+        let stats = compute(&[file("/a/x.pdf", 2048, Some(modified)), file("/a/y", 0, None), folder("/a/sub")]);
+        let bins = |bins: &[(&str, usize, u64)]| -> Vec<Value> {
+            bins.iter().map(|&(label, count, size)| json!({ "label": label, "count": count, "size": size })).collect()
+        };
+        assert_eq!(stats.to_json(), json!({
+            "size_mode": "apparent",
+            "file_count": 2,
+            "folder_count": 1,
+            "total_size": 2048,
+            "average_size": 1024,
+            "by_category": [
+                { "name": "Document", "count": 1, "size": 2048 },
+                { "name": "Other", "count": 1, "size": 0 },
+            ],
+            "by_extension": [
+                { "name": "PDF", "count": 1, "size": 2048 },
+                { "name": "(none)", "count": 1, "size": 0 },
+            ],
+            "largest": [
+                { "path": "/a/x.pdf", "size": 2048, "modified": iso_time(modified) },
+                { "path": "/a/y", "size": 0, "modified": null },
+            ],
+            "oldest": [{ "path": "/a/x.pdf", "size": 2048, "modified": iso_time(modified) }],
+            "newest": [{ "path": "/a/x.pdf", "size": 2048, "modified": iso_time(modified) }],
+            "size_histogram": bins(&[
+                ("Empty", 1, 0),
+                ("Under 1 KB", 0, 0),
+                ("1 KB to 100 KB", 1, 2048),
+                ("100 KB to 1 MB", 0, 0),
+                ("1 MB to 10 MB", 0, 0),
+                ("10 MB to 100 MB", 0, 0),
+                ("100 MB to 1 GB", 0, 0),
+                ("1 GB and over", 0, 0),
+            ]),
+            "age_histogram": bins(&[
+                ("Under a day", 0, 0),
+                ("1 to 7 days", 1, 2048),
+                ("1 to 4 weeks", 0, 0),
+                ("1 to 6 months", 0, 0),
+                ("6 to 12 months", 0, 0),
+                ("1 to 2 years", 0, 0),
+                ("2 to 5 years", 0, 0),
+                ("Over 5 years", 0, 0),
+                ("No date", 1, 0),
+            ]),
+        }));
+    }
+}
@@ -0,0 +1,437 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use crate::normalize;
+use crate::query::TextField;
+use crate::scanner::{FileInfo, FileRecord, SearchKey, SizeMode};
+use crate::trigram::TrigramIndex;
+
+const NO_EXTENSION: u32 = u32::MAX;
+const NO_MODIFIED: i64 = i64::MIN;
+
+const FLAG_DIR: u8 = 0x01;
+const FLAG_SHORTCUT: u8 = 0x02;
+
+// Column-oriented storage for scan results. Parent directories and extensions are
+// interned, names share one string pool, and the numeric fields live in packed
+// arrays, so a whole-drive scan costs a few dozen bytes per file instead of a
+// full `PathBuf` plus two `String`s. Normalized search keys are kept alongside so
+// searching doesn't have to normalize every row on every keystroke.
+#[derive(Default)]
+pub struct FileStore {
+    dirs: Vec<Arc<Path>>,
+    dir_keys: Vec<(Box<str>, usize)>,
+    dir_lookup: HashMap<Arc<Path>, u32>,
+    extensions: Vec<Box<str>>,
+    ext_lookup: HashMap<Box<str>, u32>,
+    names: String,
+    name_ends: Vec<u32>,
+    // Names in `normalize::search_form`, accent-folded unless `match_accents`
+    search_names: String,
+    search_name_ends: Vec<u32>,
+    dir_ids: Vec<u32>,
+    ext_ids: Vec<u32>,
+    sizes: Vec<u64>,
+    allocated: Vec<u64>,
+    mtimes: Vec<i64>,
+    flags: Vec<u8>,
+    // Kept up to date as rows arrive once enabled
+    index: Option<TrigramIndex>,
+    // Whether the search keys keep accents; off folds them away
+    match_accents: bool,
+}
+
+impl FileStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sizes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sizes.is_empty()
+    }
+
+    // Empties the store; the index and accent settings carry over to the next rows
+    pub fn clear(&mut self) {
+        let indexed = self.index.is_some();
+        let match_accents = self.match_accents;
+        *self = Self::default();
+        self.match_accents = match_accents;
+        self.set_indexed(indexed);
+    }
+
+    pub fn match_accents(&self) -> bool {
+        self.match_accents
+    }
+
+    // Recomputes the search keys, and the index built from them, with or without accents
+    pub fn set_match_accents(&mut self, match_accents: bool) {
+        if match_accents == self.match_accents {
+            return;
+        }
+        self.match_accents = match_accents;
+
+        self.dir_keys = self.dirs.iter()
+            .map(|dir| {
+                let (prefix, folder_len) = SearchKey::folder_prefix(dir, !match_accents);
+                (prefix.into(), folder_len)
+            })
+            .collect();
+
+        let mut search_names = String::with_capacity(self.search_names.len());
+        let mut search_name_ends = Vec::with_capacity(self.len());
+        for row in 0..self.len() {
+            search_names.push_str(&normalize::search_form(self.entry(row).name(), !match_accents));
+            search_name_ends.push(u32::try_from(search_names.len()).expect("name pool exceeds 4 GiB"));
+        }
+        self.search_names = search_names;
+        self.search_name_ends = search_name_ends;
+
+        if self.index.is_some() {
+            self.index = None;
+            self.set_indexed(true);
+        }
+    }
+
+    // Builds or drops the trigram index used to narrow text searches
+    pub fn set_indexed(&mut self, enabled: bool) {
+        if !enabled {
+            self.index = None;
+            return;
+        }
+        if self.index.is_some() {
+            return;
+        }
+
+        let mut index = TrigramIndex::new();
+        for (dir, (prefix, _)) in self.dir_keys.iter().enumerate() {
+            index.add_dir(dir as u32, prefix);
+        }
+        for row in 0..self.len() {
+            let (prefix, _) = &self.dir_keys[self.dir_ids[row] as usize];
+            index.add_row(row as u32, &self.entry(row).search_key(!self.match_accents).name, prefix);
+        }
+        self.index = Some(index);
+    }
+
+    // Rows that might contain `text`, in search form, ascending, when the index can
+    // tell; every one still has to be matched
+    pub fn text_candidates(&self, field: TextField, text: &str) -> Option<Vec<usize>> {
+        self.index.as_ref()?.candidates(field, text, &self.dir_ids)
+    }
+
+    pub fn index_memory(&self) -> Option<usize> {
+        self.index.as_ref().map(TrigramIndex::memory_bytes)
+    }
+
+    // Extensions in the scan with how many files have each, most common first
+    pub fn extension_counts(&self) -> Vec<(&str, usize)> {
+        let mut counts = vec![0usize; self.extensions.len()];
+        for &id in self.ext_ids.iter().filter(|&&id| id != NO_EXTENSION) {
+            counts[id as usize] += 1;
+        }
+        let mut result: Vec<(&str, usize)> = self.extensions.iter().map(|ext| &**ext).zip(counts).collect();
+        result.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        result
+    }
+
+    pub fn has_extension(&self, ext: &str) -> bool {
+        self.ext_lookup.contains_key(ext)
+    }
+
+    // The last component of each folder files were found in
+    pub fn folder_names(&self) -> impl Iterator<Item = &str> {
+        self.dirs.iter().filter_map(|dir| dir.file_name()?.to_str())
+    }
+
+    pub fn push(&mut self, file: FileInfo) {
+        let parent = file.path.parent().unwrap_or_else(|| Path::new(""));
+        let dir_id = self.intern_dir(parent);
+        let ext_id = match file.extension {
+            Some(ref ext) => self.intern_extension(ext),
+            None => NO_EXTENSION,
+        };
+
+        let search_name = normalize::search_form(&file.name, !self.match_accents);
+        let row = self.len() as u32;
+        if let Some(index) = &mut self.index {
+            index.add_row(row, &search_name, &self.dir_keys[dir_id as usize].0);
+        }
+
+        self.names.push_str(&file.name);
+        let name_end = u32::try_from(self.names.len()).expect("name pool exceeds 4 GiB");
+        self.name_ends.push(name_end);
+        self.search_names.push_str(&search_name);
+        let search_end = u32::try_from(self.search_names.len()).expect("name pool exceeds 4 GiB");
+        self.search_name_ends.push(search_end);
+        self.dir_ids.push(dir_id);
+        self.ext_ids.push(ext_id);
+        self.sizes.push(file.size);
+        self.allocated.push(file.allocated_size);
+        self.mtimes.push(file.modified.map(to_millis).unwrap_or(NO_MODIFIED));
+
+        let mut flags = 0;
+        if file.is_dir {
+            flags |= FLAG_DIR;
+        }
+        if file.is_shortcut {
+            flags |= FLAG_SHORTCUT;
+        }
+        self.flags.push(flags);
+    }
+
+    pub fn extend(&mut self, files: impl IntoIterator<Item = FileInfo>) {
+        for file in files {
+            self.push(file);
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<FileEntry<'_>> {
+        if index < self.len() {
+            Some(FileEntry { store: self, index })
+        } else {
+            None
+        }
+    }
+
+    // Panics if `index` is out of range, like slice indexing
+    pub fn entry(&self, index: usize) -> FileEntry<'_> {
+        assert!(index < self.len(), "file index {} out of range", index);
+        FileEntry { store: self, index }
+    }
+
+    pub fn total_size(&self, mode: SizeMode) -> u64 {
+        match mode {
+            SizeMode::Apparent => self.sizes.iter().sum(),
+            SizeMode::Allocated => self.allocated.iter().sum(),
+        }
+    }
+
+    fn intern_dir(&mut self, dir: &Path) -> u32 {
+        // Walk order keeps siblings together, so the previous row's directory is the usual hit
+        if let Some(&last) = self.dir_ids.last() {
+            if *self.dirs[last as usize] == *dir {
+                return last;
+            }
+        }
+
+        if let Some(&id) = self.dir_lookup.get(dir) {
+            return id;
+        }
+
+        let id = self.dirs.len() as u32;
+        let (prefix, folder_len) = SearchKey::folder_prefix(dir, !self.match_accents);
+        if let Some(index) = &mut self.index {
+            index.add_dir(id, &prefix);
+        }
+        self.dir_keys.push((prefix.into(), folder_len));
+        let dir: Arc<Path> = Arc::from(dir);
+        self.dirs.push(Arc::clone(&dir));
+        self.dir_lookup.insert(dir, id);
+        id
+    }
+
+    fn intern_extension(&mut self, ext: &str) -> u32 {
+        if let Some(&id) = self.ext_lookup.get(ext) {
+            return id;
+        }
+
+        let id = self.extensions.len() as u32;
+        self.extensions.push(ext.into());
+        self.ext_lookup.insert(ext.into(), id);
+        id
+    }
+}
+
+// A borrowed row of a `FileStore`
+#[derive(Clone, Copy)]
+pub struct FileEntry<'a> {
+    store: &'a FileStore,
+    index: usize,
+}
+
+impl<'a> FileEntry<'a> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    fn flags(&self) -> u8 {
+        self.store.flags[self.index]
+    }
+}
+
+impl<'a> FileRecord for FileEntry<'a> {
+    fn name(&self) -> &str {
+        let start = if self.index == 0 {
+            0
+        } else {
+            self.store.name_ends[self.index - 1] as usize
+        };
+        let end = self.store.name_ends[self.index] as usize;
+        &self.store.names[start..end]
+    }
+
+    fn parent(&self) -> &Path {
+        &self.store.dirs[self.store.dir_ids[self.index] as usize]
+    }
+
+    fn full_path(&self) -> Cow<'_, Path> {
+        Cow::Owned(self.parent().join(self.name()))
+    }
+
+    fn extension(&self) -> Option<&str> {
+        match self.store.ext_ids[self.index] {
+            NO_EXTENSION => None,
+            id => Some(&self.store.extensions[id as usize]),
+        }
+    }
+
+    fn size(&self) -> u64 {
+        self.store.sizes[self.index]
+    }
+
+    fn allocated_size(&self) -> u64 {
+        self.store.allocated[self.index]
+    }
+
+    fn modified(&self) -> Option<SystemTime> {
+        match self.store.mtimes[self.index] {
+            NO_MODIFIED => None,
+            millis => Some(from_millis(millis)),
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        self.flags() & FLAG_DIR != 0
+    }
+
+    fn is_shortcut(&self) -> bool {
+        self.flags() & FLAG_SHORTCUT != 0
+    }
+
+    fn search_key(&self, fold_accents: bool) -> SearchKey<'_> {
+        if fold_accents == self.store.match_accents {
+            return SearchKey::new(self.name(), self.parent(), fold_accents);
+        }
+        let start = if self.index == 0 {
+            0
+        } else {
+            self.store.search_name_ends[self.index - 1] as usize
+        };
+        let end = self.store.search_name_ends[self.index] as usize;
+        let (prefix, folder_len) = &self.store.dir_keys[self.store.dir_ids[self.index] as usize];
+        SearchKey {
+            name: Cow::Borrowed(&self.store.search_names[start..end]),
+            prefix: Cow::Borrowed(prefix),
+            folder_len: *folder_len,
+        }
+    }
+}
+
+fn to_millis(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(d) => d.as_millis().min(i64::MAX as u128) as i64,
+        Err(e) => -(e.duration().as_millis().min(i64::MAX as u128) as i64),
+    }
+}
+
+fn from_millis(millis: i64) -> SystemTime {
+    if millis >= 0 {
+        SystemTime::UNIX_EPOCH + Duration::from_millis(millis as u64)
+    } else {
+        SystemTime::UNIX_EPOCH - Duration::from_millis(millis.unsigned_abs())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use super::*;
+    use crate::filter::Filter;
+
+    fn file(path: &str) -> FileInfo {
+        let path = PathBuf::from(path);
+        FileInfo {
+            name: path.file_name().unwrap().to_string_lossy().into(),
+            is_dir: false,
+            is_shortcut: false,
+            size: 1,
+            allocated_size: 1,
+            modified: None,
+            extension: path.extension().map(|e| e.to_string_lossy().to_lowercase()),
+            path,
+        }
+    }
+
+    fn store(match_accents: bool) -> FileStore {
+        let mut store = FileStore::new();
+        store.set_match_accents(match_accents);
+        store.extend([
+            "/work/Site Plan/drawing.DWG",
+            "/work/site plan/Section A.dwg",
+            "/work/Résumé/CV final.pdf",
+            "/work/resume/cv.pdf",
+            "/work/Café/menu.txt",
+            "/work/cafe/Cafe\u{301} notes.txt",
+            "/archive/2023/plan.dwg",
+            "/archive/ab.c",
+            "/x/y",
+        ].map(file));
+        store.set_indexed(true);
+        store
+    }
+
+    // The rows the index narrows a search to, then matched, against matching every row
+    fn assert_index_agrees(store: &FileStore, text: &str) {
+        let mut filter = Filter::new();
+        filter.set_match_accents(store.match_accents());
+        filter.set_search(text).unwrap();
+        let matches = |rows: Vec<usize>| -> Vec<usize> {
+            rows.into_iter().filter(|&row| filter.score(&store.entry(row)).is_some()).collect()
+        };
+        let linear = matches((0..store.len()).collect());
+        let indexed = matches(filter.candidates(store).unwrap_or_else(|| (0..store.len()).collect()));
+        assert_eq!(indexed, linear, "search {:?}, accents matched: {}", text, store.match_accents());
+    }
+
+    #[test]
+    fn index_agrees_with_linear_filter() {
+        let searches = [
+            // Shorter than a trigram
+            "a", "pl", "n/", "é", "\"\"",
+            // Mixed case
+            "PLAN", "Site Plan", "name:DrAwInG", "path:SITE", "ext:DWG",
+            // Accents, composed and decomposed
+            "resume", "résumé", "Re\u{301}sume\u{301}", "cafe", "café", "name:café",
+            // Across the folder/name boundary
+            "plan/draw", "\"plan/section a\"", "2023/plan", "archive/ab", "/y", "y/x",
+            // Combined with other terms
+            "plan OR cv", "plan -draw", "dwg path:archive", "NOT resume", "*.dwg plan",
+            "nothing-matches-this",
+        ];
+        for match_accents in [false, true] {
+            let store = store(match_accents);
+            for text in searches {
+                assert_index_agrees(&store, text);
+            }
+        }
+    }
+
+    #[test]
+    fn index_is_used_for_longer_terms() {
+        let store = store(false);
+        assert_eq!(store.text_candidates(TextField::Any, "pl"), None);
+        let candidates = store.text_candidates(TextField::Any, "plan/draw").unwrap();
+        assert_eq!(candidates, vec![0]);
+        assert_eq!(store.text_candidates(TextField::Name, "plan").unwrap(), vec![6]);
+        assert_eq!(store.text_candidates(TextField::Path, "site plan").unwrap(), vec![0, 1]);
+        // Accents are folded out of the keys, so either spelling narrows the same way
+        assert_eq!(store.text_candidates(TextField::Path, "resume").unwrap(), vec![2, 3]);
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use serde_json::{json, Value};
+use crate::scanner::{FileRecord, SizeMode};
+
+// A folder in the size breakdown. `size` and `file_count` include everything below it.
+pub struct DirNode {
+    pub name: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub file_count: usize,
+    pub subdirs: Vec<DirNode>,
+    pub files: Vec<FileLeaf>,
+}
+
+pub struct FileLeaf {
+    pub name: String,
+    pub size: u64,
+}
+
+// Flat working copy used while building; children always come after their parent
+struct PendingDir {
+    name: String,
+    path: PathBuf,
+    parent: usize,
+    size: u64,
+    file_count: usize,
+    subdirs: Vec<usize>,
+    files: Vec<FileLeaf>,
+}
+
+impl DirNode {
+    // Aggregates scanned files into a tree rooted at `root`. Files outside `root`
+    // are counted against the root itself.
+    pub fn build<R: FileRecord>(root: &Path, files: impl IntoIterator<Item = R>, mode: SizeMode) -> DirNode {
+        let mut dirs = vec![PendingDir {
+            name: display_name(root),
+            path: root.to_path_buf(),
+            parent: 0,
+            size: 0,
+            file_count: 0,
+            subdirs: Vec::new(),
+            files: Vec::new(),
+        }];
+        let mut lookup: HashMap<PathBuf, usize> = HashMap::new();
+        lookup.insert(root.to_path_buf(), 0);
+
+        for file in files {
+            let index = dir_index(&mut dirs, &mut lookup, root, file.parent());
+            let size = file.size_for(mode);
+            let dir = &mut dirs[index];
+            dir.size += size;
+            dir.file_count += 1;
+            dir.files.push(FileLeaf { name: file.name().to_string(), size });
+        }
+
+        // Roll totals up; walking backwards visits every child before its parent
+        for index in (1..dirs.len()).rev() {
+            let (parent, size, file_count) = (dirs[index].parent, dirs[index].size, dirs[index].file_count);
+            dirs[parent].size += size;
+            dirs[parent].file_count += file_count;
+        }
+
+        let mut slots: Vec<Option<PendingDir>> = dirs.into_iter().map(Some).collect();
+        take_node(&mut slots, 0)
+    }
+
+    // Largest direct child, folder or file
+    pub fn largest_child(&self) -> Option<(&str, u64)> {
+        let largest_dir = self.subdirs.first().map(|d| (d.name.as_str(), d.size));
+        let largest_file = self.files.first().map(|f| (f.name.as_str(), f.size));
+        match (largest_dir, largest_file) {
+            (Some(d), Some(f)) => Some(if f.1 > d.1 { f } else { d }),
+            (d, f) => d.or(f),
+        }
+    }
+
+    // Nested JSON in the `{name, size, children}` shape treemap and sunburst
+    // libraries expect. Only the largest `max_files` files of each folder are
+    // listed; the rest are merged into one "(N smaller files)" entry.
+    pub fn to_json(&self, max_files: usize) -> Value {
+        let mut children: Vec<Value> = self.subdirs.iter()
+            .map(|d| d.to_json(max_files))
+            .collect();
+
+        for file in self.files.iter().take(max_files) {
+            children.push(json!({ "name": file.name, "size": file.size }));
+        }
+        if self.files.len() > max_files {
+            let rest = &self.files[max_files..];
+            children.push(json!({
+                "name": format!("({} smaller files)", rest.len()),
+                "size": rest.iter().map(|f| f.size).sum::<u64>(),
+            }));
+        }
+
+        let largest = self.largest_child()
+            .map(|(name, size)| json!({ "name": name, "size": size }))
+            .unwrap_or(Value::Null);
+
+        json!({
+            "name": self.name,
+            "path": self.path.to_string_lossy(),
+            "size": self.size,
+            "file_count": self.file_count,
+            "largest_child": largest,
+            "children": children,
+        })
+    }
+
+    // Self-contained HTML page that draws the tree as a zoomable treemap
+    pub fn to_html(&self, max_files: usize) -> String {
+        // Keep the embedded JSON from closing the script element early
+        let data = self.to_json(max_files).to_string().replace("</", "<\\/");
+        TREEMAP_TEMPLATE
+            .replace("__TITLE__", &html_escape(&self.path.to_string_lossy()))
+            .replace("__DATA__", &data)
+    }
+}
+
+fn dir_index(dirs: &mut Vec<PendingDir>, lookup: &mut HashMap<PathBuf, usize>, root: &Path, dir: &Path) -> usize {
+    if let Some(&index) = lookup.get(dir) {
+        return index;
+    }
+
+    let parent_path = match dir.parent() {
+        Some(parent) if dir.starts_with(root) => parent,
+        _ => return 0,
+    };
+    let parent = dir_index(dirs, lookup, root, parent_path);
+
+    let index = dirs.len();
+    dirs.push(PendingDir {
+        name: display_name(dir),
+        path: dir.to_path_buf(),
+        parent,
+        size: 0,
+        file_count: 0,
+        subdirs: Vec::new(),
+        files: Vec::new(),
+    });
+    dirs[parent].subdirs.push(index);
+    lookup.insert(dir.to_path_buf(), index);
+    index
+}
+
+fn take_node(slots: &mut [Option<PendingDir>], index: usize) -> DirNode {
+    let pending = slots[index].take().expect("each folder is visited once");
+    let mut subdirs: Vec<DirNode> = pending.subdirs.iter()
+        .map(|&child| take_node(slots, child))
+        .collect();
+    let mut files = pending.files;
+
+    // Biggest first, which is what both the largest-child lookup and the treemap want
+    subdirs.sort_by_key(|dir| std::cmp::Reverse(dir.size));
+    files.sort_by_key(|file| std::cmp::Reverse(file.size));
+
+    DirNode {
+        name: pending.name,
+        path: pending.path,
+        size: pending.size,
+        file_count: pending.file_count,
+        subdirs,
+        files,
+    }
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+pub fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+const TREEMAP_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Size Treemap - __TITLE__</title>
+<style>
+body { margin: 0; font-family: "Segoe UI", Arial, sans-serif; background: #f4f4f4; }
+#bar { height: 28px; padding: 8px 12px; background: #fff; border-bottom: 1px solid #ddd; line-height: 28px; }
+#bar a { color: #0366d6; cursor: pointer; }
+#map { position: absolute; top: 45px; left: 8px; right: 8px; bottom: 8px; }
+.cell { position: absolute; box-sizing: border-box; border: 1px solid #fff; overflow: hidden;
+        padding: 2px 4px; font-size: 12px; color: #fff; cursor: pointer; }
+.cell.file { background: #8a8a8a; cursor: default; }
+</style>
+</head>
+<body>
+<div id="bar"></div>
+<div id="map"></div>
+<script>
+const data = __DATA__;
+const trail = [data];
+
+function formatSize(n) {
+    const units = ["B", "KB", "MB", "GB", "TB"];
+    let i = 0;
+    while (n >= 1024 && i < units.length - 1) { n /= 1024; i++; }
+    return i === 0 ? n + " " + units[i] : n.toFixed(1) + " " + units[i];
+}
+
+// Squarified treemap: fill rows along the short side while the aspect ratio improves
+function worst(row, side, scale) {
+    const areas = row.map(d => d.size * scale);
+    const sum = areas.reduce((a, b) => a + b, 0);
+    return Math.max(side * side * Math.max(...areas) / (sum * sum),
+                    (sum * sum) / (side * side * Math.min(...areas)));
+}
+
+function place(row, box, scale, out) {
+    const sum = row.reduce((a, d) => a + d.size * scale, 0);
+    if (box.w >= box.h) {
+        const width = sum / box.h;
+        let y = box.y;
+        for (const d of row) { const h = d.size * scale / width; out.push({ d, x: box.x, y, w: width, h }); y += h; }
+        return { x: box.x + width, y: box.y, w: box.w - width, h: box.h };
+    }
+    const height = sum / box.w;
+    let x = box.x;
+    for (const d of row) { const w = d.size * scale / height; out.push({ d, x, y: box.y, w, h: height }); x += w; }
+    return { x: box.x, y: box.y + height, w: box.w, h: box.h - height };
+}
+
+function layout(items, box) {
+    const total = items.reduce((a, d) => a + d.size, 0);
+    const scale = box.w * box.h / total;
+    const out = [];
+    let row = [];
+    for (const item of items) {
+        const side = Math.min(box.w, box.h);
+        if (row.length === 0 || worst(row.concat([item]), side, scale) <= worst(row, side, scale)) {
+            row.push(item);
+        } else {
+            box = place(row, box, scale, out);
+            row = [item];
+        }
+    }
+    if (row.length) place(row, box, scale, out);
+    return out;
+}
+
+function render() {
+    const node = trail[trail.length - 1];
+    const bar = document.getElementById("bar");
+    bar.innerHTML = "";
+    trail.forEach((n, i) => {
+        if (i > 0) bar.append(" / ");
+        const link = document.createElement("a");
+        link.textContent = n.name;
+        link.onclick = () => { trail.length = i + 1; render(); };
+        bar.append(link);
+    });
+    bar.append("  -  " + formatSize(node.size) + " in " + node.file_count + " files");
+
+    const map = document.getElementById("map");
+    map.innerHTML = "";
+    const items = (node.children || []).filter(d => d.size > 0).sort((a, b) => b.size - a.size);
+    if (items.length === 0) return;
+
+    layout(items, { x: 0, y: 0, w: map.clientWidth, h: map.clientHeight }).forEach((r, i) => {
+        const cell = document.createElement("div");
+        const isDir = Array.isArray(r.d.children);
+        cell.className = isDir ? "cell" : "cell file";
+        cell.style.left = r.x + "px";
+        cell.style.top = r.y + "px";
+        cell.style.width = r.w + "px";
+        cell.style.height = r.h + "px";
+        if (isDir) cell.style.background = "hsl(" + (i * 47 % 360) + ", 45%, 42%)";
+        cell.title = (r.d.path || r.d.name) + "\n" + formatSize(r.d.size) +
+            (isDir ? "\n" + r.d.file_count + " files" : "");
+        if (r.w > 40 && r.h > 16) cell.textContent = r.d.name + " (" + formatSize(r.d.size) + ")";
+        if (isDir) cell.onclick = () => { trail.push(r.d); render(); };
+        map.append(cell);
+    });
+}
+
+window.onresize = render;
+render();
+</script>
+</body>
+</html>
+"#;
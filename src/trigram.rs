@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+use crate::query::TextField;
+
+// Postings search `MAX_TRIGRAMS` trigrams of a term at most, one bit each; any
+// beyond that are left to the match that verifies every candidate
+const MAX_TRIGRAMS: usize = 64;
+
+// Trigram postings over the lowercase search keys of a `FileStore`, used to cut a
+// plain text search down to the rows that could contain it before each one is
+// matched. Folder trigrams are kept per folder rather than per row, so a file
+// costs only the trigrams of its name plus the two that span the separator.
+#[derive(Default)]
+pub struct TrigramIndex {
+    // Name trigram -> rows, ascending
+    names: HashMap<u64, Vec<u32>>,
+    // Trigrams that start in the folder and end in the name -> rows, ascending
+    joins: HashMap<u64, Vec<u32>>,
+    // Folder trigram -> folder ids, ascending
+    dirs: HashMap<u64, Vec<u32>>,
+    dir_count: usize,
+}
+
+impl TrigramIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `prefix` is the folder's search key, separator included. Folders are added
+    // in id order.
+    pub fn add_dir(&mut self, dir: u32, prefix: &str) {
+        for trigram in trigrams(prefix) {
+            push_unique(self.dirs.entry(trigram).or_default(), dir);
+        }
+        self.dir_count = self.dir_count.max(dir as usize + 1);
+    }
+
+    // Rows are added in order, with the lowercase name and folder prefix keys
+    pub fn add_row(&mut self, row: u32, name: &str, prefix: &str) {
+        for trigram in trigrams(name) {
+            push_unique(self.names.entry(trigram).or_default(), row);
+        }
+
+        let mut tail: Vec<char> = prefix.chars().rev().take(2).collect();
+        tail.reverse();
+        let tail_len = tail.len();
+        let window: Vec<char> = tail.into_iter().chain(name.chars().take(2)).collect();
+        for start in 0..tail_len {
+            if let Some(chars) = window.get(start..start + 3) {
+                push_unique(self.joins.entry(pack(chars)).or_default(), row);
+            }
+        }
+    }
+
+    // Rows whose keys hold every trigram of a lowercase `needle` in `field`, in
+    // ascending order; `None` if the needle is too short to look up. This is a
+    // superset of the matches, so each row still has to be checked.
+    pub fn candidates(&self, field: TextField, needle: &str, dir_ids: &[u32]) -> Option<Vec<usize>> {
+        let mut wanted: Vec<u64> = trigrams(needle).collect();
+        wanted.sort_unstable();
+        wanted.dedup();
+        wanted.truncate(MAX_TRIGRAMS);
+        if wanted.is_empty() {
+            return None;
+        }
+
+        if field == TextField::Name {
+            let mut lists: Vec<&[u32]> = wanted.iter()
+                .map(|trigram| self.names.get(trigram).map_or(&[][..], Vec::as_slice))
+                .collect();
+            lists.sort_by_key(|list| list.len());
+            let mut rows = lists[0].to_vec();
+            for list in &lists[1..] {
+                rows = intersect(&rows, list);
+            }
+            return Some(rows.into_iter().map(|row| row as usize).collect());
+        }
+
+        // Which of the wanted trigrams each folder lacks
+        let all: u64 = if wanted.len() == 64 { u64::MAX } else { (1 << wanted.len()) - 1 };
+        let mut dir_missing = vec![all; self.dir_count];
+        for (bit, trigram) in wanted.iter().enumerate() {
+            for &dir in self.dirs.get(trigram).into_iter().flatten() {
+                dir_missing[dir as usize] &= !(1 << bit);
+            }
+        }
+        let missing = |row: usize| dir_missing.get(dir_ids[row] as usize).copied().unwrap_or(all);
+
+        if field == TextField::Path {
+            return Some((0..dir_ids.len()).filter(|&row| missing(row) == 0).collect());
+        }
+
+        // Anywhere in the full path: the folder, the name or across the separator
+        let mut found = vec![0u64; dir_ids.len()];
+        for (bit, trigram) in wanted.iter().enumerate() {
+            let rows = self.names.get(trigram).into_iter().chain(self.joins.get(trigram)).flatten();
+            for &row in rows {
+                if let Some(mask) = found.get_mut(row as usize) {
+                    *mask |= 1 << bit;
+                }
+            }
+        }
+        Some((0..dir_ids.len()).filter(|&row| missing(row) & !found[row] == 0).collect())
+    }
+
+    // Approximate heap use in bytes
+    pub fn memory_bytes(&self) -> usize {
+        [&self.names, &self.joins, &self.dirs]
+            .iter()
+            .map(|postings| {
+                postings.capacity() * (size_of::<u64>() + size_of::<Vec<u32>>() + 1)
+                    + postings.values().map(|list| list.capacity() * size_of::<u32>()).sum::<usize>()
+            })
+            .sum()
+    }
+}
+
+// Three characters packed into one key; a char fits in 21 bits
+fn pack(chars: &[char]) -> u64 {
+    (chars[0] as u64) << 42 | (chars[1] as u64) << 21 | chars[2] as u64
+}
+
+fn trigrams(text: &str) -> impl Iterator<Item = u64> {
+    let chars: Vec<char> = text.chars().collect();
+    (0..chars.len().saturating_sub(2)).map(move |start| pack(&chars[start..start + 3]))
+}
+
+// Postings are filled in ascending order, so a repeat can only be the last entry
+fn push_unique(list: &mut Vec<u32>, id: u32) {
+    if list.last() != Some(&id) {
+        list.push(id);
+    }
+}
+
+// Merges of two ascending lists
+pub fn intersect<T: Ord + Copy>(a: &[T], b: &[T]) -> Vec<T> {
+    let mut result = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                result.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result
+}
+
+pub fn union<T: Ord + Copy>(a: &[T], b: &[T]) -> Vec<T> {
+    let mut result = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => {
+                result.push(a[i]);
+                i += 1;
+            }
+            std::cmp::Ordering::Greater => {
+                result.push(b[j]);
+                j += 1;
+            }
+            std::cmp::Ordering::Equal => {
+                result.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    result.extend_from_slice(&a[i..]);
+    result.extend_from_slice(&b[j..]);
+    result
+}